@@ -1,20 +1,24 @@
 use anyhow::{Context, Result};
 use axum::{
-    extract::Query,
-    http::StatusCode,
-    response::{Html, Json},
+    extract::{Path, Query, Request},
+    http::{header, Method, StatusCode},
+    middleware::{self, Next},
+    response::{Html, IntoResponse, Json, Response},
     routing::{get, post},
     Router,
 };
 use rust_embed::RustEmbed;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
-use tower_http::cors::CorsLayer;
 use regex::Regex;
 
 use crate::core::{
     config::load_project_config,
     memory::Memory,
+    passage::Passage,
+    query::{Search, SearchMode},
+    ranking::Signals,
+    types::{Conversation, SearchResult, Session, SessionDigest, TextMatch},
     validation::ensure_project_initialized,
 };
 
@@ -39,11 +43,30 @@ struct StatusResponse {
     last_activity: Option<String>,
 }
 
+#[derive(Serialize)]
+struct SessionItem {
+    #[serde(flatten)]
+    session: Session,
+    has_digest: bool,
+}
+
+#[derive(Serialize)]
+struct SessionDetail {
+    id: String,
+    /// None once every conversation was deleted and only the digest remains
+    session: Option<Session>,
+    digest: Option<SessionDigest>,
+    conversations: Vec<Conversation>,
+}
+
 #[derive(Deserialize)]
 struct SearchQuery {
     q: String,
     #[serde(default = "default_limit")]
     limit: usize,
+    /// `keyword` (default), `fuzzy` or `regex`
+    #[serde(default)]
+    mode: SearchMode,
 }
 
 fn default_limit() -> usize {
@@ -65,6 +88,12 @@ struct SearchResultItem {
     assistant_response: String,
     snippet: String,
     highlighted_snippet: String,
+    /// Byte offsets into `user_message` / `assistant_response`
+    matches: Vec<TextMatch>,
+    /// Per-signal breakdown of `score`
+    signals: Signals,
+    /// Matched passage; offsets are into `user_message`, a newline, then `assistant_response`
+    passage: Option<Passage>,
     project_path: Option<String>,
     tags: Vec<String>,
     token_count: usize,
@@ -97,15 +126,48 @@ async fn create_app() -> Result<Router> {
         .route("/api/status", get(api_status))
         .route("/api/search", get(api_search))
         .route("/api/export", post(api_export))
+        .route("/api/sessions", get(api_sessions))
+        .route("/api/sessions/:id", get(api_session).delete(api_delete_session))
         .route("/api/init", post(|| async { api_init().await }))
         .route("/api/clear", post(|| async { api_clear().await }))
         .route("/api/reset", post(|| async { api_reset().await }))
         .route("/static/*file", get(serve_static))
-        .layer(CorsLayer::permissive());
+        .layer(middleware::from_fn(same_origin_only));
     
     Ok(app)
 }
 
+/// Reject state-changing requests made by pages from other origins
+///
+/// The interface is served from this server, so it needs no CORS. Browsers still
+/// send simple cross-site POSTs without asking first, but they always attach an
+/// `Origin` header, which must then name this host. Clients other than browsers
+/// send none and are let through.
+async fn same_origin_only(request: Request, next: Next) -> Result<Response, StatusCode> {
+    if ![Method::GET, Method::HEAD].contains(request.method()) {
+        if let Some(origin) = request.headers().get(header::ORIGIN) {
+            let origin_host = origin.to_str().ok()
+                .and_then(|origin| origin.split_once("://"))
+                .map(|(_, host)| host);
+            let host = request.headers().get(header::HOST).and_then(|host| host.to_str().ok());
+            if origin_host.is_none() || origin_host != host {
+                return Err(StatusCode::FORBIDDEN);
+            }
+        }
+    }
+    Ok(next.run(request).await)
+}
+
+/// Session `id` refers to, like `sessions show`: 404 if none, 409 if ambiguous
+fn resolve_session(memory: &Memory, id: &str) -> Result<String, StatusCode> {
+    let mut matches = crate::commands::sessions::matching_session_ids(memory, id);
+    match matches.len() {
+        0 => Err(StatusCode::NOT_FOUND),
+        1 => Ok(matches.remove(0)),
+        _ => Err(StatusCode::CONFLICT),
+    }
+}
+
 async fn serve_index() -> Result<Html<String>, StatusCode> {
     match WebAssets::get("index.html") {
         Some(content) => {
@@ -191,20 +253,27 @@ async fn api_status() -> Result<Json<StatusResponse>, StatusCode> {
     Ok(Json(response))
 }
 
-async fn api_search(Query(params): Query<SearchQuery>) -> Result<Json<SearchResponse>, StatusCode> {
+async fn api_search(Query(params): Query<SearchQuery>) -> Result<Json<SearchResponse>, Response> {
+    // Same query syntax and modes as `off-context search`, and the same reason when
+    // the query is rejected
+    let search = Search::parse(&params.q, params.mode).map_err(|e| {
+        let body = serde_json::json!({ "error": format!("Invalid search query: {:#}", e) });
+        (StatusCode::BAD_REQUEST, Json(body)).into_response()
+    })?;
+    
     // Reuse logic from search command
     let config = load_project_config()
         .await
-        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR.into_response())?;
     
-    let memory = Memory::new(&config.database)
+    let memory = Memory::new(&config)
         .await
-        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR.into_response())?;
     
     let search_results = memory
-        .search(&params.q, params.limit)
+        .run_search(&search, params.limit)
         .await
-        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR.into_response())?;
     
     let total_conversations = memory
         .conversation_count()
@@ -221,7 +290,10 @@ async fn api_search(Query(params): Query<SearchQuery>) -> Result<Json<SearchResp
                 user_message: conv.user_message.clone(),
                 assistant_response: conv.assistant_response.clone(),
                 snippet: r.snippet.clone(),
-                highlighted_snippet: highlight_search_terms(&r.snippet, &params.q),
+                highlighted_snippet: highlight_matches(&r.snippet, &r),
+                matches: r.matches.clone(),
+                signals: r.signals,
+                passage: r.passage,
                 project_path: conv.metadata.project_path.clone(),
                 tags: conv.metadata.tags.clone(),
                 token_count: conv.metadata.token_count,
@@ -238,6 +310,80 @@ async fn api_search(Query(params): Query<SearchQuery>) -> Result<Json<SearchResp
     Ok(Json(response))
 }
 
+async fn api_sessions() -> Result<Json<Vec<SessionItem>>, StatusCode> {
+    let config = load_project_config()
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    
+    let memory = Memory::new(&config)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    
+    let sessions = memory
+        .sessions()
+        .into_iter()
+        .map(|session| SessionItem {
+            has_digest: memory.session_digest(&session.id).is_some(),
+            session,
+        })
+        .collect();
+    
+    Ok(Json(sessions))
+}
+
+async fn api_session(Path(id): Path<String>) -> Result<Json<SessionDetail>, StatusCode> {
+    let config = load_project_config()
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    
+    let memory = Memory::new(&config)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    
+    let id = resolve_session(&memory, &id)?;
+    let session = memory.session(&id);
+    let digest = memory.session_digest(&id);
+    
+    Ok(Json(SessionDetail {
+        conversations: memory.session_conversations(&id),
+        id,
+        session,
+        digest,
+    }))
+}
+
+async fn api_delete_session(Path(id): Path<String>) -> Result<Json<serde_json::Value>, StatusCode> {
+    let config = load_project_config()
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    
+    let memory = Memory::new(&config)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    
+    let id = resolve_session(&memory, &id)?;
+    
+    let deleted = memory
+        .delete_session(&id)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    if let Ok(config_dir) = crate::core::config::project_config_dir() {
+        crate::core::session_state::SessionStateStore::new(config_dir)
+            .forget(&id)
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    }
+    memory
+        .compact()
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    
+    Ok(Json(serde_json::json!({
+        "success": true,
+        "session_id": id,
+        "deleted_conversations": deleted
+    })))
+}
+
 async fn api_export(
     Json(payload): Json<HashMap<String, String>>,
 ) -> Result<Json<serde_json::Value>, StatusCode> {
@@ -248,7 +394,7 @@ async fn api_export(
         .await
         .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
     
-    let memory = Memory::new(&config.database)
+    let memory = Memory::new(&config)
         .await
         .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
     
@@ -259,12 +405,15 @@ async fn api_export(
         .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
     
     // Convert to SearchResult format for compatibility with export functions
-    let search_results: Vec<crate::core::types::SearchResult> = all_conversations
+    let search_results: Vec<SearchResult> = all_conversations
         .into_iter()
-        .map(|conv| crate::core::types::SearchResult {
+        .map(|conv| SearchResult {
             conversation: conv,
             score: 1.0, // Perfect score since we want all conversations
             snippet: "Full conversation".to_string(), // Not used in export
+            matches: Vec::new(),
+            signals: Signals::default(),
+            passage: None,
         })
         .collect();
     
@@ -287,7 +436,7 @@ async fn api_export(
 
 async fn api_init() -> Result<Json<serde_json::Value>, StatusCode> {
     println!("API init called");
-    match crate::commands::init::handle_init().await {
+    match crate::commands::init::handle_init(crate::hooks::SettingsScope::Local, false, false).await {
         Ok(_) => Ok(Json(serde_json::json!({
             "success": true,
             "message": "Project initialized successfully"
@@ -303,7 +452,7 @@ async fn api_init() -> Result<Json<serde_json::Value>, StatusCode> {
 }
 
 async fn api_clear() -> Result<Json<serde_json::Value>, StatusCode> {
-    match crate::commands::clear::handle_clear().await {
+    match crate::commands::clear::handle_clear(crate::hooks::SettingsScope::Local, false, false, false).await {
         Ok(_) => Ok(Json(serde_json::json!({
             "success": true,
             "message": "Project hooks cleared successfully"
@@ -348,7 +497,7 @@ async fn reset_memory_only() -> Result<()> {
     println!("Resetting off-context memory...");
     
     // Clear database
-    match Memory::new(&config.database).await {
+    match Memory::new(&config).await {
         Ok(memory) => {
             memory.clear().await.context("Failed to clear memory database")?;
             println!("Database cleared successfully");
@@ -359,45 +508,31 @@ async fn reset_memory_only() -> Result<()> {
         }
     }
     
-    // Clean up session injection tracking files
+    // Forget which sessions already received context
     if let Ok(config_dir) = project_config_dir() {
-        if let Ok(entries) = std::fs::read_dir(&config_dir) {
-            for entry in entries.flatten() {
-                let path = entry.path();
-                if let Some(name) = path.file_name() {
-                    if name.to_string_lossy().starts_with("session_injected_") {
-                        match tokio::fs::remove_file(&path).await {
-                            Ok(_) => println!("Session tracking file removed: {}", name.to_string_lossy()),
-                            Err(_) => println!("Could not remove session file: {}", name.to_string_lossy()),
-                        }
-                    }
-                }
-            }
+        match crate::core::session_state::SessionStateStore::new(config_dir).clear() {
+            Ok(count) => println!("Session tracking state removed ({} sessions)", count),
+            Err(e) => println!("Could not remove session tracking state: {}", e),
         }
     }
     
     Ok(())
 }
 
-/// Highlight search terms in text with HTML <mark> tags
-fn highlight_search_terms(text: &str, query: &str) -> String {
-    if query.trim().is_empty() {
-        return text.to_string();
-    }
-    
-    // Split query into individual words and escape special regex characters
-    let terms: Vec<String> = query
-        .split_whitespace()
-        .filter(|term| !term.is_empty())
-        .map(|term| regex::escape(term))
-        .collect();
+/// Highlight the text a search matched with HTML <mark> tags
+fn highlight_matches(text: &str, result: &SearchResult) -> String {
+    // Longest first, so a match is not cut short by one of its prefixes
+    let mut terms: Vec<&str> = result.matches.iter().map(|m| m.text(&result.conversation)).collect();
+    terms.sort_by(|a, b| b.len().cmp(&a.len()).then(a.cmp(b)));
+    terms.dedup();
     
     if terms.is_empty() {
         return text.to_string();
     }
     
-    // Create a regex pattern that matches any of the terms (case insensitive)
-    let pattern = format!("(?i)({})", terms.join("|"));
+    // Create a regex pattern that matches any of the matched strings
+    let terms: Vec<String> = terms.into_iter().map(regex::escape).collect();
+    let pattern = format!("({})", terms.join("|"));
     
     match Regex::new(&pattern) {
         Ok(re) => {
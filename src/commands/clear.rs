@@ -1,31 +1,63 @@
 use anyhow::Result;
-use serde_json::{json, Value};
+use std::io::{self, Write};
+use crate::commands::init::preview_settings;
+use crate::core::config::find_project_root;
 use crate::core::validation::ensure_project_initialized;
+use crate::hooks::settings::{SettingsFile, SettingsScope};
 
-pub async fn handle_clear() -> Result<()> {
+/// Remove off-context's hooks from one settings scope, or with `all` from every one
+///
+/// The shared project and user settings reach beyond this checkout, so clearing
+/// them all at once asks first unless `yes` is given.
+pub async fn handle_clear(scope: SettingsScope, all: bool, yes: bool, dry_run: bool) -> Result<()> {
     // Ensure we're in a project
     ensure_project_initialized()?;
-    let project_root = std::env::current_dir()?;
-    let claude_dir = project_root.join(".claude");
-    let settings_file = claude_dir.join("settings.local.json");
+    let project_root = match find_project_root() {
+        Some(root) => root,
+        None => std::env::current_dir()?,
+    };
 
-    if !settings_file.exists() {
-        println!("off-context: No settings.local.json found in the project.");
-        return Ok(());
+    let scopes = if all { SettingsScope::ALL.to_vec() } else { vec![scope] };
+
+    if all && !yes && !dry_run {
+        print!("⚠️ This removes off-context hooks from the shared project settings and from your user settings for every project. Continue? (y/N): ");
+        io::stdout().flush()?;
+
+        let mut input = String::new();
+        io::stdin().read_line(&mut input)?;
+
+        if input.trim().to_lowercase() != "y" {
+            println!("❌ Clear cancelled");
+            return Ok(());
+        }
     }
-    let content = std::fs::read_to_string(&settings_file)?;
-    let mut existing: Value = serde_json::from_str(&content).unwrap_or(json!({}));
-    let mut changed = false;
-    if let Some(obj) = existing.as_object_mut() {
-        if obj.remove("hooks").is_some() {
-            changed = true;
+
+    for scope in scopes {
+        let settings_file = scope.path(&project_root)?;
+        if !settings_file.exists() {
+            println!("off-context: No {} settings file ({})", scope, settings_file.display());
+            continue;
         }
+
+        // Only off-context's own hooks go; everything else stays as configured
+        let mut settings = SettingsFile::load(&settings_file)?;
+        let removed = settings.remove_hooks();
+        if removed == 0 {
+            println!("off-context: No off-context hooks found in {}", settings_file.display());
+            continue;
+        }
+
+        if dry_run {
+            preview_settings(&settings);
+            continue;
+        }
+
+        settings.save()?;
+        println!("off-context: {} hooks removed from {}", removed, settings_file.display());
     }
-    if changed {
-        std::fs::write(&settings_file, serde_json::to_string_pretty(&existing)?)?;
-        println!("off-context: hooks removed from {}", settings_file.display());
-    } else {
-        println!("off-context: No hooks block found in {}", settings_file.display());
+
+    if dry_run {
+        println!("off-context: dry run, nothing written");
     }
     Ok(())
-} 
\ No newline at end of file
+}
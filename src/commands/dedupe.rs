@@ -0,0 +1,288 @@
+use anyhow::{Context, Result};
+use std::collections::HashMap;
+use uuid::Uuid;
+
+use crate::core::{
+    config::load_project_config,
+    hash::fnv1a,
+    memory::Memory,
+    preview::line_preview,
+    types::Conversation,
+    validation::ensure_project_initialized,
+};
+
+/// Identity a conversation can share with its duplicates
+#[derive(Debug, PartialEq, Eq, Hash)]
+enum DedupeKey {
+    Id(Uuid),
+    Content(u64),
+}
+
+/// One set of duplicates collapsed into a single conversation
+struct Merge {
+    survivor: Conversation,
+    /// Ids to delete once the survivor is stored
+    removed: Vec<Uuid>,
+    copies: usize,
+}
+
+pub async fn handle_dedupe(dry_run: bool) -> Result<()> {
+    // Ensure we're in a project
+    ensure_project_initialized()?;
+
+    let config = load_project_config().await.context("Failed to load configuration")?;
+    let memory = Memory::new(&config).await
+        .context("Failed to initialize memory store")?;
+
+    let conversations = memory.all_conversations().await?;
+    let scanned = conversations.len();
+    let merges = plan_merges(conversations);
+
+    let duplicate_groups: Vec<&Merge> = merges.iter().filter(|m| m.copies > 1).collect();
+    let duplicates: usize = duplicate_groups.iter().map(|m| m.copies - 1).sum();
+    let rekeyed = merges.iter().filter(|m| m.copies == 1).count();
+
+    println!("🧹 off-context Dedupe (Project-local)");
+    println!("━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━");
+    println!("📊 Scanned {} conversations", scanned);
+    println!("   🔁 Duplicate groups: {}", duplicate_groups.len());
+    println!("   🗑️ Duplicates to remove: {}", duplicates);
+    println!("   🔑 Conversations moved to stable ids: {}", rekeyed);
+
+    if !duplicate_groups.is_empty() {
+        println!();
+        for merge in duplicate_groups.iter().take(10) {
+            println!("   • {} ({} copies)", line_preview(&merge.survivor.user_message, 60), merge.copies);
+        }
+        if duplicate_groups.len() > 10 {
+            println!("   … and {} more", duplicate_groups.len() - 10);
+        }
+    }
+
+    if merges.is_empty() {
+        println!();
+        println!("✅ Nothing to do");
+        return Ok(());
+    }
+
+    if dry_run {
+        println!();
+        println!("💡 Dry run - nothing was changed. Re-run without --dry-run to apply.");
+        return Ok(());
+    }
+
+    for merge in &merges {
+        // Store the survivor first so a crash leaves duplicates, never a gap
+        memory.store_conversation(&merge.survivor).await
+            .context("Failed to store merged conversation")?;
+        for id in &merge.removed {
+            memory.delete_conversation(id).await
+                .context("Failed to delete duplicate conversation")?;
+        }
+    }
+
+    println!();
+    println!("✅ Dedupe complete");
+    if let Ok(total) = memory.conversation_count().await {
+        println!("   📚 Conversations in database: {}", total);
+    }
+
+    Ok(())
+}
+
+/// Group duplicates and decide what each group collapses into
+///
+/// Conversations are duplicates when they share an id, a stable id (same session and
+/// source message) or the same session and exchange text. Groups that are already a
+/// single conversation under its stable id need no change and are left out.
+fn plan_merges(conversations: Vec<Conversation>) -> Vec<Merge> {
+    let mut parent: Vec<usize> = (0..conversations.len()).collect();
+    let mut owners: HashMap<DedupeKey, usize> = HashMap::new();
+
+    for (i, conversation) in conversations.iter().enumerate() {
+        let keys = [
+            DedupeKey::Id(conversation.id),
+            DedupeKey::Id(conversation.stable_id()),
+            DedupeKey::Content(content_key(conversation)),
+        ];
+        for key in keys {
+            match owners.get(&key) {
+                Some(&j) => union(&mut parent, i, j),
+                None => {
+                    owners.insert(key, i);
+                }
+            }
+        }
+    }
+
+    let mut groups: HashMap<usize, Vec<Conversation>> = HashMap::new();
+    for (i, conversation) in conversations.into_iter().enumerate() {
+        let root = find(&mut parent, i);
+        groups.entry(root).or_default().push(conversation);
+    }
+
+    let mut merges: Vec<Merge> = groups.into_values().filter_map(merge_group).collect();
+    merges.sort_by_key(|m| m.survivor.timestamp);
+    merges
+}
+
+fn merge_group(mut group: Vec<Conversation>) -> Option<Merge> {
+    // Prefer copies that know their source message, then the newest, then the longest
+    group.sort_by(|a, b| {
+        let rank = |c: &Conversation| {
+            (c.metadata.source_uuid.is_some(), c.timestamp, c.assistant_response.len())
+        };
+        rank(b).cmp(&rank(a))
+    });
+
+    let mut survivor = group[0].clone();
+    for other in &group[1..] {
+        extend_unique(&mut survivor.metadata.tags, &other.metadata.tags);
+        extend_unique(&mut survivor.metadata.tools_used, &other.metadata.tools_used);
+        extend_unique(&mut survivor.metadata.files_touched, &other.metadata.files_touched);
+        extend_unique(&mut survivor.metadata.files_mentioned, &other.metadata.files_mentioned);
+        extend_unique(&mut survivor.metadata.languages, &other.metadata.languages);
+        extend_unique(&mut survivor.metadata.symbols, &other.metadata.symbols);
+        survivor.metadata.pinned |= other.metadata.pinned;
+    }
+    survivor.id = survivor.stable_id();
+
+    if group.len() == 1 && group[0].id == survivor.id {
+        return None;
+    }
+
+    let removed = group.iter().map(|c| c.id).filter(|id| *id != survivor.id).collect();
+    Some(Merge { survivor, removed, copies: group.len() })
+}
+
+fn content_key(conversation: &Conversation) -> u64 {
+    fnv1a(format!(
+        "{}\u{0}{}\u{0}{}",
+        conversation.metadata.session_id.as_deref().unwrap_or(""),
+        conversation.user_message.trim(),
+        conversation.assistant_response.trim()
+    ).as_bytes())
+}
+
+fn extend_unique(target: &mut Vec<String>, values: &[String]) {
+    for value in values {
+        if !target.contains(value) {
+            target.push(value.clone());
+        }
+    }
+}
+
+fn find(parent: &mut [usize], mut i: usize) -> usize {
+    while parent[i] != i {
+        parent[i] = parent[parent[i]];
+        i = parent[i];
+    }
+    i
+}
+
+fn union(parent: &mut [usize], a: usize, b: usize) {
+    let (a, b) = (find(parent, a), find(parent, b));
+    if a != b {
+        parent[a] = b;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn exchange(timestamp: &str, user: &str, assistant: &str) -> Conversation {
+        Conversation::sample("session-a", timestamp, user, assistant)
+    }
+
+    /// A copy stored by an older version under a random id
+    fn legacy(mut conversation: Conversation) -> Conversation {
+        conversation.id = Uuid::new_v4();
+        conversation
+    }
+
+    /// A copy parsed from the transcript line `source`
+    fn from_line(mut conversation: Conversation, source: &str) -> Conversation {
+        conversation.metadata.source_uuid = Some(source.to_string());
+        conversation.id = conversation.stable_id();
+        conversation
+    }
+
+    #[test]
+    fn distinct_conversations_under_stable_ids_need_nothing() {
+        let a = exchange("2026-09-01T10:00:00Z", "first", "answer");
+        let b = exchange("2026-09-01T10:05:00Z", "second", "answer");
+        let mut other_session = a.clone();
+        other_session.metadata.session_id = Some("session-b".to_string());
+        other_session.id = other_session.stable_id();
+
+        assert!(plan_merges(vec![a, b, other_session]).is_empty());
+    }
+
+    #[test]
+    fn lone_legacy_conversation_moves_to_its_stable_id() {
+        let old = legacy(exchange("2026-09-01T10:00:00Z", "first", "answer"));
+        let merges = plan_merges(vec![old.clone()]);
+        assert_eq!(merges.len(), 1);
+        assert_eq!(merges[0].copies, 1);
+        assert_eq!(merges[0].survivor.id, old.stable_id());
+        assert_eq!(merges[0].removed, [old.id]);
+    }
+
+    #[test]
+    fn duplicates_are_grouped_transitively() {
+        let text = exchange("2026-09-01T10:00:00Z", "deploy?", "Run make deploy.");
+        // Same text as the legacy copy, same source line as the longer one
+        let legacy_copy = legacy(text.clone());
+        let parsed = from_line(text.clone(), "u-1");
+        let mut grown = from_line(exchange("2026-09-01T10:00:30Z", "deploy?", "Run make deploy, then check the logs."), "u-1");
+        grown.id = Uuid::new_v4();
+
+        let merges = plan_merges(vec![legacy_copy.clone(), grown.clone(), parsed.clone()]);
+        assert_eq!(merges.len(), 1);
+        let merge = &merges[0];
+        assert_eq!(merge.copies, 3);
+        // Knows its source line and is the newest
+        assert_eq!(merge.survivor.assistant_response, grown.assistant_response);
+        assert_eq!(merge.survivor.id, parsed.id);
+        let mut removed = merge.removed.clone();
+        removed.sort();
+        let mut expected = vec![legacy_copy.id, grown.id];
+        expected.sort();
+        assert_eq!(removed, expected);
+    }
+
+    #[test]
+    fn survivor_unions_metadata_and_keeps_pins() {
+        let mut pinned = legacy(exchange("2026-09-01T10:00:00Z", "deploy?", "Run make deploy."));
+        pinned.metadata.pinned = true;
+        pinned.metadata.tags = vec!["deploy".to_string(), "ops".to_string()];
+        pinned.metadata.files_touched = vec!["Makefile".to_string()];
+        pinned.metadata.symbols = vec!["deploy".to_string()];
+
+        let mut parsed = from_line(pinned.clone(), "u-1");
+        parsed.metadata.pinned = false;
+        parsed.metadata.tags = vec!["ops".to_string(), "release".to_string()];
+        parsed.metadata.files_touched = Vec::new();
+        parsed.metadata.languages = vec!["bash".to_string()];
+
+        let merges = plan_merges(vec![pinned, parsed.clone()]);
+        assert_eq!(merges.len(), 1);
+        let survivor = &merges[0].survivor;
+        assert_eq!(survivor.id, parsed.id);
+        assert!(survivor.metadata.pinned);
+        assert_eq!(survivor.metadata.tags, ["ops", "release", "deploy"]);
+        assert_eq!(survivor.metadata.files_touched, ["Makefile"]);
+        assert_eq!(survivor.metadata.languages, ["bash"]);
+        assert_eq!(survivor.metadata.symbols, ["deploy"]);
+    }
+
+    #[test]
+    fn merges_are_ordered_by_survivor_time() {
+        let later = legacy(exchange("2026-09-02T10:00:00Z", "later", "answer"));
+        let earlier = legacy(exchange("2026-09-01T10:00:00Z", "earlier", "answer"));
+        let merges = plan_merges(vec![later, earlier]);
+        let prompts: Vec<&str> = merges.iter().map(|m| m.survivor.user_message.as_str()).collect();
+        assert_eq!(prompts, ["earlier", "later"]);
+    }
+}
@@ -0,0 +1,122 @@
+use anyhow::{bail, Context, Result};
+use clap::Subcommand;
+use std::sync::Arc;
+
+use crate::core::{
+    config::{load_project_config, save_project_config},
+    crypto::{self, NEW_PASSPHRASE_ENV, PASSPHRASE_ENV},
+    memory::Memory,
+    store::legacy_backup_path,
+    validation::ensure_project_initialized,
+};
+
+#[derive(Subcommand)]
+pub enum EncryptionAction {
+    /// Encrypt the project memory store
+    Enable {
+        /// Where the key comes from: key_file (~/.off-context/keys) or passphrase
+        #[arg(long, default_value = "key_file")]
+        key_source: String,
+    },
+
+    /// Re-encrypt every record under a fresh key and retire the old one
+    Rotate {
+        /// Switch key source while rotating (key_file or passphrase)
+        #[arg(long)]
+        key_source: Option<String>,
+    },
+
+    /// Decrypt the store back to plaintext and forget its keys
+    Disable,
+}
+
+pub async fn handle_encryption(action: EncryptionAction) -> Result<()> {
+    // Ensure we're in a project
+    ensure_project_initialized()?;
+
+    let mut config = load_project_config().await.context("Failed to load configuration")?;
+
+    println!("🔐 off-context Encryption (Project-local)");
+    println!("━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━");
+
+    match action {
+        EncryptionAction::Enable { key_source } => {
+            let db_path = std::path::PathBuf::from(&config.database.path);
+            if crypto::status(&db_path)?.is_some() {
+                println!("✅ Memory store is already encrypted");
+                println!("💡 Use `off-context encryption rotate` to change its key");
+                return Ok(());
+            }
+
+            config.encryption.enabled = true;
+            config.encryption.key_source = key_source;
+            let backup = legacy_backup_path(&db_path.join("conversations.json"));
+            let had_backup = backup.exists();
+            // Opening the store with encryption enabled sets up the key and rewrites it
+            let memory = Memory::new(&config).await
+                .context("Failed to encrypt memory store")?;
+            save_project_config(&config).await?;
+
+            println!("✅ Encrypted {} conversations", memory.conversation_count().await?);
+            if had_backup && !backup.exists() {
+                println!("🗑️ Removed plaintext migration backup {}", backup.display());
+            }
+            print_key_hint(&config.encryption.key_source);
+        }
+        EncryptionAction::Rotate { key_source } => {
+            let mut memory = Memory::new(&config).await
+                .context("Failed to initialize memory store")?;
+            if memory.cipher().is_none() {
+                bail!("Memory store is not encrypted; run `off-context encryption enable` first");
+            }
+            if let Some(key_source) = key_source {
+                config.encryption.key_source = key_source;
+            }
+            config.encryption.enabled = true;
+
+            // The header lists old and new key until every record is rewritten, so an
+            // interrupted rotation leaves a readable store
+            let cipher = crypto::begin_rotation(memory.base_path(), &config.encryption)?;
+            memory.reencrypt(Some(Arc::new(cipher))).await
+                .context("Failed to re-encrypt memory store")?;
+            let in_use = memory.encryption_keys_in_use().await?;
+            crypto::finish_rotation(memory.base_path(), &in_use)?;
+            save_project_config(&config).await?;
+
+            println!("✅ Re-encrypted {} conversations under a new key", memory.conversation_count().await?);
+            if config.encryption.key_source == "passphrase" {
+                println!("💡 From now on set {} to the value of {}", PASSPHRASE_ENV, NEW_PASSPHRASE_ENV);
+            }
+        }
+        EncryptionAction::Disable => {
+            let mut memory = Memory::new(&config).await
+                .context("Failed to initialize memory store")?;
+            let cipher = match memory.cipher() {
+                Some(cipher) => cipher.decrypt_only(),
+                None => {
+                    println!("✅ Memory store is not encrypted");
+                    return Ok(());
+                }
+            };
+
+            memory.reencrypt(Some(Arc::new(cipher))).await
+                .context("Failed to decrypt memory store")?;
+            let in_use = memory.encryption_keys_in_use().await?;
+            crypto::remove(memory.base_path(), &in_use)?;
+            config.encryption.enabled = false;
+            save_project_config(&config).await?;
+
+            println!("✅ Decrypted {} conversations; keys removed", memory.conversation_count().await?);
+        }
+    }
+
+    Ok(())
+}
+
+fn print_key_hint(key_source: &str) {
+    if key_source == "passphrase" {
+        println!("💡 Keep {} set for every off-context process (hooks included)", PASSPHRASE_ENV);
+    } else if let Ok(dir) = crate::core::config::config_dir() {
+        println!("💡 Key stored under {}; back it up, the store is unreadable without it", dir.join("keys").display());
+    }
+}
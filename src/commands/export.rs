@@ -1,7 +1,7 @@
 use anyhow::{Context, Result};
 use chrono::DateTime;
 
-use crate::core::{config::load_project_config, memory::Memory, types::Conversation, validation::ensure_project_initialized};
+use crate::core::{config::load_project_config, memory::Memory, query::Expr, types::Conversation, validation::ensure_project_initialized};
 
 pub async fn handle_export(format: &str, output: Option<&str>) -> Result<()> {
     // Ensure we're in a project
@@ -10,24 +10,22 @@ pub async fn handle_export(format: &str, output: Option<&str>) -> Result<()> {
     println!("📤 Exporting project conversations...");
     println!("📋 Format: {}", format);
     
-    let output_file = output.unwrap_or_else(|| {
-        match format {
-            "json" => "conversations.json",
-            "md" => "conversations.md",
-            _ => "conversations.txt",
-        }
+    let output_file = output.unwrap_or(match format {
+        "json" => "conversations.json",
+        "md" => "conversations.md",
+        _ => "conversations.txt",
     });
     
     println!("📁 Output: {}", output_file);
     
     // Load configuration and initialize memory
     let config = load_project_config().await.context("Failed to load configuration")?;
-    let memory = Memory::new(&config.database).await
+    let memory = Memory::new(&config).await
         .context("Failed to initialize memory store")?;
     
-    // Get all conversations via search (empty query returns all)
+    // Get all conversations via search, newest first
     let search_results = memory
-        .search("", 10000) // Large limit to get all conversations
+        .search(&Expr::all(), 10000) // Large limit to get all conversations
         .await
         .context("Failed to retrieve conversations")?;
     
@@ -141,7 +139,7 @@ pub fn export_as_text(search_results: &[crate::core::types::SearchResult]) -> Re
                                 i + 1, 
                                 format_timestamp(&conversation.timestamp)));
         content.push_str(&"-".repeat(50));
-        content.push_str("\n");
+        content.push('\n');
         
         // Metadata
         if let Some(project_path) = &conversation.metadata.project_path {
@@ -159,14 +157,14 @@ pub fn export_as_text(search_results: &[crate::core::types::SearchResult]) -> Re
         for line in conversation.user_message.lines() {
             content.push_str(&format!("> {}\n", line));
         }
-        content.push_str("\n");
+        content.push('\n');
         
         // Assistant response
         content.push_str("ASSISTANT:\n");
         for line in conversation.assistant_response.lines() {
             content.push_str(&format!("< {}\n", line));
         }
-        content.push_str("\n");
+        content.push('\n');
         
         if i < search_results.len() - 1 {
             content.push_str(&"=".repeat(50));
@@ -1,12 +1,19 @@
 use anyhow::{Context, Result};
+use std::path::Path;
 use tracing::{debug, warn};
 
 use crate::core::{
     config::{load_project_config, is_in_project},
+    ingest::ingest_transcript,
     memory::Memory,
-    parser::parse_transcript,
+    redact::Redactor,
+    retention,
+    summarize,
 };
 
+/// Most session digests written by a single hook run
+const HOOK_DIGEST_LIMIT: usize = 3;
+
 /// Handle hook processing - called by Claude Code hooks
 pub async fn handle_hook(transcript_path: &str) -> Result<()> {
     debug!("🪝 Processing Claude Code hook: {}", transcript_path);
@@ -23,30 +30,27 @@ pub async fn handle_hook(transcript_path: &str) -> Result<()> {
     // Load configuration
     let config = load_project_config().await.context("Failed to load configuration")?;
     
-    // Parse transcript file to extract conversations
-    let conversations = parse_transcript(transcript_path).await
-        .context("Failed to parse transcript file")?;
-    
-    if conversations.is_empty() {
-        debug!("No conversations found in transcript");
-        return Ok(());
-    }
-    
-    let conversation_count = conversations.len();
-    
     // Initialize memory store
-    match Memory::new(&config.database).await {
+    match Memory::new(&config).await {
         Ok(memory) => {
-            // Store each conversation
-            for conversation in conversations {
-                if let Err(e) = memory.store_conversation(&conversation).await {
-                    warn!("Failed to store conversation {}: {}", conversation.id, e);
-                    // Continue processing other conversations
-                }
-            }
+            // Only the part of the transcript not yet seen is parsed and stored
+            let redactor = Redactor::new(&config.redaction)?;
+            let stored = ingest_transcript(&memory, &redactor, Path::new(transcript_path)).await
+                .context("Failed to ingest transcript file")?;
             
             let duration = start.elapsed();
-            debug!("Stored {} conversations in {:?}", conversation_count, duration);
+            debug!("Stored {} new conversations in {:?}", stored, duration);
+            
+            // Enforce retention only when the store actually grew
+            if stored > 0 {
+                if let Err(e) = retention::apply(&memory, &config.retention).await {
+                    warn!("Failed to apply retention rules: {}", e);
+                }
+                // Digest sessions that have gone idle, a few per run to keep the hook short
+                if let Err(e) = summarize::summarize_sessions(&memory, &config.summarization, false, Some(HOOK_DIGEST_LIMIT)).await {
+                    warn!("Failed to summarize sessions: {}", e);
+                }
+            }
         }
         Err(e) => {
             warn!("Failed to initialize memory store: {}", e);
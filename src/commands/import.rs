@@ -5,8 +5,9 @@ use walkdir::WalkDir;
 
 use crate::core::{
     config::load_project_config,
+    ingest::ingest_transcript,
     memory::Memory,
-    parser::parse_transcript,
+    redact::Redactor,
     validation::ensure_project_initialized,
 };
 
@@ -45,8 +46,9 @@ pub async fn handle_import(path: Option<&str>) -> Result<()> {
     
     // Initialize memory and configuration
     let config = load_project_config().await.context("Failed to load configuration")?;
-    let memory = Memory::new(&config.database).await
+    let memory = Memory::new(&config).await
         .context("Failed to initialize memory store")?;
+    let redactor = Redactor::new(&config.redaction)?;
     
     let mut total_conversations = 0;
     let mut processed_files = 0;
@@ -57,7 +59,7 @@ pub async fn handle_import(path: Option<&str>) -> Result<()> {
     for (i, transcript_file) in transcript_files.iter().enumerate() {
         let progress = format!("[{}/{}]", i + 1, transcript_files.len());
         
-        match process_transcript_file(&memory, transcript_file).await {
+        match process_transcript_file(&memory, &redactor, transcript_file).await {
             Ok(conversation_count) => {
                 if conversation_count > 0 {
                     println!("  {} ✅ {}: {} conversations", 
@@ -106,21 +108,9 @@ pub async fn handle_import(path: Option<&str>) -> Result<()> {
     Ok(())
 }
 
-async fn process_transcript_file(memory: &Memory, file_path: &Path) -> Result<usize> {
-    let conversations = parse_transcript(&file_path.to_string_lossy()).await
-        .context("Failed to parse transcript")?;
-    
-    if conversations.is_empty() {
-        return Ok(0);
-    }
-    
-    // Store conversations
-    for conversation in &conversations {
-        memory.store_conversation(conversation).await
-            .context("Failed to store conversation")?;
-    }
-    
-    Ok(conversations.len())
+async fn process_transcript_file(memory: &Memory, redactor: &Redactor, file_path: &Path) -> Result<usize> {
+    // Checkpointed, so files imported before only contribute what was added since
+    ingest_transcript(memory, redactor, file_path).await
 }
 
 fn find_transcript_files(base_path: &Path) -> Result<Vec<PathBuf>> {
@@ -151,8 +141,9 @@ fn find_transcript_files(base_path: &Path) -> Result<Vec<PathBuf>> {
 
 fn is_transcript_file(path: &Path) -> bool {
     if let Some(filename) = path.file_name().and_then(|f| f.to_str()) {
-        // Look for common Claude Code transcript patterns
-        filename.ends_with(".json") && (
+        // Look for common Claude Code transcript patterns: `<session id>.jsonl`
+        // under `~/.claude/projects`, or exported `.json` conversations
+        (filename.ends_with(".jsonl") || filename.ends_with(".json")) && (
             filename.contains("transcript") ||
             filename.contains("conversation") ||
             filename.contains("claude") ||
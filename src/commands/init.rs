@@ -1,49 +1,56 @@
-use anyhow::Result;
+use anyhow::{bail, Result};
 use std::fs;
-use serde_json::{json, Value};
 use crate::core::config::load_project_config;
+use crate::commands::setup;
+use crate::hooks::{hook_command, settings::SettingsFile, SettingsScope, REGISTERED_EVENTS};
 
-pub async fn handle_init() -> Result<()> {
-    let hooks_dir = dirs::home_dir().unwrap().join(".config/claude/hooks");
-    let user_hook = hooks_dir.join("UserPromptSubmit.sh");
-    let stop_hook = hooks_dir.join("Stop.sh");
+pub async fn handle_init(scope: SettingsScope, wrappers: bool, dry_run: bool) -> Result<()> {
+    if wrappers && scope.is_shared() {
+        bail!("--wrappers cannot be used with --scope {}: wrapper paths only exist on this machine", scope);
+    }
     let project_root = std::env::current_dir()?;
-    let claude_dir = project_root.join(".claude");
-    let settings_file = claude_dir.join("settings.local.json");
+    let settings_file = scope.path(&project_root)?;
 
-    fs::create_dir_all(&claude_dir)?;
+    // off-context's hook for each event
+    let mut commands = Vec::new();
+    for event in REGISTERED_EVENTS {
+        let command = if wrappers {
+            setup::hook_wrapper_path(event)?.to_string_lossy().into_owned()
+        } else {
+            hook_command(event, scope)
+        };
+        commands.push((event, command));
+    }
 
-    // Generate hooks block
-    let hooks_json = json!({
-        "UserPromptSubmit": [
-            { "hooks": [ { "type": "command", "command": user_hook.to_string_lossy() } ] }
-        ],
-        "Stop": [
-            { "hooks": [ { "type": "command", "command": stop_hook.to_string_lossy() } ] }
-        ]
-    });
+    // Merge into the existing settings, leaving other hooks alone
+    let mut settings = SettingsFile::load(&settings_file)?;
+    settings.install_hooks(&commands)?;
 
-    // If it already exists, merge while preserving other configs
-    let merged: Value = if settings_file.exists() {
-        let content = fs::read_to_string(&settings_file)?;
-        let mut existing: Value = serde_json::from_str(&content).unwrap_or(json!({}));
-        // Remove old hooks
-        if let Some(obj) = existing.as_object_mut() {
-            obj.remove("hooks");
-        }
-        // Add new hooks
-        if let Some(obj) = existing.as_object_mut() {
-            obj.insert("hooks".to_string(), Value::Object(hooks_json.as_object().unwrap().clone()));
-        }
-        existing
+    if dry_run {
+        preview_settings(&settings);
+        println!("off-context: dry run, nothing written");
+        return Ok(());
+    }
+
+    if wrappers {
+        setup::write_hook_wrappers().await?;
+    }
+    if settings.is_changed() {
+        settings.save()?;
+        println!("off-context: hooks configured in {}", settings_file.display());
     } else {
-        let mut obj = serde_json::Map::new();
-        obj.insert("hooks".to_string(), Value::Object(hooks_json.as_object().unwrap().clone()));
-        Value::Object(obj)
-    };
+        println!("off-context: hooks already configured in {}", settings_file.display());
+    }
 
-    fs::write(&settings_file, serde_json::to_string_pretty(&merged)?)?;
-    println!("off-context: hooks configured in {}", settings_file.display());
+    // Hooks in two scopes would run twice per event
+    for other in SettingsScope::ALL.into_iter().filter(|s| *s != scope) {
+        let path = other.path(&project_root)?;
+        let installed = path.exists() && SettingsFile::load(&path).is_ok_and(|s| !s.installed_events().is_empty());
+        if installed {
+            println!("⚠️ off-context hooks are also in {} ({}); remove them with: off-context clear --scope {}",
+                path.display(), other, other);
+        }
+    }
     
     // Create .off-context directory in project root
     let off_context_dir = project_root.join(".off-context");
@@ -75,4 +82,16 @@ pub async fn handle_uninstall() -> Result<()> {
     }
     println!("If desired, remove the global binary with: sudo rm /usr/local/bin/off-context");
     Ok(())
-} 
\ No newline at end of file
+} 
+
+/// Print what writing `settings` would change
+pub fn preview_settings(settings: &SettingsFile) {
+    if !settings.is_changed() {
+        println!("off-context: {} is up to date", settings.path().display());
+        return;
+    }
+    println!("off-context: would update {}", settings.path().display());
+    for line in settings.diff() {
+        println!("{}", line);
+    }
+}
@@ -1,11 +1,21 @@
 use anyhow::{Context, Result};
+use serde::Serialize;
+use std::collections::HashSet;
 use std::io::{self, Read};
-use tracing::warn;
+use tracing::{debug, warn};
+use uuid::Uuid;
 
 use crate::core::{
-    config::{load_project_config, is_in_project},
+    config::{load_project_config, is_in_project, project_config_dir},
+    context::ContextBuilder,
     memory::Memory,
+    passage::Passage,
+    query::{Expr, Role},
+    session_state::SessionStateStore,
+    summarize::render_digest,
+    types::{Config, ContextConfig, Conversation, SessionDigest},
 };
+use crate::hooks::payload::{HookEvent, SessionStart, UserPromptSubmit};
 
 /// Handle context injection - called by UserPromptSubmit hook
 pub async fn handle_inject(query: &str) -> Result<()> {
@@ -43,7 +53,7 @@ pub async fn handle_inject(query: &str) -> Result<()> {
 
 pub async fn inject_context_internal(query: &str) -> Result<String> {
     // Try to parse the prompt JSON
-    let prompt_json: serde_json::Value = match serde_json::from_str(query) {
+    let mut prompt_json: serde_json::Value = match serde_json::from_str(query) {
         Ok(val) => val,
         Err(_) => return Ok(query.to_string()), // If not JSON, return the original text
     };
@@ -60,99 +70,291 @@ pub async fn inject_context_internal(query: &str) -> Result<String> {
     if !config.hooks.auto_inject {
         return Ok(query.to_string());
     }
-    let memory = match Memory::new(&config.database).await {
-        Ok(memory) => memory,
-        Err(_) => {
-            return Ok(query.to_string());
-        }
+    let block = match previous_session_context(&config, current_session_id.as_deref(), &HashSet::new()).await? {
+        Some(injection) => injection.block,
+        None => return Ok(query.to_string()),
     };
-    // Search all saved conversations
-    let all_convs = memory.all_conversations().await.unwrap_or_default();
-    // Group by session_id and sort by timestamp
-    use std::collections::BTreeMap;
-    let mut sessions: BTreeMap<String, Vec<&crate::core::types::Conversation>> = BTreeMap::new();
-    for conv in &all_convs {
-        if let Some(sid) = &conv.metadata.session_id {
-            sessions.entry(sid.clone()).or_default().push(conv);
-        }
-    }
-    // Sort sessions by timestamp of last conversation
-    let mut session_vec: Vec<_> = sessions.into_iter().collect();
-    session_vec.sort_by_key(|(_, v)| v.last().map(|c| c.timestamp));
-    // Find sessions different from current one, sorted by timestamp
-    let mut prev_session: Option<&Vec<&crate::core::types::Conversation>> = None;
-    
-    if let Some(current_sid) = &current_session_id {
-        // Get the most recent session that's not the current one
-        for (sid, convs) in session_vec.iter().rev() {
-            if sid != current_sid {
-                prev_session = Some(convs);
-                break;
+    // Replace the .prompt field in JSON with the expanded prompt
+    if let Some(prompt_field) = prompt_json.get_mut("prompt") {
+        *prompt_field = serde_json::Value::String(format!("{}\n\n{}", block, prompt_field.as_str().unwrap_or("")));
+    }
+    let result = serde_json::to_string(&prompt_json).unwrap_or(query.to_string());
+    Ok(result)
+}
+
+/// Where the injected memory for a UserPromptSubmit hook comes from
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ContextSource {
+    /// Past exchanges ranked against the prompt (`inject-prompt`)
+    Relevant,
+    /// The last few exchanges of the previous session (`smart-inject`)
+    PreviousSession,
+}
+
+/// A memory block and the conversations it was built from
+struct Injection {
+    block: String,
+    conversations: Vec<Uuid>,
+}
+
+/// Structured UserPromptSubmit / SessionStart hook output
+///
+/// See Claude Code's hook documentation: `additionalContext` is added to the
+/// conversation as context, leaving the user's prompt untouched.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ContextOutput {
+    #[serde(rename = "continue")]
+    pub continue_processing: bool,
+    pub suppress_output: bool,
+    pub hook_specific_output: ContextSpecificOutput,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ContextSpecificOutput {
+    pub hook_event_name: &'static str,
+    pub additional_context: String,
+}
+
+impl ContextOutput {
+    pub fn with_context(event: HookEvent, context: String) -> Self {
+        Self {
+            continue_processing: true,
+            suppress_output: true,
+            hook_specific_output: ContextSpecificOutput {
+                hook_event_name: event.name(),
+                additional_context: context,
+            },
+        }
+    }
+}
+
+/// Respond to a UserPromptSubmit hook payload
+///
+/// Whether memory is injected is decided by the session's injection state: the
+/// first prompt of a session always gets it, later ones only under the
+/// `hooks.reinject_*` policies, and never the same conversation twice.
+///
+/// Returns what should be written to stdout, if anything. By default the memory is
+/// emitted as `hookSpecificOutput.additionalContext`; with `hooks.prompt_output =
+/// "legacy"` it is glued onto the prompt text as before (`Relevant` echoes the
+/// prompt, `PreviousSession` echoes the payload with its `prompt` field rewritten).
+pub async fn respond_to_prompt(
+    input: &UserPromptSubmit,
+    payload: &serde_json::Value,
+    source: ContextSource,
+) -> Option<String> {
+    let prompt = input.prompt.as_str();
+    let session_id = input.common.session_id();
+
+    let config = if is_in_project() {
+        match load_project_config().await {
+            Ok(config) => Some(config),
+            Err(e) => {
+                warn!("Failed to load configuration, skipping injection: {}", e);
+                None
             }
         }
     } else {
-        // If no current session_id, get the most recent session
-        prev_session = session_vec.last().map(|(_, v)| v);
-    }
-    // Build memory block
-    let mut instruction_block = String::from("[INSTRUCTION]\n");
-    if let Some(convs) = prev_session {
-        let n = 3;
-        for conv in convs.iter().rev().take(n).rev() {
-            instruction_block.push_str(&format!(
-                "Remember: in the last conversation, you answered \"{}\" to the question \"{}\".\n",
-                conv.assistant_response, conv.user_message
-            ));
+        None
+    };
+    let legacy = config.as_ref().is_some_and(|c| c.hooks.prompt_output == "legacy");
+
+    let context = match &config {
+        Some(config) if config.hooks.auto_inject && !prompt.is_empty() => {
+            inject_for_session(config, source, prompt, session_id).await
         }
+        _ => None,
+    };
+
+    if legacy {
+        let enhanced = match &context {
+            Some(block) => format!("{}\n\n{}", block, prompt),
+            None => prompt.to_string(),
+        };
+        return Some(match source {
+            ContextSource::Relevant => enhanced,
+            ContextSource::PreviousSession => {
+                let mut payload = payload.clone();
+                if payload.get("prompt").is_some() {
+                    payload["prompt"] = serde_json::Value::String(enhanced);
+                }
+                payload.to_string()
+            }
+        });
     }
-    instruction_block.push_str("[/INSTRUCTION]\n\n");
-    // Replace the .prompt field in JSON with the expanded prompt
-    let mut new_json = prompt_json.clone();
-    if let Some(prompt_field) = new_json.get_mut("prompt") {
-        *prompt_field = serde_json::Value::String(instruction_block.clone() + prompt_field.as_str().unwrap_or(""));
-    }
-    let result = serde_json::to_string(&new_json).unwrap_or(query.to_string());
-    Ok(result)
+
+    let output = ContextOutput::with_context(HookEvent::UserPromptSubmit, context?);
+    serde_json::to_string(&output).ok()
 }
 
-/// Simple context injection for UserPromptSubmit hook
-pub async fn inject_context_simple(prompt: &str) -> Result<String> {
-    // Only inject if we're in a project - otherwise pass through original query
+/// Respond to a SessionStart hook payload
+///
+/// Recalls the previous session up front. A session restarted after compaction
+/// gets it again, since the compaction summary may have dropped it; a resumed
+/// session that already had it does not. Prompts still get relevant memory on
+/// top, minus the conversations recalled here.
+pub async fn respond_to_session_start(input: &SessionStart) -> Result<Option<String>> {
     if !is_in_project() {
-        return Ok(prompt.to_string());
+        return Ok(None);
     }
-    
-    // Load configuration
     let config = load_project_config().await.context("Failed to load configuration")?;
     if !config.hooks.auto_inject {
-        return Ok(prompt.to_string());
+        return Ok(None);
+    }
+
+    let session_id = input.common.session_id().filter(|id| !id.is_empty());
+    let sessions = SessionStateStore::new(project_config_dir()?);
+    let mut claim = None;
+    let mut already_injected = HashSet::new();
+    if let Some(session_id) = session_id {
+        claim = sessions.check_in_start(session_id, input.source == "compact", &config.hooks)?;
+        if claim.is_none() {
+            debug!("Session {} already recalled the previous session", session_id);
+            return Ok(None);
+        }
+        if let Some(state) = sessions.get(session_id)? {
+            already_injected.extend(state.injected_conversations);
+        }
+    }
+
+    let injection = previous_session_context(&config, session_id, &already_injected).await?;
+    if let Some((session_id, claim)) = session_id.zip(claim.as_ref()) {
+        match &injection {
+            Some(injection) => sessions.record_injected(session_id, &injection.conversations)?,
+            None => sessions.release(session_id, claim)?,
+        }
     }
+    let injection = match injection {
+        Some(injection) => injection,
+        None => return Ok(None),
+    };
 
-    let memory = match Memory::new(&config.database).await {
+    let output = ContextOutput::with_context(HookEvent::SessionStart, injection.block);
+    Ok(Some(serde_json::to_string(&output)?))
+}
+
+/// Build the memory block for a prompt if the session's state calls for one
+async fn inject_for_session(
+    config: &Config,
+    source: ContextSource,
+    prompt: &str,
+    session_id: Option<&str>,
+) -> Option<String> {
+    let sessions = project_config_dir().ok().map(SessionStateStore::new);
+    let session = session_id.filter(|id| !id.is_empty()).zip(sessions.as_ref());
+
+    // Without a session id or a state store every prompt is treated as a first one
+    let mut already_injected = HashSet::new();
+    let mut claim = None;
+    if let Some((session_id, sessions)) = session {
+        match sessions.check_in(session_id, prompt, &config.hooks) {
+            Ok(Some(granted)) => {
+                debug!("Injecting into session {}: {:?}", session_id, granted.reason);
+                claim = Some(granted);
+            }
+            Ok(None) => return None,
+            Err(e) => warn!("Failed to read session state, injecting anyway: {}", e),
+        }
+        if let Ok(Some(state)) = sessions.get(session_id) {
+            already_injected.extend(state.injected_conversations);
+        }
+    }
+
+    let result = match source {
+        ContextSource::Relevant => relevant_context(config, prompt, session_id, &already_injected).await,
+        ContextSource::PreviousSession => previous_session_context(config, session_id, &already_injected).await,
+    };
+    let injection = result.unwrap_or_else(|e| {
+        warn!("Context injection failed: {}", e);
+        None
+    });
+
+    if let Some((session_id, sessions)) = session {
+        let recorded = match (&injection, &claim) {
+            (Some(injection), _) => sessions.record_injected(session_id, &injection.conversations),
+            (None, Some(claim)) => sessions.release(session_id, claim),
+            (None, None) => Ok(()),
+        };
+        if let Err(e) = recorded {
+            warn!("Failed to record injected conversations: {}", e);
+        }
+    }
+    injection.map(|injection| injection.block)
+}
+
+/// Memory block of past exchanges relevant to `prompt`, within the token budget
+///
+/// Exchanges from other sessions that have a digest are represented by the digest,
+/// which goes first; the rest are injected as raw exchanges, and so is the matched
+/// passage of a long exchange (after its digest) and any exchange whose digest did
+/// not fit. Conversations in `exclude` (already injected into this session) are left out.
+async fn relevant_context(
+    config: &Config,
+    prompt: &str,
+    current_session_id: Option<&str>,
+    exclude: &HashSet<Uuid>,
+) -> Result<Option<Injection>> {
+    let memory = match Memory::new(config).await {
         Ok(memory) => {
             memory
         },
         Err(_) => {
-            return Ok(prompt.to_string());
+            return Ok(None);
         },
     };
 
-    // Get last few conversations from memory
-    let all_convs = memory.all_conversations().await.unwrap_or_default();
+    // Get conversations from memory
+    let all_convs: Vec<Conversation> = memory.all_conversations().await.unwrap_or_default()
+        .into_iter()
+        .filter(|c| !exclude.contains(&c.id))
+        .collect();
     if all_convs.is_empty() {
-        return Ok(prompt.to_string());
+        return Ok(None);
     }
 
-    // Sort by timestamp and get latest conversations
-    let mut sorted_convs = all_convs;
-    sorted_convs.sort_by_key(|c| c.timestamp);
+    // Rank against the prompt, topped up with the most recent exchanges
+    let selected = select_context(&memory, all_convs, prompt, &config.context, exclude).await;
     
-    // Take only last 2 conversations to reduce token usage
-    let recent_convs: Vec<_> = sorted_convs.iter().rev().take(2).rev().collect();
+    // Exchanges from other sessions that have a digest are represented by it, except
+    // a passage matched inside a long exchange, which the digest does not carry
+    let mut digests: Vec<(SessionDigest, Vec<Uuid>)> = Vec::new();
+    if config.summarization.enabled {
+        for (conv, passage) in &selected {
+            let digest = conv.metadata.session_id.as_deref()
+                .filter(|sid| Some(*sid) != current_session_id)
+                .and_then(|sid| memory.session_digest(sid));
+            let Some(digest) = digest else { continue };
+            let index = match digests.iter().position(|(d, _)| d.session_id == digest.session_id) {
+                Some(index) => index,
+                None => {
+                    digests.push((digest, Vec::new()));
+                    digests.len() - 1
+                }
+            };
+            if !passage.is_some_and(|p| p.count > 1) {
+                digests[index].1.push(conv.id);
+            }
+        }
+    }
+    let represented: usize = digests.iter().map(|(_, ids)| ids.len()).sum();
     
-    let mut context_block = String::from("[PREV: ");
-    let mut first = true;
-    for conv in recent_convs {
+    let mut injected = Vec::new();
+    let mut builder = ContextBuilder::new(
+        config.context.max_tokens,
+        digests.len() + selected.len() - represented,
+        "; ",
+    );
+    builder.reserve("[PREV: ]\n\n");
+    // Digests go first; the exchanges of one that does not fit are injected raw instead
+    for (digest, ids) in &digests {
+        if builder.push_entry(&render_digest(digest).lines().collect::<Vec<_>>().join(" ")) {
+            injected.extend(ids);
+        }
+    }
+    let raw = selected.iter().filter(|(conv, _)| !injected.contains(&conv.id)).collect::<Vec<_>>();
+    for (conv, passage) in raw {
         // Clean user message from all log artifacts and system noise
         let clean_user_msg = conv.user_message
             .replace("<user-prompt-submit-hook>", "")
@@ -164,27 +366,182 @@ pub async fn inject_context_simple(prompt: &str) -> Result<String> {
             .replace("[0m", "")
             .replace("[32m", "")
             .trim()
-            .chars().take(80).collect::<String>();
+            .to_string();
         
-        let clean_assistant_msg = conv.assistant_response
+        // Of a long answer, only the passage that matched
+        let answer = match passage {
+            Some(passage) if passage.count > 1 && !passage.side(conv, Role::Assistant).trim().is_empty() => {
+                passage.side(conv, Role::Assistant)
+            }
+            _ => conv.assistant_response.as_str(),
+        };
+        let clean_assistant_msg = answer
             .replace("<user-prompt-submit-hook>", "")
             .replace("[2m", "")
             .replace("[0m", "")
             .replace("[32m", "")
             .trim()
-            .chars().take(200).collect::<String>();
+            .to_string();
             
         if !clean_user_msg.is_empty() && !clean_assistant_msg.is_empty() {
-            if !first {
-                context_block.push_str("; ");
+            let pushed = builder.push_exchange(&clean_user_msg, &clean_assistant_msg, |question, answer| {
+                format!("U:\"{}\" A:\"{}\"", question, answer)
+            });
+            if pushed {
+                injected.push(conv.id);
             }
-            context_block.push_str(&format!("U:\"{}\" A:\"{}\"", clean_user_msg, clean_assistant_msg));
-            first = false;
         }
     }
-    context_block.push_str("]\n\n");
     
-    Ok(format!("{}{}", context_block, prompt))
+    let packed = builder.finish();
+    if packed.is_empty() {
+        return Ok(None);
+    }
+    debug!(
+        "Injected {} of {} selected entries using {}/{} tokens",
+        packed.entries.len(),
+        digests.len() + selected.len(),
+        packed.tokens_used,
+        config.context.max_tokens
+    );
+    
+    Ok(Some(Injection {
+        block: format!("[PREV: {}]", packed.join()),
+        conversations: injected,
+    }))
+}
+
+/// Memory block recalling the most recent other session
+///
+/// Uses the session's digest when it has one, otherwise its last few exchanges.
+/// Conversations in `exclude` (already injected into this session) are left out,
+/// and so is a digest whose conversations all were.
+async fn previous_session_context(
+    config: &Config,
+    current_session_id: Option<&str>,
+    exclude: &HashSet<Uuid>,
+) -> Result<Option<Injection>> {
+    let memory = match Memory::new(config).await {
+        Ok(memory) => memory,
+        Err(_) => {
+            return Ok(None);
+        }
+    };
+    let sessions = memory.sessions();
+    let digests = if config.summarization.enabled { memory.session_digests() } else { Vec::new() };
+    
+    // Most recent session other than the current one; a digest can outlive the
+    // conversations it summarizes
+    let mut candidates: Vec<(&str, chrono::DateTime<chrono::Utc>)> = sessions
+        .iter()
+        .map(|session| (session.id.as_str(), session.ended_at))
+        .collect();
+    candidates.extend(
+        digests.iter()
+            .filter(|d| !sessions.iter().any(|s| s.id == d.session_id))
+            .map(|d| (d.session_id.as_str(), d.ended_at)),
+    );
+    let prev_session = candidates
+        .into_iter()
+        .filter(|(sid, _)| Some(*sid) != current_session_id)
+        .max_by_key(|(_, ended_at)| *ended_at)
+        .map(|(sid, _)| sid);
+    let prev_session = match prev_session {
+        Some(sid) => sid,
+        None => return Ok(None),
+    };
+    
+    let session_convs = memory.session_conversations(prev_session);
+    let convs: Vec<&Conversation> = session_convs.iter().filter(|c| !exclude.contains(&c.id)).collect();
+    if !session_convs.is_empty() && convs.is_empty() {
+        debug!("Session {} was already injected", prev_session);
+        return Ok(None);
+    }
+
+    if let Some(digest) = digests.iter().find(|d| d.session_id == prev_session) {
+        let mut builder = ContextBuilder::new(config.context.max_tokens, 1, "");
+        builder.reserve("[INSTRUCTION]\nRemember the previous session.\n[/INSTRUCTION]\n\n");
+        builder.push_entry(&render_digest(digest));
+        let packed = builder.finish();
+        if !packed.is_empty() {
+            debug!("Packed digest of session {} into {} tokens", prev_session, packed.tokens_used);
+            return Ok(Some(Injection {
+                block: format!(
+                    "[INSTRUCTION]\nRemember the previous session.\n{}\n[/INSTRUCTION]",
+                    packed.join().trim_end()
+                ),
+                conversations: convs.iter().map(|c| c.id).collect(),
+            }));
+        }
+    }
+    
+    // Build memory block within the token budget
+    let n = 3;
+    let mut builder = ContextBuilder::new(config.context.max_tokens, convs.len().min(n), "");
+    builder.reserve("[INSTRUCTION]\n[/INSTRUCTION]\n\n");
+    let mut injected = Vec::new();
+    for conv in convs.iter().rev().take(n).rev() {
+        let pushed = builder.push_exchange(&conv.user_message, &conv.assistant_response, |question, answer| {
+            format!(
+                "Remember: in the last conversation, you answered \"{}\" to the question \"{}\".\n",
+                answer, question
+            )
+        });
+        if pushed {
+            injected.push(conv.id);
+        }
+    }
+    let packed = builder.finish();
+    if packed.is_empty() {
+        return Ok(None);
+    }
+    debug!("Packed {} exchanges into {} tokens", packed.entries.len(), packed.tokens_used);
+    Ok(Some(Injection {
+        block: format!("[INSTRUCTION]\n{}[/INSTRUCTION]", packed.join()),
+        conversations: injected,
+    }))
+}
+
+/// Pick the exchanges to inject for `prompt`, with the passage that matched
+///
+/// Best matches from `Memory::search` come first; any remaining slots up to
+/// `max_results` are filled with the most recent exchanges.
+async fn select_context(
+    memory: &Memory,
+    mut all_convs: Vec<Conversation>,
+    prompt: &str,
+    context: &ContextConfig,
+    exclude: &HashSet<Uuid>,
+) -> Vec<(Conversation, Option<Passage>)> {
+    // Over-fetch so hits already injected into the session don't crowd out new ones
+    let relevant = match memory.search(&Expr::text(prompt), context.max_results + exclude.len()).await {
+        Ok(results) => results,
+        Err(e) => {
+            warn!("Context search failed, using recent conversations only: {}", e);
+            Vec::new()
+        }
+    };
+    
+    let mut seen = HashSet::new();
+    let mut selected = Vec::new();
+    for result in relevant {
+        if !exclude.contains(&result.conversation.id) && seen.insert(result.conversation.id) {
+            selected.push((result.conversation, result.passage));
+        }
+    }
+    
+    all_convs.sort_by_key(|c| std::cmp::Reverse(c.timestamp));
+    for conv in all_convs {
+        if selected.len() >= context.max_results {
+            break;
+        }
+        if seen.insert(conv.id) {
+            selected.push((conv, None));
+        }
+    }
+    
+    selected.truncate(context.max_results);
+    selected
 }
 
 /// Check if the query looks like a simple command that doesn't need context
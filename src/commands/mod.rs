@@ -4,6 +4,13 @@ pub mod search;
 pub mod reset;
 pub mod import;
 pub mod export;
+pub mod dedupe;
+pub mod scan;
+pub mod prune;
+pub mod pin;
+pub mod summarize;
+pub mod sessions;
+pub mod encryption;
 pub mod hook;
 pub mod inject;
 pub mod init;
@@ -0,0 +1,45 @@
+use anyhow::{bail, Context, Result};
+
+use crate::core::{
+    config::load_project_config,
+    memory::Memory,
+    validation::ensure_project_initialized,
+};
+
+/// Pin (or unpin) a conversation so retention rules never prune it
+///
+/// `id` may be any unique prefix of the conversation id shown by `search`.
+pub async fn handle_pin(id: &str, unpin: bool) -> Result<()> {
+    // Ensure we're in a project
+    ensure_project_initialized()?;
+
+    let config = load_project_config().await.context("Failed to load configuration")?;
+    let memory = Memory::new(&config).await
+        .context("Failed to initialize memory store")?;
+
+    let prefix = id.trim().to_lowercase();
+    if prefix.is_empty() {
+        bail!("Conversation id must not be empty");
+    }
+    let mut matches: Vec<_> = memory.all_conversations().await?
+        .into_iter()
+        .filter(|c| c.id.to_string().starts_with(&prefix))
+        .collect();
+
+    let conversation = match matches.len() {
+        0 => bail!("No conversation with id {}", id),
+        1 => matches.remove(0),
+        n => bail!("Id prefix {} matches {} conversations; use more characters", id, n),
+    };
+
+    let action = if unpin { "Unpinned" } else { "Pinned" };
+    let changed = memory.set_pinned(&conversation.id, !unpin).await
+        .context("Failed to update conversation")?;
+    if !changed {
+        println!("✅ Conversation {} is already {}", conversation.id, action.to_lowercase());
+        return Ok(());
+    }
+
+    println!("📌 {} conversation {}", action, conversation.id);
+    Ok(())
+}
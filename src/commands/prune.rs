@@ -0,0 +1,97 @@
+use anyhow::{Context, Result};
+
+use crate::core::{
+    config::load_project_config,
+    memory::Memory,
+    preview::line_preview,
+    retention::{self, PruneReason},
+    validation::ensure_project_initialized,
+};
+
+/// Delete conversations (and the digests of sessions left empty) that fall outside
+/// the project's retention rules
+pub async fn handle_prune(dry_run: bool) -> Result<()> {
+    // Ensure we're in a project
+    ensure_project_initialized()?;
+
+    let config = load_project_config().await.context("Failed to load configuration")?;
+    let memory = Memory::new(&config).await
+        .context("Failed to initialize memory store")?;
+    let rules = &config.retention;
+
+    println!("✂️ off-context Prune (Project-local)");
+    println!("━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━");
+    if !retention::is_configured(rules) {
+        println!("⚠️ No retention rules set. Add them to .off-context/config.toml:");
+        println!();
+        println!("   [retention]");
+        println!("   max_age_days = 90");
+        println!("   max_conversations = 5000");
+        println!("   max_store_mb = 100");
+        return Ok(());
+    }
+
+    println!("📏 Rules:");
+    if let Some(days) = rules.max_age_days {
+        println!("   ⏰ Max age: {} days", days);
+    }
+    if let Some(max) = rules.max_conversations {
+        println!("   💾 Max conversations: {}", max);
+    }
+    if let Some(mb) = rules.max_store_mb {
+        println!("   📦 Max store size: {}MB", mb);
+    }
+    println!("   📌 Keep pinned: {}", if rules.keep_pinned { "yes" } else { "no" });
+
+    let plan = retention::plan_for(&memory, rules).await?;
+    let candidates = &plan.conversations;
+    let scanned = memory.conversation_count().await?;
+
+    println!();
+    println!("📊 Scanned {} conversations", scanned);
+    for reason in [PruneReason::Age, PruneReason::Count, PruneReason::Size] {
+        let count = candidates.iter().filter(|c| c.reason == reason).count();
+        if count > 0 {
+            println!("   🗑️ {} {}", count, reason.label());
+        }
+    }
+    if !plan.digests.is_empty() {
+        println!("   🗑️ {} session digests with no conversations left", plan.digests.len());
+    }
+
+    if plan.is_empty() {
+        println!();
+        println!("✅ Nothing to prune");
+        return Ok(());
+    }
+
+    println!();
+    for candidate in candidates {
+        let conversation = &candidate.conversation;
+        println!(
+            "   • {} {} ({})",
+            conversation.timestamp.format("%Y-%m-%d"),
+            line_preview(&conversation.user_message, 60),
+            candidate.reason.label()
+        );
+    }
+    for session_id in &plan.digests {
+        println!("   • Digest of session {}", session_id);
+    }
+
+    if dry_run {
+        println!();
+        println!("💡 Dry run - nothing was deleted. Re-run without --dry-run to apply.");
+        return Ok(());
+    }
+
+    retention::execute(&memory, &plan).await?;
+    // Pruned text should not linger in old segments
+    memory.compact().await.context("Failed to compact memory store")?;
+
+    println!();
+    println!("✅ Pruned {} conversations and {} session digests", candidates.len(), plan.digests.len());
+    println!("   📚 Conversations in database: {}", memory.conversation_count().await?);
+
+    Ok(())
+}
@@ -2,7 +2,7 @@ use anyhow::{Context, Result};
 use std::io::{self, Write};
 use tracing::{info, warn};
 
-use crate::core::{config::{load_project_config, project_config_dir}, memory::Memory, validation::ensure_project_initialized};
+use crate::core::{config::{load_project_config, project_config_dir}, memory::Memory, session_state::SessionStateStore, validation::ensure_project_initialized};
 
 pub async fn handle_reset(yes: bool) -> Result<()> {
     // Ensure we're in a project
@@ -11,7 +11,7 @@ pub async fn handle_reset(yes: bool) -> Result<()> {
     // Show current status before reset
     let config = load_project_config().await.context("Failed to load configuration")?;
     
-    let conversation_count = match Memory::new(&config.database).await {
+    let conversation_count = match Memory::new(&config).await {
         Ok(memory) => memory.conversation_count().await.unwrap_or(0),
         Err(_) => 0,
     };
@@ -40,7 +40,7 @@ pub async fn handle_reset(yes: bool) -> Result<()> {
     info!("Starting memory reset");
     
     // Clear database
-    match Memory::new(&config.database).await {
+    match Memory::new(&config).await {
         Ok(memory) => {
             memory.clear().await.context("Failed to clear memory database")?;
             println!("  ✅ Database cleared");
@@ -51,19 +51,13 @@ pub async fn handle_reset(yes: bool) -> Result<()> {
         }
     }
     
-    // Clean up session injection tracking files
+    // Forget which sessions already received context
     if let Ok(config_dir) = project_config_dir() {
-        if let Ok(entries) = std::fs::read_dir(&config_dir) {
-            for entry in entries.flatten() {
-                let path = entry.path();
-                if let Some(name) = path.file_name() {
-                    if name.to_string_lossy().starts_with("session_injected_") {
-                        match tokio::fs::remove_file(&path).await {
-                            Ok(_) => println!("  ✅ Session tracking file removed: {}", name.to_string_lossy()),
-                            Err(_) => println!("  ⚠️ Could not remove session file: {}", name.to_string_lossy()),
-                        }
-                    }
-                }
+        match SessionStateStore::new(config_dir).clear() {
+            Ok(count) => println!("  ✅ Session tracking state removed ({} sessions)", count),
+            Err(e) => {
+                warn!("Failed to clear session state: {}", e);
+                println!("  ⚠️ Could not remove session tracking state");
             }
         }
     }
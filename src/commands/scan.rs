@@ -0,0 +1,106 @@
+use anyhow::{Context, Result};
+use std::path::Path;
+
+use crate::core::{
+    config::load_project_config,
+    memory::Memory,
+    redact::{RedactionReport, Redactor},
+    store::legacy_backup_path,
+    validation::ensure_project_initialized,
+};
+
+/// Redact secrets and PII from conversations that are already stored
+pub async fn handle_scan(dry_run: bool) -> Result<()> {
+    // Ensure we're in a project
+    ensure_project_initialized()?;
+
+    let config = load_project_config().await.context("Failed to load configuration")?;
+    let memory = Memory::new(&config).await
+        .context("Failed to initialize memory store")?;
+    let redactor = Redactor::new(&config.redaction)?;
+
+    println!("🔐 off-context Scan (Project-local)");
+    println!("━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━");
+    if !config.redaction.enabled {
+        println!("⚠️ Redaction is disabled in config.toml ([redaction] enabled = false)");
+        return Ok(());
+    }
+
+    let conversations = memory.all_conversations().await?;
+    let scanned = conversations.len();
+    let mut totals = RedactionReport::default();
+    let mut affected = 0;
+
+    for original in conversations {
+        let mut redacted = original.clone();
+        let report = redactor.redact_conversation(&mut redacted);
+        if report.is_empty() {
+            continue;
+        }
+        affected += 1;
+        totals.merge(report);
+
+        if dry_run {
+            continue;
+        }
+
+        // Content-derived ids follow the redacted text, as they do at ingest
+        redacted.id = redacted.stable_id();
+        memory.store_conversation(&redacted).await
+            .context("Failed to store redacted conversation")?;
+        if redacted.id != original.id {
+            memory.delete_conversation(&original.id).await
+                .context("Failed to remove unredacted conversation")?;
+        }
+    }
+
+    // Digests written before redaction quote the same text
+    let mut affected_digests = 0;
+    for mut digest in memory.session_digests() {
+        let report = redactor.redact_digest(&mut digest);
+        if report.is_empty() {
+            continue;
+        }
+        affected_digests += 1;
+        totals.merge(report);
+
+        if !dry_run {
+            memory.store_digest(&digest).await
+                .context("Failed to store redacted session digest")?;
+        }
+    }
+
+    println!("📊 Scanned {} conversations", scanned);
+    println!("   🔎 Conversations with findings: {}", affected);
+    if affected_digests > 0 {
+        println!("   📝 Session digests with findings: {}", affected_digests);
+    }
+    for (detector, count) in &totals.findings {
+        println!("   • {}: {}", detector, count);
+    }
+
+    if affected + affected_digests == 0 {
+        println!();
+        println!("✅ No secrets found");
+        return Ok(());
+    }
+
+    if dry_run {
+        println!();
+        println!("💡 Dry run - nothing was changed. Re-run without --dry-run to redact.");
+        return Ok(());
+    }
+
+    // Superseded records still hold the plaintext until the log is rewritten
+    memory.compact().await.context("Failed to compact memory store")?;
+
+    println!();
+    println!("✅ Redacted {} findings in {} conversations and {} digests", totals.total(), affected, affected_digests);
+
+    let legacy_backup = legacy_backup_path(&Path::new(&config.database.path).join("conversations.json"));
+    if legacy_backup.exists() {
+        println!("⚠️ Pre-migration backup still holds unredacted history: {}", legacy_backup.display());
+    }
+
+    Ok(())
+}
@@ -2,13 +2,28 @@ use anyhow::{Context, Result};
 use chrono::DateTime;
 use tracing::debug;
 
-use crate::core::{config::load_project_config, memory::Memory, validation::ensure_project_initialized};
+use crate::core::{
+    config::load_project_config,
+    memory::Memory,
+    query::{Search, SearchMode},
+    types::SearchResult,
+    validation::ensure_project_initialized,
+};
 
-pub async fn handle_search(query: &str, limit: usize) -> Result<()> {
+/// Matched strings listed under each result in fuzzy and regex mode
+const MAX_LISTED_MATCHES: usize = 5;
+
+pub async fn handle_search(query: &str, limit: usize, mode: SearchMode, explain: bool) -> Result<()> {
     // Ensure we're in a project
     ensure_project_initialized()?;
     
-    println!("🔍 Searching project for: \"{}\"", query);
+    let search = Search::parse(query, mode).context("Invalid search query")?;
+    
+    match mode {
+        SearchMode::Keyword => println!("🔍 Searching project for: \"{}\"", query),
+        SearchMode::Fuzzy => println!("🔍 Fuzzy searching project for: \"{}\"", query),
+        SearchMode::Regex => println!("🔍 Searching project for pattern: /{}/", query),
+    }
     println!("━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━");
     
     let start = std::time::Instant::now();
@@ -17,12 +32,12 @@ pub async fn handle_search(query: &str, limit: usize) -> Result<()> {
     let config = load_project_config().await.context("Failed to load configuration")?;
     
     // Initialize memory store
-    let memory = Memory::new(&config.database).await
+    let memory = Memory::new(&config).await
         .context("Failed to initialize memory store")?;
     
     // Perform search
     let search_results = memory
-        .search(query, limit)
+        .run_search(&search, limit)
         .await
         .context("Failed to search conversations")?;
     
@@ -43,7 +58,8 @@ pub async fn handle_search(query: &str, limit: usize) -> Result<()> {
     for (i, result) in search_results.iter().enumerate() {
         let conversation = &result.conversation;
         
-        println!("📝 Result {} (similarity: {:.2})", i + 1, result.score);
+        println!("📝 Result {} (score: {:.4})", i + 1, result.score);
+        println!("   🆔 {}{}", conversation.id, if conversation.metadata.pinned { " 📌" } else { "" });
         println!("   ⏰ {}", format_timestamp(&conversation.timestamp));
         
         if let Some(project_path) = &conversation.metadata.project_path {
@@ -55,6 +71,9 @@ pub async fn handle_search(query: &str, limit: usize) -> Result<()> {
         }
         
         println!("   💬 Tokens: {}", conversation.metadata.token_count);
+        if let Some(passage) = result.passage.filter(|p| p.count > 1) {
+            println!("   📄 Passage {} of {}", passage.ordinal + 1, passage.count);
+        }
         println!();
         
         // Show conversation snippet
@@ -68,6 +87,14 @@ pub async fn handle_search(query: &str, limit: usize) -> Result<()> {
         
         println!("   └─────────────────────────────────────────────");
         
+        if mode != SearchMode::Keyword && !result.matches.is_empty() {
+            println!("   🎯 Matched: {}", matched_text(result));
+        }
+        
+        if explain {
+            print_signals(result, mode);
+        }
+        
         if i < search_results.len() - 1 {
             println!();
         }
@@ -89,6 +116,51 @@ pub async fn handle_search(query: &str, limit: usize) -> Result<()> {
     Ok(())
 }
 
+/// Per-signal breakdown of a result's score
+fn print_signals(result: &SearchResult, mode: SearchMode) {
+    let keyword_measure = match mode {
+        SearchMode::Regex => "matches",
+        SearchMode::Keyword | SearchMode::Fuzzy => "bm25",
+    };
+    let signals = &result.signals;
+    
+    println!("   🧮 Ranking:");
+    for (name, measure, signal) in [
+        ("keyword", keyword_measure, signals.keyword),
+        ("semantic", "cosine", signals.semantic),
+        ("recency", "decay", signals.recency),
+    ] {
+        match signal {
+            Some(signal) => println!(
+                "      {:<9} #{:<3} {} {:.3}  +{:.4}",
+                name, signal.rank, measure, signal.raw, signal.contribution
+            ),
+            None => println!("      {:<9} –", name),
+        }
+    }
+}
+
+/// Distinct matched strings, in the order they first occur
+fn matched_text(result: &SearchResult) -> String {
+    let mut seen: Vec<&str> = Vec::new();
+    for m in &result.matches {
+        let text = m.text(&result.conversation);
+        if !seen.contains(&text) {
+            seen.push(text);
+        }
+    }
+    let total = seen.len();
+    let mut listed: Vec<String> = seen
+        .into_iter()
+        .take(MAX_LISTED_MATCHES)
+        .map(|text| format!("`{}`", text))
+        .collect();
+    if total > MAX_LISTED_MATCHES {
+        listed.push(format!("+{} more", total - MAX_LISTED_MATCHES));
+    }
+    format!("{} ({} total)", listed.join(", "), result.matches.len())
+}
+
 fn format_timestamp(timestamp: &DateTime<chrono::Utc>) -> String {
     let now = chrono::Utc::now();
     let duration = now.signed_duration_since(*timestamp);
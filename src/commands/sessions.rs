@@ -0,0 +1,249 @@
+use anyhow::{bail, Context, Result};
+use clap::Subcommand;
+use std::collections::BTreeSet;
+use std::io::{self, Write};
+
+use crate::core::{
+    config::{load_project_config, project_config_dir},
+    memory::Memory,
+    preview::line_preview,
+    session_state::SessionStateStore,
+    summarize::render_digest,
+    types::Session,
+    validation::ensure_project_initialized,
+};
+
+#[derive(Subcommand)]
+pub enum SessionsAction {
+    /// List sessions, most recently active first
+    List {
+        /// Maximum number of sessions to show
+        #[arg(short, long, default_value = "20")]
+        limit: usize,
+    },
+
+    /// Show a session's details, digest and exchanges
+    Show {
+        /// Session id (or a unique prefix of it)
+        id: String,
+    },
+
+    /// Delete a session's conversations and digest
+    Delete {
+        /// Session id (or a unique prefix of it)
+        id: String,
+        /// Skip confirmation prompt
+        #[arg(short, long)]
+        yes: bool,
+    },
+}
+
+pub async fn handle_sessions(action: SessionsAction) -> Result<()> {
+    // Ensure we're in a project
+    ensure_project_initialized()?;
+
+    let config = load_project_config().await.context("Failed to load configuration")?;
+    let memory = Memory::new(&config).await
+        .context("Failed to initialize memory store")?;
+
+    println!("🗂️ off-context Sessions (Project-local)");
+    println!("━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━");
+
+    match action {
+        SessionsAction::List { limit } => {
+            let sessions = memory.sessions();
+            if sessions.is_empty() {
+                println!("📭 No sessions stored yet");
+                return Ok(());
+            }
+
+            println!("📊 {}", plural(sessions.len(), "session"));
+            for session in sessions.iter().take(limit) {
+                println!();
+                print_summary(&memory, session);
+            }
+            if sessions.len() > limit {
+                println!();
+                println!("💡 {} older sessions not shown; use --limit to see more", sessions.len() - limit);
+            }
+        }
+        SessionsAction::Show { id } => {
+            let session_id = resolve_session_id(&memory, &id)?;
+            match memory.session(&session_id) {
+                Some(session) => print_summary(&memory, &session),
+                None => println!("🆔 {} (conversations deleted; digest only)", session_id),
+            }
+            let state = SessionStateStore::new(project_config_dir()?).get(&session_id)?;
+            if let Some(state) = state.filter(|s| s.injection_count > 0) {
+                println!(
+                    "   💉 Memory injected {} ({}), last at {}",
+                    plural(state.injection_count as usize, "time"),
+                    plural(state.injected_conversations.len(), "conversation"),
+                    state.last_injected_at.map_or_else(String::new, |t| t.format("%Y-%m-%d %H:%M").to_string())
+                );
+            }
+
+            if let Some(digest) = memory.session_digest(&session_id) {
+                println!();
+                println!("📝 Digest ({}):", digest.summarizer);
+                for line in render_digest(&digest).lines() {
+                    println!("   │ {}", line);
+                }
+            }
+
+            let turns = memory.session_conversations(&session_id);
+            if !turns.is_empty() {
+                println!();
+                println!("💬 Exchanges:");
+            }
+            for (i, turn) in turns.iter().enumerate() {
+                println!();
+                println!("   {}. {} 🆔 {}", i + 1, turn.timestamp.format("%Y-%m-%d %H:%M"), turn.id);
+                println!("      👤 {}", line_preview(&turn.user_message, 100));
+                println!("      🤖 {}", line_preview(&turn.assistant_response, 160));
+            }
+        }
+        SessionsAction::Delete { id, yes } => {
+            let session_id = resolve_session_id(&memory, &id)?;
+            let turn_count = memory.session(&session_id).map_or(0, |s| s.turn_count);
+
+            println!("🆔 {}", session_id);
+            println!("   💾 Conversations to delete: {}", turn_count);
+
+            if !yes {
+                println!();
+                print!("⚠️ This will delete the session's conversations and digest. Continue? (y/N): ");
+                io::stdout().flush()?;
+
+                let mut input = String::new();
+                io::stdin().read_line(&mut input)?;
+
+                if input.trim().to_lowercase() != "y" {
+                    println!("❌ Delete cancelled");
+                    return Ok(());
+                }
+            }
+
+            let deleted = memory.delete_session(&session_id).await
+                .context("Failed to delete session")?;
+            SessionStateStore::new(project_config_dir()?).forget(&session_id)?;
+            // Deleted text should not linger in old segments
+            memory.compact().await.context("Failed to compact memory store")?;
+
+            println!("✅ Deleted session {} ({})", session_id, plural(deleted, "conversation"));
+        }
+    }
+
+    Ok(())
+}
+
+/// Resolve a unique session id prefix, including sessions only a digest remains of
+pub fn resolve_session_id(memory: &Memory, id: &str) -> Result<String> {
+    if id.trim().is_empty() {
+        bail!("Session id must not be empty");
+    }
+
+    let mut matches = matching_session_ids(memory, id);
+    match matches.len() {
+        0 => bail!("No session with id {}", id),
+        1 => Ok(matches.remove(0)),
+        n => bail!("Id prefix {} matches {} sessions; use more characters", id, n),
+    }
+}
+
+/// Sessions `id` may refer to: itself if it is a known id, otherwise every session
+/// it is a prefix of
+pub fn matching_session_ids(memory: &Memory, id: &str) -> Vec<String> {
+    let prefix = id.trim();
+    let known: BTreeSet<String> = memory.sessions().into_iter().map(|s| s.id)
+        .chain(memory.session_digests().into_iter().map(|d| d.session_id))
+        .collect();
+    if known.contains(prefix) {
+        return vec![prefix.to_string()];
+    }
+    known.into_iter().filter(|s| s.starts_with(prefix)).collect()
+}
+
+/// `count` followed by `noun`, with an `s` unless there is exactly one
+fn plural(count: usize, noun: &str) -> String {
+    format!("{} {}{}", count, noun, if count == 1 { "" } else { "s" })
+}
+
+fn print_summary(memory: &Memory, session: &Session) {
+    let digest = if memory.session_digest(&session.id).is_some() { " 📝" } else { "" };
+    println!("🆔 {}{}", session.id, digest);
+    println!(
+        "   📅 {} → {}",
+        session.started_at.format("%Y-%m-%d %H:%M"),
+        session.ended_at.format("%Y-%m-%d %H:%M")
+    );
+    println!("   💬 {}, {}", plural(session.turn_count, "turn"), plural(session.token_total, "token"));
+    if let Some(branch) = &session.git_branch {
+        println!("   🌿 Branch: {}", branch);
+    }
+    if let Some(cwd) = &session.cwd {
+        println!("   📁 {}", cwd);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::{config::default_config, types::{Conversation, SessionDigest}};
+
+    async fn memory_with(dir: &std::path::Path, sessions: &[&str], digest_only: &str) -> Memory {
+        let mut config = default_config();
+        config.database.path = dir.join("db").to_string_lossy().to_string();
+        let memory = Memory::new(&config).await.unwrap();
+        for session in sessions {
+            let conversation = Conversation::sample(session, "2026-09-01T10:00:00Z", "prompt", "answer");
+            memory.store_conversation(&conversation).await.unwrap();
+        }
+        let at = "2026-09-01T10:00:00Z".parse().unwrap();
+        memory.store_digest(&SessionDigest {
+            session_id: digest_only.to_string(),
+            started_at: at,
+            ended_at: at,
+            conversation_count: 1,
+            topics: vec!["prompt → answer".to_string()],
+            decisions: Vec::new(),
+            files_touched: Vec::new(),
+            todos: Vec::new(),
+            summarizer: "extractive".to_string(),
+            source_fingerprint: 0,
+            created_at: at,
+        }).await.unwrap();
+        memory
+    }
+
+    #[test]
+    fn counts_are_pluralized() {
+        assert_eq!(plural(1, "session"), "1 session");
+        assert_eq!(plural(0, "session"), "0 sessions");
+        assert_eq!(plural(2, "turn"), "2 turns");
+    }
+
+    #[tokio::test]
+    async fn resolves_exact_ids_and_unique_prefixes() {
+        let dir = tempfile::tempdir().unwrap();
+        let memory = memory_with(dir.path(), &["abc123", "abd456", "abc"], "zzz789").await;
+
+        // An exact id wins even when it is also a prefix of another
+        assert_eq!(resolve_session_id(&memory, "abc").unwrap(), "abc");
+        assert_eq!(resolve_session_id(&memory, " abc1 ").unwrap(), "abc123");
+        assert_eq!(resolve_session_id(&memory, "abd").unwrap(), "abd456");
+        // Sessions only a digest is left of
+        assert_eq!(resolve_session_id(&memory, "zz").unwrap(), "zzz789");
+    }
+
+    #[tokio::test]
+    async fn rejects_ambiguous_unknown_and_empty_ids() {
+        let dir = tempfile::tempdir().unwrap();
+        let memory = memory_with(dir.path(), &["abc123", "abd456"], "zzz789").await;
+
+        let ambiguous = resolve_session_id(&memory, "ab").unwrap_err().to_string();
+        assert!(ambiguous.contains("matches 2 sessions"), "{}", ambiguous);
+        assert!(resolve_session_id(&memory, "nope").unwrap_err().to_string().contains("No session"));
+        assert!(resolve_session_id(&memory, "  ").is_err());
+    }
+}
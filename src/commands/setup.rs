@@ -1,19 +1,21 @@
 use anyhow::{Context, Result};
+use std::path::{Path, PathBuf};
 use std::process::Command;
 use tracing::{info, warn};
 
 use crate::core::{
-    config::{claude_code_hooks_dir, config_dir, init_config},
+    config::{claude_code_hooks_dir, config_dir, config_file_path, init_config},
     memory::Memory,
 };
+use crate::hooks::{HookEvent, REGISTERED_EVENTS};
 
 /// Handle the setup command - configure Claude Code hooks
-pub async fn handle_setup(force: bool) -> Result<()> {
+pub async fn handle_setup(force: bool, wrappers: bool) -> Result<()> {
     info!("🚀 Starting off-context setup...");
     
     if !force {
         // Check if already configured
-        if is_already_configured().await? {
+        if is_already_configured(wrappers).await? {
             println!("✅ off-context is already configured!");
             println!("💡 Use --force to reconfigure");
             return Ok(());
@@ -31,7 +33,11 @@ pub async fn handle_setup(force: bool) -> Result<()> {
     
     // 3. Configure hooks
     info!("🔗 Configuring Claude Code hooks...");
-    configure_hooks().await?;
+    if wrappers {
+        write_hook_wrappers().await?;
+    } else {
+        println!("  Hooks run the off-context binary directly ✅");
+    }
     
     // 4. Initialize local database (create config dir structure)
     info!("🗄️ Initializing memory database...");
@@ -49,17 +55,17 @@ pub async fn handle_setup(force: bool) -> Result<()> {
     Ok(())
 }
 
-async fn is_already_configured() -> Result<bool> {
-    // Check if hooks are configured
-    let hooks_dir = claude_code_hooks_dir()?;
-    let user_prompt_hook = hooks_dir.join("UserPromptSubmit.sh");
-    let stop_hook = hooks_dir.join("Stop.sh");
+async fn is_already_configured(wrappers: bool) -> Result<bool> {
+    // Check if the global config was written (the directory alone also holds hook logs)
+    let config_exists = config_file_path()?.exists();
     
-    // Check if config directory exists
-    let config_dir = config_dir()?;
-    let config_exists = config_dir.exists();
-    
-    let hooks_configured = user_prompt_hook.exists() && stop_hook.exists();
+    // Wrappers only count when asked for
+    let mut hooks_configured = true;
+    if wrappers {
+        for event in REGISTERED_EVENTS {
+            hooks_configured &= hook_wrapper_path(event)?.exists();
+        }
+    }
     
     Ok(config_exists && hooks_configured)
 }
@@ -89,94 +95,54 @@ async fn detect_claude_code() -> Result<()> {
     Ok(())
 }
 
-async fn configure_hooks() -> Result<()> {
+/// Write optional bash wrappers that forward each hook to `off-context hook-event`
+///
+/// `init` registers the binary directly; the wrappers are for setups that want a
+/// script to point Claude Code at (`init --wrappers`).
+pub async fn write_hook_wrappers() -> Result<()> {
     let hooks_dir = claude_code_hooks_dir()?;
     
     // Create hooks directory if it doesn't exist
     tokio::fs::create_dir_all(&hooks_dir).await
         .context("Failed to create hooks directory")?;
 
-    // Create UserPromptSubmit hook for context injection
-    let user_prompt_hook = hooks_dir.join("UserPromptSubmit.sh");
-    let user_prompt_script = r#"#!/bin/bash
-# off-context UserPromptSubmit hook
-# Receives JSON via stdin with session_id, transcript_path, prompt
+    for event in REGISTERED_EVENTS {
+        let hook = hook_wrapper_path(event)?;
+        let script = format!(r#"#!/bin/bash
+# off-context {event} hook
+# Receives the hook JSON via stdin; does nothing if off-context is not installed
 if command -v off-context >/dev/null 2>&1; then
-    off-context inject-prompt
-else
-    cat
+    exec off-context hook-event {arg}
 fi
-"#;
-
-    tokio::fs::write(&user_prompt_hook, user_prompt_script).await
-        .context("Failed to write UserPromptSubmit hook")?;
+"#, arg = event.arg());
 
-    // Make the hook executable (Unix systems)
-    #[cfg(unix)]
-    {
-        use std::os::unix::fs::PermissionsExt;
-        let mut perms = tokio::fs::metadata(&user_prompt_hook).await?.permissions();
-        perms.set_mode(0o755);
-        tokio::fs::set_permissions(&user_prompt_hook, perms).await
-            .context("Failed to make UserPromptSubmit hook executable")?;
+        tokio::fs::write(&hook, script).await
+            .with_context(|| format!("Failed to write {} hook", event))?;
+        make_executable(&hook).await
+            .with_context(|| format!("Failed to make {} hook executable", event))?;
+        println!("  {} hook created ✅", event);
     }
 
-    // Create Stop hook for conversation capture
-    let stop_hook = hooks_dir.join("Stop.sh");
-    let stop_script = r#"#!/bin/bash
-# off-context Stop hook
-# Receives JSON via stdin with session_id, transcript_path
-
-LOG_FILE="$HOME/.off-context/hooks.log"
+    println!("  Hooks directory: {}", hooks_dir.display());
 
-log() {
-    echo "[$(date '+%Y-%m-%d %H:%M:%S')] [Stop] $1" >> "$LOG_FILE"
+    Ok(())
 }
 
-# Read JSON from stdin
-INPUT_JSON=$(cat)
-log "Stop hook input: $INPUT_JSON"
-
-# Extract transcript_path from JSON
-if command -v jq >/dev/null 2>&1; then
-    TRANSCRIPT_FILE=$(echo "$INPUT_JSON" | jq -r '.transcript_path // empty')
-    SESSION_ID=$(echo "$INPUT_JSON" | jq -r '.session_id // empty')
-    log "Extracted transcript: $TRANSCRIPT_FILE, session: $SESSION_ID"
-else
-    log "jq not found, cannot parse JSON input"
-    exit 1
-fi
-
-if [ -n "$TRANSCRIPT_FILE" ] && [ -f "$TRANSCRIPT_FILE" ]; then
-    log "Processing transcript: $TRANSCRIPT_FILE"
-    if command -v off-context >/dev/null 2>&1; then
-        off-context hook "$TRANSCRIPT_FILE" >>"$LOG_FILE" 2>&1 &
-        log "off-context hook called for $TRANSCRIPT_FILE"
-    else
-        log "off-context not found, skipping hook call"
-    fi
-else
-    log "No valid transcript file to process: $TRANSCRIPT_FILE"
-fi
-"#;
-
-    tokio::fs::write(&stop_hook, stop_script).await
-        .context("Failed to write Stop hook")?;
+/// Path of the bash wrapper for `event`
+pub fn hook_wrapper_path(event: HookEvent) -> Result<PathBuf> {
+    Ok(claude_code_hooks_dir()?.join(format!("{}.sh", event)))
+}
 
-    // Make the hook executable (Unix systems)
+async fn make_executable(path: &Path) -> Result<()> {
     #[cfg(unix)]
     {
         use std::os::unix::fs::PermissionsExt;
-        let mut perms = tokio::fs::metadata(&stop_hook).await?.permissions();
+        let mut perms = tokio::fs::metadata(path).await?.permissions();
         perms.set_mode(0o755);
-        tokio::fs::set_permissions(&stop_hook, perms).await
-            .context("Failed to make Stop hook executable")?;
+        tokio::fs::set_permissions(path, perms).await?;
     }
-
-    println!("  UserPromptSubmit hook created ✅");
-    println!("  Stop hook created ✅");
-    println!("  Hooks directory: {}", hooks_dir.display());
-
+    #[cfg(not(unix))]
+    let _ = path;
     Ok(())
 }
 
@@ -189,7 +155,7 @@ async fn initialize_database() -> Result<()> {
     // Test database connection by creating a Memory instance
     // This will create the collection if needed
     let config = crate::core::config::load_config().await?;
-    match Memory::new(&config.database).await {
+    match Memory::new(&config).await {
         Ok(_) => {
             println!("  Database connection test passed ✅");
         }
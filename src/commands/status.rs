@@ -1,12 +1,15 @@
 use anyhow::Result;
+use std::path::PathBuf;
 use tracing::debug;
 
 use crate::core::{
-    config::{claude_code_hooks_dir, project_config_dir, load_project_config, find_project_root},
+    config::{claude_code_hooks_dir, project_config_dir, project_database_path, load_project_config, find_project_root},
+    crypto,
     embeddings::EmbeddingGenerator,
     memory::Memory,
     validation::ensure_project_initialized,
 };
+use crate::hooks::{settings::SettingsFile, HookEvent, SettingsScope, REGISTERED_EVENTS};
 
 /// Handle the status command - show system information
 pub async fn handle_status() -> Result<()> {
@@ -16,9 +19,25 @@ pub async fn handle_status() -> Result<()> {
     println!("📊 off-context Status (Project-local)");
     println!("━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━");
     
-    // Check hook configuration
-    let hooks_status = check_hooks_status().await?;
-    println!("🔗 Claude Code Hooks: {}", if hooks_status { "✅ Active" } else { "❌ Not configured" });
+    // Check hook configuration in every settings scope
+    let installs = hook_installs()?;
+    if installs.is_empty() {
+        println!("🔗 Claude Code Hooks: ❌ Not configured");
+    } else {
+        let scopes: Vec<&str> = installs.iter().map(|i| i.scope.name()).collect();
+        println!("🔗 Claude Code Hooks: ✅ Active ({})", scopes.join(", "));
+    }
+    for install in &installs {
+        println!("   📄 {}: {} ({}/{} events)",
+            install.scope, install.path.display(), install.events.len(), REGISTERED_EVENTS.len());
+        let missing: Vec<&str> = REGISTERED_EVENTS.iter()
+            .filter(|e| !install.events.contains(e))
+            .map(|e| e.name())
+            .collect();
+        if !missing.is_empty() {
+            println!("      ⚠️ Missing: {}", missing.join(", "));
+        }
+    }
     
     // Check database status
     let db_status = check_database_status().await?;
@@ -30,6 +49,24 @@ pub async fn handle_status() -> Result<()> {
         println!("   📅 Last activity: {}", db_status.last_activity.unwrap_or_else(|| "Never".to_string()));
     }
     
+    // Encryption is read from the key header, so it shows even when the store is locked
+    match crypto::status(&project_database_path()?)? {
+        Some(encryption) => {
+            println!("🔐 Encryption at rest: ✅ Enabled ({})", encryption.cipher);
+            println!("   🔑 Key: {} ({})", encryption.key_id, encryption.key_source);
+            if encryption.retired_keys > 0 {
+                println!("   ⚠️ Interrupted key rotation ({} old keys); run: off-context encryption rotate", encryption.retired_keys);
+            }
+            if encryption.pending_rewrite {
+                println!("   ⚠️ Plaintext records not yet rewritten; the next command that opens the store encrypts them");
+            }
+            if !db_status.exists {
+                println!("   💡 Store could not be opened; check the key file or {}", crypto::PASSPHRASE_ENV);
+            }
+        }
+        None => println!("🔐 Encryption at rest: ❌ Disabled"),
+    }
+    
     // Check embedding service
     let embeddings_status = check_embeddings_status().await?;
     println!("🧠 Embeddings: {}", if embeddings_status.available { "✅ Available" } else { "⚠️ Using fallback" });
@@ -52,15 +89,20 @@ pub async fn handle_status() -> Result<()> {
     println!("   🔍 Average search time: {}ms", search_time);
     println!("   💽 Database path: {}", project_config.join("qdrant").display());
     
-    // Show hooks directory (global)
+    // Show the optional bash wrappers (global)
     if let Ok(hooks_dir) = claude_code_hooks_dir() {
-        println!("   🪝 Global hooks: {}", hooks_dir.display());
+        if hooks_dir.exists() {
+            println!("   🪝 Hook wrappers: {}", hooks_dir.display());
+        }
     }
     
-    if !hooks_status {
+    if installs.is_empty() {
+        println!();
+        println!("💡 Hooks not found. Run: off-context init --scope local|project|user");
+        println!("💡 This project is isolated but needs hooks to capture and inject memory");
+    } else if installs.len() > 1 {
         println!();
-        println!("💡 Global hooks not found. Run: off-context setup");
-        println!("💡 This project is isolated but needs global hooks to work");
+        println!("💡 Hooks in more than one scope run once per scope; keep one with: off-context clear --scope <scope>");
     }
     
     Ok(())
@@ -80,36 +122,42 @@ pub struct EmbeddingsStatus {
     pub dimensions: usize,
 }
 
-pub async fn check_hooks_status() -> Result<bool> {
-    // First check if global hooks exist
-    let hooks_dir = claude_code_hooks_dir()?;
-    let user_prompt_hook = hooks_dir.join("UserPromptSubmit.sh");
-    let stop_hook = hooks_dir.join("Stop.sh");
-    
-    let global_hooks_exist = user_prompt_hook.exists() && stop_hook.exists();
-    debug!("Global hooks status: UserPromptSubmit={}, Stop={}", user_prompt_hook.exists(), stop_hook.exists());
-    
-    if !global_hooks_exist {
-        return Ok(false);
-    }
-    
-    // Then check if local project hooks are configured
-    let project_root = std::env::current_dir()?;
-    let claude_dir = project_root.join(".claude");
-    let settings_file = claude_dir.join("settings.local.json");
-    
-    if !settings_file.exists() {
-        debug!("No settings.local.json found");
-        return Ok(false);
+/// off-context hooks found in one settings scope
+pub struct HookInstall {
+    pub scope: SettingsScope,
+    pub path: PathBuf,
+    pub events: Vec<HookEvent>,
+}
+
+/// Settings scopes that have off-context hooks for the current project
+pub fn hook_installs() -> Result<Vec<HookInstall>> {
+    let project_root = match find_project_root() {
+        Some(root) => root,
+        None => std::env::current_dir()?,
+    };
+
+    let mut installs = Vec::new();
+    for scope in SettingsScope::ALL {
+        let path = scope.path(&project_root)?;
+        if !path.exists() {
+            continue;
+        }
+        match SettingsFile::load(&path) {
+            Ok(settings) => {
+                let events = settings.installed_events();
+                debug!("{} settings: {} off-context hooks", scope, events.len());
+                if !events.is_empty() {
+                    installs.push(HookInstall { scope, path, events });
+                }
+            }
+            Err(e) => debug!("Skipping {} settings: {:#}", scope, e),
+        }
     }
-    
-    let content = std::fs::read_to_string(&settings_file)?;
-    let settings: serde_json::Value = serde_json::from_str(&content).unwrap_or(serde_json::json!({}));
-    
-    let local_hooks_configured = settings.get("hooks").is_some();
-    debug!("Local hooks configured: {}", local_hooks_configured);
-    
-    Ok(global_hooks_exist && local_hooks_configured)
+    Ok(installs)
+}
+
+pub async fn check_hooks_status() -> Result<bool> {
+    Ok(!hook_installs()?.is_empty())
 }
 
 pub async fn check_database_status() -> Result<DatabaseStatus> {
@@ -118,7 +166,7 @@ pub async fn check_database_status() -> Result<DatabaseStatus> {
         Err(_) => return Ok(DatabaseStatus::default()),
     };
     
-    match Memory::new(&config.database).await {
+    match Memory::new(&config).await {
         Ok(memory) => {
             let conversation_count = memory.conversation_count().await.unwrap_or(0);
             
@@ -147,16 +195,16 @@ pub async fn check_database_status() -> Result<DatabaseStatus> {
 pub async fn check_embeddings_status() -> Result<EmbeddingsStatus> {
     let config = load_project_config().await?;
     
-    match EmbeddingGenerator::new().await {
+    match EmbeddingGenerator::new(&config.embeddings).await {
         Ok(generator) => {
             let ollama_available = generator.is_ollama_available().await;
             
             Ok(EmbeddingsStatus {
                 available: ollama_available,
                 provider: if ollama_available { 
-                    config.embeddings.provider 
+                    format!("{} ({})", config.embeddings.provider, config.embeddings.model)
                 } else { 
-                    "simple (fallback)".to_string() 
+                    generator.provider_name()
                 },
                 dimensions: config.embeddings.dimension,
             })
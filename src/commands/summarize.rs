@@ -0,0 +1,50 @@
+use anyhow::{Context, Result};
+
+use crate::core::{
+    config::load_project_config,
+    memory::Memory,
+    summarize::{render_digest, summarize_sessions},
+    validation::ensure_project_initialized,
+};
+
+/// Write digests for finished sessions and show them
+pub async fn handle_summarize(force: bool) -> Result<()> {
+    // Ensure we're in a project
+    ensure_project_initialized()?;
+
+    let config = load_project_config().await.context("Failed to load configuration")?;
+    let memory = Memory::new(&config).await
+        .context("Failed to initialize memory store")?;
+
+    println!("📝 off-context Summarize (Project-local)");
+    println!("━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━");
+    if !config.summarization.enabled {
+        println!("⚠️ Summarization is disabled in config.toml ([summarization] enabled = false)");
+        return Ok(());
+    }
+
+    let written = summarize_sessions(&memory, &config.summarization, force, None).await
+        .context("Failed to summarize sessions")?;
+
+    let mut digests = memory.session_digests();
+    digests.sort_by_key(|d| d.ended_at);
+
+    println!("🧠 Summarizer: {}", config.summarization.provider);
+    println!("   ✍️ Digests written: {}", written);
+    println!("   📚 Digests stored: {}", digests.len());
+
+    for digest in digests.iter().rev().take(5) {
+        println!();
+        println!("   🗂️ {} ({})", digest.session_id, digest.summarizer);
+        for line in render_digest(digest).lines() {
+            println!("   │ {}", line);
+        }
+    }
+
+    if !force {
+        println!();
+        println!("💡 Sessions idle for less than {} minutes are skipped; use --force to include them", config.summarization.idle_minutes);
+    }
+
+    Ok(())
+}
@@ -0,0 +1,322 @@
+use regex::Regex;
+use serde_json::Value;
+use std::path::Path;
+use std::sync::OnceLock;
+
+use crate::core::transcript::ToolCall;
+
+/// Symbols kept per exchange; a pasted file can name thousands
+const MAX_SYMBOLS: usize = 200;
+
+/// Paths kept per exchange
+const MAX_FILES: usize = 50;
+
+/// File extensions a word must end in to count as a path when it has no `/`
+const SOURCE_EXTENSIONS: &str = "rs|toml|md|json|jsonl|yaml|yml|lock|py|pyi|js|mjs|cjs|ts|tsx|jsx|go|java|kt|swift|c|h|cc|cpp|hpp|cs|rb|php|sh|bash|zsh|sql|html|css|scss|vue|svelte|proto|txt";
+
+/// Words that look like calls or definitions in code but are part of the language
+const KEYWORDS: &[&str] = &[
+    "Err", "Ok", "Some", "and", "as", "assert", "async", "await", "break", "case", "catch", "class",
+    "const", "continue", "crate", "def", "do", "elif", "else", "enum", "except", "fn", "for",
+    "func", "function", "if", "impl", "import", "in", "interface", "let", "loop", "match", "mod",
+    "mut", "new", "not", "or", "pub", "return", "self", "Self", "sizeof", "static", "struct",
+    "super", "switch", "trait", "type", "typeof", "use", "var", "where", "while", "with", "yield",
+];
+
+/// A fenced code block in markdown text
+struct CodeBlock<'a> {
+    /// The info string's first word, lowercased (` ```rust ` → `rust`)
+    language: Option<String>,
+    code: &'a str,
+}
+
+/// Code-level references found in an exchange, each list in first-seen order
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct CodeRefs {
+    /// Languages of the fenced code blocks
+    pub languages: Vec<String>,
+    /// Function, type and other identifiers defined, called or named
+    pub symbols: Vec<String>,
+    /// File paths named in the text, code or tool commands
+    pub files: Vec<String>,
+}
+
+impl CodeRefs {
+    /// Collect references from a message: code blocks are read as code, the rest as
+    /// prose where only inline code and identifier-shaped words count
+    pub fn scan_markdown(&mut self, text: &str) {
+        let (blocks, prose) = split_fences(text);
+        for block in blocks {
+            if let Some(language) = block.language {
+                push_unique(&mut self.languages, language, usize::MAX);
+            }
+            self.scan_code(block.code);
+        }
+
+        for span in inline_code(&prose) {
+            let name = span.trim().trim_end_matches("()");
+            if is_symbol(name) {
+                self.push_symbol(name);
+            }
+            self.scan_code(span);
+        }
+        for found in prose_symbol_regex().find_iter(&prose) {
+            self.push_symbol(found.as_str().trim_end_matches("()"));
+        }
+        self.scan_paths(&prose);
+    }
+
+    /// Collect references from what a tool was asked to do: code it wrote or
+    /// replaced, identifiers it searched for and paths in shell commands
+    pub fn scan_tool_call(&mut self, call: &ToolCall) {
+        for key in ["old_string", "new_string", "content", "new_source"] {
+            if let Some(code) = call.input.get(key).and_then(Value::as_str) {
+                self.scan_code(code);
+            }
+        }
+        // MultiEdit
+        for edit in call.input.get("edits").and_then(Value::as_array).into_iter().flatten() {
+            for key in ["old_string", "new_string"] {
+                if let Some(code) = edit.get(key).and_then(Value::as_str) {
+                    self.scan_code(code);
+                }
+            }
+        }
+        if let Some(pattern) = call.input.get("pattern").and_then(Value::as_str) {
+            if is_symbol(pattern) {
+                self.push_symbol(pattern);
+            }
+        }
+        if let Some(command) = call.input.get("command").and_then(Value::as_str) {
+            self.scan_paths(command);
+        }
+    }
+
+    fn scan_code(&mut self, code: &str) {
+        for caps in definition_regex().captures_iter(code) {
+            self.push_symbol(&caps[1]);
+        }
+        for caps in reference_regex().captures_iter(code) {
+            if let Some(name) = caps.get(1).or_else(|| caps.get(2)) {
+                self.push_symbol(name.as_str());
+            }
+        }
+        self.scan_paths(code);
+    }
+
+    fn scan_paths(&mut self, text: &str) {
+        for found in path_regex().find_iter(text) {
+            // Skip the path part of URLs (`https://host/a.rs`)
+            let preceded = text[..found.start()].chars().next_back();
+            if matches!(preceded, Some(':' | '/' | '@')) {
+                continue;
+            }
+            let path = found.as_str().trim_start_matches("./");
+            push_unique(&mut self.files, path.to_string(), MAX_FILES);
+        }
+    }
+
+    /// Record an identifier, and for a qualified one (`Memory::search`) each of its
+    /// parts too
+    fn push_symbol(&mut self, name: &str) {
+        let parts: Vec<&str> = name.split("::").collect();
+        if parts.len() > 1 {
+            push_unique(&mut self.symbols, name.to_string(), MAX_SYMBOLS);
+        }
+        for part in parts {
+            if part.len() > 1 && !KEYWORDS.contains(&part) && !part.starts_with(|c: char| c.is_ascii_digit()) {
+                push_unique(&mut self.symbols, part.to_string(), MAX_SYMBOLS);
+            }
+        }
+    }
+}
+
+/// Whether `path` names `query`: equal to it, or ending with it component-wise, so
+/// `parser.rs` and `src/core/parser.rs` both name `/work/app/src/core/parser.rs`
+pub fn path_matches(path: &str, query: &str) -> bool {
+    let query = query.trim_start_matches("./");
+    !query.is_empty() && Path::new(path).ends_with(query)
+}
+
+/// Fenced code blocks in order, and the text outside them
+fn split_fences(text: &str) -> (Vec<CodeBlock<'_>>, String) {
+    let mut blocks = Vec::new();
+    let mut prose = String::new();
+    // Fence marker, language and where the code starts
+    let mut open: Option<(&str, Option<String>, usize)> = None;
+    let mut offset = 0;
+
+    for line in text.split_inclusive('\n') {
+        let line_start = offset;
+        offset += line.len();
+        let trimmed = line.trim();
+        let marker = ["```", "~~~"].into_iter().find(|m| trimmed.starts_with(m));
+
+        match (&open, marker) {
+            (Some((fence, _, _)), Some(m)) if m == *fence && trimmed.trim_start_matches(m.chars().next().unwrap()).is_empty() => {
+                let (_, language, start) = open.take().expect("fence is open");
+                blocks.push(CodeBlock { language, code: &text[start..line_start] });
+            }
+            (Some(_), _) => {}
+            (None, Some(m)) => {
+                let language = trimmed
+                    .trim_start_matches(m.chars().next().unwrap())
+                    .split(|c: char| c.is_whitespace() || c == ',' || c == '{')
+                    .next()
+                    .filter(|lang| !lang.is_empty())
+                    .map(str::to_lowercase);
+                open = Some((m, language, offset));
+            }
+            (None, None) => prose.push_str(line),
+        }
+    }
+
+    // An unclosed fence runs to the end
+    if let Some((_, language, start)) = open {
+        blocks.push(CodeBlock { language, code: &text[start..] });
+    }
+    (blocks, prose)
+}
+
+/// Contents of `inline code` spans
+fn inline_code(text: &str) -> Vec<&str> {
+    text.split('`').skip(1).step_by(2).filter(|span| !span.trim().is_empty()).collect()
+}
+
+/// A plain or qualified identifier (`handle_hook`, `Memory::search`)
+fn is_symbol(text: &str) -> bool {
+    static SYMBOL: OnceLock<Regex> = OnceLock::new();
+    SYMBOL
+        .get_or_init(|| Regex::new(r"^[A-Za-z_][A-Za-z0-9_]*(?:::[A-Za-z_][A-Za-z0-9_]*)*$").expect("symbol pattern"))
+        .is_match(text)
+}
+
+/// Names introduced by a definition: `fn name`, `struct Name`, `def name`, ...
+fn definition_regex() -> &'static Regex {
+    static DEFINITION: OnceLock<Regex> = OnceLock::new();
+    DEFINITION.get_or_init(|| {
+        Regex::new(r"\b(?:fn|struct|enum|trait|type|mod|union|class|interface|def|func|function|const|static)\s+(?:mut\s+)?([A-Za-z_][A-Za-z0-9_]*)")
+            .expect("definition pattern")
+    })
+}
+
+/// Calls (`name(`, `Type::method(`) and qualified paths (`a::b`)
+fn reference_regex() -> &'static Regex {
+    static REFERENCE: OnceLock<Regex> = OnceLock::new();
+    REFERENCE.get_or_init(|| {
+        Regex::new(r"\b([A-Za-z_][A-Za-z0-9_]*(?:::[A-Za-z_][A-Za-z0-9_]*)+)|\b([A-Za-z_][A-Za-z0-9_]*)\s*\(")
+            .expect("reference pattern")
+    })
+}
+
+/// Words in prose shaped like identifiers rather than English: `snake_case`,
+/// `CamelCase`, `a::b` paths and `calls()`
+fn prose_symbol_regex() -> &'static Regex {
+    static PROSE_SYMBOL: OnceLock<Regex> = OnceLock::new();
+    PROSE_SYMBOL.get_or_init(|| {
+        Regex::new(concat!(
+            r"\b(?:[A-Za-z][A-Za-z0-9]*(?:_[A-Za-z0-9]+)+",
+            r"|[A-Z][a-z0-9]+(?:[A-Z][a-z0-9]*)+",
+            r"|[A-Za-z_][A-Za-z0-9_]*(?:::[A-Za-z_][A-Za-z0-9_]*)+",
+            r"|[A-Za-z_][A-Za-z0-9_]*\(\))",
+        ))
+        .expect("prose symbol pattern")
+    })
+}
+
+/// Relative or absolute paths with a directory, or bare file names with a known
+/// source extension
+fn path_regex() -> &'static Regex {
+    static PATH: OnceLock<Regex> = OnceLock::new();
+    PATH.get_or_init(|| {
+        Regex::new(&format!(
+            r"(?:~|\.{{1,2}})?/?(?:[A-Za-z0-9_.-]+/)+[A-Za-z0-9_-][A-Za-z0-9_.-]*\.[A-Za-z0-9]{{1,10}}\b|\b[A-Za-z0-9_-][A-Za-z0-9_.-]*\.(?:{})\b",
+            SOURCE_EXTENSIONS
+        ))
+        .expect("path pattern")
+    })
+}
+
+fn push_unique(list: &mut Vec<String>, value: String, max: usize) {
+    if list.len() < max && !list.contains(&value) {
+        list.push(value);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn scanned(text: &str) -> CodeRefs {
+        let mut refs = CodeRefs::default();
+        refs.scan_markdown(text);
+        refs
+    }
+
+    #[test]
+    fn fences_split_code_from_prose() {
+        let text = "Intro\n```rust\nfn a() {}\n```\nmiddle\n~~~\n```\nx\n~~~\n```Python {linenos}\nunclosed";
+        let (blocks, prose) = split_fences(text);
+
+        let blocks: Vec<(Option<&str>, &str)> = blocks.iter().map(|b| (b.language.as_deref(), b.code)).collect();
+        assert_eq!(blocks, [
+            (Some("rust"), "fn a() {}\n"),
+            // A different marker does not close the fence
+            (None, "```\nx\n"),
+            (Some("python"), "unclosed"),
+        ]);
+        assert_eq!(prose, "Intro\nmiddle\n");
+    }
+
+    #[test]
+    fn paths_skip_urls_and_drop_leading_dot_slash() {
+        let mut refs = CodeRefs::default();
+        refs.scan_paths(
+            "See https://github.com/org/repo/blob/main/src/lib.rs, ./src/core/parser.rs and Cargo.toml; \
+             not deploy@host:/etc/app.conf or version 1.2 or e.g. this.",
+        );
+        assert_eq!(refs.files, ["src/core/parser.rs", "Cargo.toml"]);
+    }
+
+    #[test]
+    fn qualified_symbols_are_split_into_parts() {
+        let mut refs = CodeRefs::default();
+        refs.push_symbol("Memory::search");
+        refs.push_symbol("self::x");
+        refs.push_symbol("search");
+        refs.push_symbol("2d");
+        assert_eq!(refs.symbols, ["Memory::search", "Memory", "search", "self::x"]);
+    }
+
+    #[test]
+    fn code_and_prose_symbols() {
+        let refs = scanned(concat!(
+            "Call `Memory::search` from handle_hook() and check TokenCache, but not the word Regular.\n",
+            "```rust\n",
+            "pub fn parse_lines(content: &str) {\n    serde_json::from_str(content);\n    if ok(x) {}\n}\n",
+            "```\n",
+        ));
+        assert_eq!(refs.languages, ["rust"]);
+        // Defined and called in the block, then named in prose
+        let expected = ["parse_lines", "serde_json::from_str", "from_str", "ok"];
+        for symbol in expected.into_iter().chain(["Memory::search", "handle_hook", "TokenCache"]) {
+            assert!(refs.symbols.iter().any(|s| s == symbol), "{} missing from {:?}", symbol, refs.symbols);
+        }
+        for word in ["if", "fn", "Regular", "Call", "content"] {
+            assert!(!refs.symbols.iter().any(|s| s == word), "{} in {:?}", word, refs.symbols);
+        }
+    }
+
+    #[test]
+    fn paths_match_by_whole_trailing_components() {
+        let path = "/work/app/src/core/parser.rs";
+        assert!(path_matches(path, "parser.rs"));
+        assert!(path_matches(path, "core/parser.rs"));
+        assert!(path_matches(path, "./src/core/parser.rs"));
+        assert!(path_matches(path, path));
+        assert!(!path_matches(path, "ser.rs"));
+        assert!(!path_matches(path, "src/core"));
+        assert!(!path_matches(path, "./"));
+        assert!(!path_matches(path, ""));
+    }
+}
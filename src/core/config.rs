@@ -20,6 +20,7 @@ pub fn default_config() -> Config {
             provider: "simple".to_string(), // Default to simple for reliability
             model: "nomic-embed-text".to_string(),
             dimension: 384, // Smaller dimension for faster processing
+            base_url: default_embeddings_base_url(),
         },
         context: ContextConfig {
             max_results: 5,
@@ -29,7 +30,16 @@ pub fn default_config() -> Config {
         hooks: HooksConfig {
             enabled: true,
             auto_inject: true,
+            prompt_output: default_prompt_output(),
+            reinject_after_turns: None,
+            reinject_on_topic_change: false,
+            session_state_ttl_days: default_session_state_ttl_days(),
         },
+        redaction: RedactionConfig::default(),
+        encryption: EncryptionConfig::default(),
+        retention: RetentionConfig::default(),
+        summarization: SummarizationConfig::default(),
+        ranking: RankingConfig::default(),
     }
 }
 
@@ -39,6 +49,11 @@ pub fn config_dir() -> Result<PathBuf> {
     Ok(home.join(".off-context"))
 }
 
+/// Directory of the rotated hook logs
+pub fn hook_log_dir() -> Result<PathBuf> {
+    Ok(config_dir()?.join("logs"))
+}
+
 /// Get the configuration file path
 pub fn config_file_path() -> Result<PathBuf> {
     Ok(config_dir()?.join("config.toml"))
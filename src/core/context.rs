@@ -0,0 +1,204 @@
+use crate::core::tokenizer::{count_tokens, truncate_to_tokens};
+
+/// Answers squeezed below this many tokens are not worth injecting
+const MIN_ANSWER_TOKENS: usize = 16;
+
+/// Share of an entry's budget the user question may take
+const QUESTION_SHARE: usize = 4;
+
+/// Greedy, token-budgeted assembly of injected context
+///
+/// Ranked exchanges are added in order. Each gets an even share of whatever budget is
+/// left across the exchanges still to come (so space a short one leaves unused flows
+/// to the next), truncated at sentence boundaries. An exchange that cannot fit even in
+/// truncated form is skipped.
+pub struct ContextBuilder {
+    budget: usize,
+    slots_left: usize,
+    separator: String,
+    separator_tokens: usize,
+    used: usize,
+    entries: Vec<String>,
+}
+
+/// Result of packing: the rendered entries and what they cost
+pub struct PackedContext {
+    pub entries: Vec<String>,
+    pub separator: String,
+    pub tokens_used: usize,
+}
+
+impl PackedContext {
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn join(&self) -> String {
+        self.entries.join(&self.separator)
+    }
+}
+
+impl ContextBuilder {
+    /// Pack up to `budget` tokens, spread over `expected_entries` exchanges
+    pub fn new(budget: usize, expected_entries: usize, separator: &str) -> Self {
+        Self {
+            budget,
+            slots_left: expected_entries.max(1),
+            separator: separator.to_string(),
+            separator_tokens: count_tokens(separator),
+            used: 0,
+            entries: Vec::new(),
+        }
+    }
+
+    /// Account for fixed text (headers, footers) surrounding the entries
+    pub fn reserve(&mut self, text: &str) {
+        self.used += count_tokens(text);
+    }
+
+    pub fn remaining(&self) -> usize {
+        self.budget.saturating_sub(self.used)
+    }
+
+    /// Add a question/answer pair rendered by `render(question, answer)`
+    ///
+    /// Returns whether the exchange made it in.
+    pub fn push_exchange(
+        &mut self,
+        question: &str,
+        answer: &str,
+        render: impl Fn(&str, &str) -> String,
+    ) -> bool {
+        let separator = if self.entries.is_empty() { 0 } else { self.separator_tokens };
+        let available = self.remaining().saturating_sub(separator);
+        let share = (available / self.slots_left.max(1)).max(MIN_ANSWER_TOKENS * 2);
+        let limit = share.min(available);
+        self.slots_left = self.slots_left.saturating_sub(1);
+
+        let question = truncate_to_tokens(question, limit / QUESTION_SHARE);
+        if question.is_empty() {
+            return false;
+        }
+
+        let frame_tokens = count_tokens(&render(&question, ""));
+        let answer_budget = limit.saturating_sub(frame_tokens);
+        if answer_budget < MIN_ANSWER_TOKENS.min(count_tokens(answer)) {
+            return false;
+        }
+
+        let answer = truncate_to_tokens(answer, answer_budget);
+        if answer.is_empty() {
+            return false;
+        }
+
+        let entry = render(&question, &answer);
+        let cost = count_tokens(&entry) + separator;
+        if cost > self.remaining() {
+            return false;
+        }
+
+        self.used += cost;
+        self.entries.push(entry);
+        true
+    }
+
+    /// Add an already rendered entry, truncated to its share of the budget
+    ///
+    /// Returns whether it made it in.
+    pub fn push_entry(&mut self, text: &str) -> bool {
+        let separator = if self.entries.is_empty() { 0 } else { self.separator_tokens };
+        let available = self.remaining().saturating_sub(separator);
+        let share = (available / self.slots_left.max(1)).max(MIN_ANSWER_TOKENS * 2);
+        self.slots_left = self.slots_left.saturating_sub(1);
+
+        let entry = truncate_to_tokens(text, share.min(available));
+        if entry.is_empty() {
+            return false;
+        }
+
+        self.used += count_tokens(&entry) + separator;
+        self.entries.push(entry);
+        true
+    }
+
+    pub fn finish(self) -> PackedContext {
+        PackedContext {
+            entries: self.entries,
+            separator: self.separator,
+            tokens_used: self.used,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn render(question: &str, answer: &str) -> String {
+        format!("Q: {}\nA: {}", question, answer)
+    }
+
+    fn answer(sentences: usize) -> String {
+        "The handler validates the token and returns early on failure. ".repeat(sentences)
+    }
+
+    #[test]
+    fn never_exceeds_the_budget() {
+        for budget in [40, 100, 250, 1000] {
+            let mut builder = ContextBuilder::new(budget, 4, "\n\n");
+            builder.reserve("[PREV: header]");
+            for n in 0..6 {
+                builder.push_exchange("How is the token checked?", &answer(n * 5 + 1), render);
+            }
+            let packed = builder.finish();
+            assert!(packed.tokens_used <= budget, "{} > {}", packed.tokens_used, budget);
+            let actual = count_tokens("[PREV: header]") + count_tokens(&packed.join());
+            assert!(actual <= budget, "{} > {}", actual, budget);
+        }
+    }
+
+    #[test]
+    fn unused_share_flows_to_later_entries() {
+        let mut builder = ContextBuilder::new(400, 2, "\n\n");
+        assert!(builder.push_exchange("Short?", "Yes.", render));
+        let left = builder.remaining();
+        assert!(builder.push_exchange("Long?", &answer(40), render));
+        let packed = builder.finish();
+
+        // The second entry gets everything the first left over, not just half
+        assert!(count_tokens(&packed.entries[1]) > 200, "{}", packed.entries[1]);
+        assert!(count_tokens(&packed.entries[1]) <= left);
+        assert!(packed.entries[1].ends_with("failure."), "{}", packed.entries[1]);
+    }
+
+    #[test]
+    fn long_answers_are_cut_at_sentences_and_questions_to_a_share() {
+        let mut builder = ContextBuilder::new(200, 1, "\n");
+        let question = "Why does it fail? ".repeat(40);
+        assert!(builder.push_exchange(&question, &answer(50), render));
+        let entry = &builder.finish().entries[0];
+        let (q, a) = entry.split_once("\nA: ").unwrap();
+        assert!(count_tokens(q) <= 200 / QUESTION_SHARE + 2, "{}", q);
+        assert!(a.ends_with("failure."), "{}", a);
+    }
+
+    #[test]
+    fn exchange_that_cannot_fit_is_skipped() {
+        let mut builder = ContextBuilder::new(30, 1, "\n");
+        builder.reserve(&"filler ".repeat(25));
+        assert!(!builder.push_exchange("How is the token checked?", &answer(3), render));
+        assert!(builder.finish().is_empty());
+    }
+
+    #[test]
+    fn separators_are_charged_between_entries() {
+        let separator = "\n\n---\n\n";
+        let mut builder = ContextBuilder::new(500, 3, separator);
+        assert!(builder.push_entry("Session digest: token checks moved to middleware."));
+        assert!(builder.push_entry("Another digest."));
+        let packed = builder.finish();
+        let parts: usize = packed.entries.iter().map(|entry| count_tokens(entry)).sum();
+        assert_eq!(packed.tokens_used, parts + count_tokens(separator));
+        assert_eq!(packed.join(), format!("Session digest: token checks moved to middleware.{}Another digest.", separator));
+    }
+}
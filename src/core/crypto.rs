@@ -0,0 +1,482 @@
+use anyhow::{bail, Context, Result};
+use argon2::{Algorithm, Argon2, Params, Version};
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+use chacha20poly1305::aead::{Aead, AeadCore, KeyInit, OsRng};
+use chacha20poly1305::aead::rand_core::RngCore;
+use chacha20poly1305::{XChaCha20Poly1305, XNonce};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use tracing::{debug, info, warn};
+
+use crate::core::config::config_dir;
+use crate::core::types::EncryptionConfig;
+
+/// Environment variable holding the store passphrase
+pub const PASSPHRASE_ENV: &str = "OFF_CONTEXT_PASSPHRASE";
+
+/// Environment variable holding the replacement passphrase during key rotation
+pub const NEW_PASSPHRASE_ENV: &str = "OFF_CONTEXT_NEW_PASSPHRASE";
+
+/// Key header stored next to the segments; it holds no secret material
+const HEADER_FILE: &str = "encryption.json";
+
+/// Prefix of every encrypted record: `enc:<key id>:<base64(nonce || ciphertext)>`
+const ENCRYPTED_PREFIX: &str = "enc:";
+
+/// Plaintext encrypted into each key's `check` to tell a wrong key from corruption
+const KEY_CHECK: &[u8] = b"off-context key check";
+
+const CIPHER_NAME: &str = "xchacha20poly1305";
+const NONCE_LEN: usize = 24;
+const KEY_LEN: usize = 32;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Header {
+    version: u32,
+    cipher: String,
+    /// Key new records are encrypted with
+    active: String,
+    /// Every key records may still be encrypted with; more than one only mid-rotation
+    keys: Vec<KeySpec>,
+    /// Set until the plaintext records from before encryption was enabled are rewritten
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pending_rewrite: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct KeySpec {
+    id: String,
+    #[serde(flatten)]
+    source: KeySource,
+    check: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "source", rename_all = "snake_case")]
+enum KeySource {
+    /// 32 random bytes in `~/.off-context/keys/<id>.key`
+    KeyFile,
+    /// Argon2id over the passphrase in `OFF_CONTEXT_PASSPHRASE`
+    Passphrase {
+        salt: String,
+        m_cost: u32,
+        t_cost: u32,
+        p_cost: u32,
+    },
+}
+
+impl KeySource {
+    fn label(&self) -> &'static str {
+        match self {
+            KeySource::KeyFile => "key file",
+            KeySource::Passphrase { .. } => "passphrase",
+        }
+    }
+}
+
+/// Authenticated encryption for store records
+///
+/// Holds every key listed in the header that could be derived, so records written
+/// before a rotation stay readable. Without an active key it only decrypts, and
+/// `encrypt` passes text through unchanged.
+pub struct Cipher {
+    keys: HashMap<String, XChaCha20Poly1305>,
+    active: Option<String>,
+}
+
+impl std::fmt::Debug for Cipher {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Cipher").field("active", &self.active).finish()
+    }
+}
+
+impl Cipher {
+    /// Encrypt a record with the active key
+    pub fn encrypt(&self, plaintext: &str) -> String {
+        let (id, key) = match self.active.as_ref().and_then(|id| self.keys.get(id).map(|k| (id, k))) {
+            Some(active) => active,
+            None => return plaintext.to_string(),
+        };
+
+        let nonce = XChaCha20Poly1305::generate_nonce(&mut OsRng);
+        let ciphertext = key
+            .encrypt(&nonce, plaintext.as_bytes())
+            .expect("XChaCha20-Poly1305 encryption cannot fail for in-memory buffers");
+
+        let mut payload = nonce.to_vec();
+        payload.extend_from_slice(&ciphertext);
+        format!("{}{}:{}", ENCRYPTED_PREFIX, id, BASE64.encode(payload))
+    }
+
+    /// Decrypt a record, passing plaintext records through
+    pub fn decrypt(&self, record: &str) -> Result<String> {
+        let rest = match record.strip_prefix(ENCRYPTED_PREFIX) {
+            Some(rest) => rest,
+            None => return Ok(record.to_string()),
+        };
+        let (id, payload) = rest.split_once(':').context("Malformed encrypted record")?;
+        let key = self.keys.get(id)
+            .with_context(|| format!("Record is encrypted with unavailable key {}", id))?;
+
+        let payload = BASE64.decode(payload.trim_end()).context("Malformed encrypted record")?;
+        if payload.len() < NONCE_LEN {
+            bail!("Encrypted record is truncated");
+        }
+        let (nonce, ciphertext) = payload.split_at(NONCE_LEN);
+        let plaintext = key
+            .decrypt(XNonce::from_slice(nonce), ciphertext)
+            .map_err(|_| anyhow::anyhow!("Encrypted record failed authentication"))?;
+
+        String::from_utf8(plaintext).context("Decrypted record is not valid UTF-8")
+    }
+
+    /// A copy that still reads every key but writes plaintext
+    pub fn decrypt_only(&self) -> Cipher {
+        Cipher { keys: self.keys.clone(), active: None }
+    }
+}
+
+/// Decode a record that may or may not be encrypted
+pub fn decode(cipher: Option<&Cipher>, record: &str) -> Result<String> {
+    match cipher {
+        Some(cipher) => cipher.decrypt(record),
+        None if record.starts_with(ENCRYPTED_PREFIX) => {
+            bail!("Memory store is encrypted but no key is configured")
+        }
+        None => Ok(record.to_string()),
+    }
+}
+
+/// Id of the key `record` is encrypted with, if it is encrypted
+pub fn key_id(record: &str) -> Option<&str> {
+    record
+        .strip_prefix(ENCRYPTED_PREFIX)
+        .and_then(|rest| rest.split_once(':'))
+        .map(|(id, _)| id)
+}
+
+/// Fail unless `record` is plaintext or encrypted with a key we hold
+///
+/// Unlike a failed `decode`, which a torn or corrupt line also causes, this means
+/// the store cannot be read at all.
+pub fn check_readable(cipher: Option<&Cipher>, record: &str) -> Result<()> {
+    let id = match key_id(record) {
+        Some(id) => id,
+        None => return Ok(()),
+    };
+    match cipher {
+        Some(cipher) if cipher.keys.contains_key(id) => Ok(()),
+        Some(_) => bail!(
+            "Memory store has records encrypted with key {} which is not available (finish `off-context encryption rotate`?)",
+            id
+        ),
+        None => bail!("Memory store is encrypted but no key is configured"),
+    }
+}
+
+/// Encode a record with the active key, if any
+pub fn encode(cipher: Option<&Cipher>, record: &str) -> String {
+    match cipher {
+        Some(cipher) => cipher.encrypt(record),
+        None => record.to_string(),
+    }
+}
+
+/// Encryption state of a store, readable without any key
+#[derive(Debug)]
+pub struct EncryptionStatus {
+    pub cipher: String,
+    pub key_source: &'static str,
+    pub key_id: String,
+    /// Keys left over from an interrupted rotation
+    pub retired_keys: usize,
+    /// Plaintext records from before encryption was enabled may still be on disk
+    pub pending_rewrite: bool,
+}
+
+pub fn status(db_dir: &Path) -> Result<Option<EncryptionStatus>> {
+    Ok(read_header(db_dir)?.map(|header| {
+        let active = header.keys.iter().find(|k| k.id == header.active);
+        EncryptionStatus {
+            cipher: header.cipher.clone(),
+            key_source: active.map_or("unknown", |k| k.source.label()),
+            key_id: header.active.clone(),
+            retired_keys: header.keys.len().saturating_sub(1),
+            pending_rewrite: header.pending_rewrite,
+        }
+    }))
+}
+
+/// Open the store's keys, setting encryption up first if `config` asks for it
+///
+/// Returns the cipher (if the store is encrypted) and whether plaintext records
+/// may still be on disk: encryption was just set up, or a process that set it up
+/// died before rewriting them. Call `finish_rewrite` once they are.
+pub fn open(db_dir: &Path, config: &EncryptionConfig) -> Result<(Option<Cipher>, bool)> {
+    if let Some(header) = read_header(db_dir)? {
+        if !config.enabled {
+            debug!("Store is encrypted; `encryption.enabled = false` only takes effect via `off-context encryption disable`");
+        }
+        return Ok((Some(unlock(&header)?), header.pending_rewrite));
+    }
+
+    if !config.enabled {
+        return Ok((None, false));
+    }
+
+    let (spec, key) = create_key(&config.key_source, PASSPHRASE_ENV)?;
+    let header = Header {
+        version: 1,
+        cipher: CIPHER_NAME.to_string(),
+        active: spec.id.clone(),
+        keys: vec![spec],
+        pending_rewrite: true,
+    };
+    write_header(db_dir, &header)?;
+    info!("Enabled encryption at rest ({})", config.key_source);
+
+    let mut keys = HashMap::new();
+    keys.insert(header.active.clone(), key);
+    Ok((Some(Cipher { keys, active: Some(header.active) }), true))
+}
+
+/// Record that no plaintext records from before encryption are left on disk
+pub fn finish_rewrite(db_dir: &Path) -> Result<()> {
+    let mut header = read_header(db_dir)?.context("Memory store is not encrypted")?;
+    if header.pending_rewrite {
+        header.pending_rewrite = false;
+        write_header(db_dir, &header)?;
+    }
+    Ok(())
+}
+
+/// Add a fresh key from `config` as the active one, keeping the old keys readable
+///
+/// Call `finish_rotation` once every record has been rewritten with the new key.
+pub fn begin_rotation(db_dir: &Path, config: &EncryptionConfig) -> Result<Cipher> {
+    let mut header = read_header(db_dir)?.context("Memory store is not encrypted")?;
+    let mut cipher = unlock(&header)?;
+
+    let passphrase_env = match config.key_source.as_str() {
+        "passphrase" => NEW_PASSPHRASE_ENV,
+        _ => PASSPHRASE_ENV,
+    };
+    let (spec, key) = create_key(&config.key_source, passphrase_env)?;
+
+    header.active = spec.id.clone();
+    header.keys.push(spec);
+    write_header(db_dir, &header)?;
+
+    cipher.keys.insert(header.active.clone(), key);
+    cipher.active = Some(header.active);
+    Ok(cipher)
+}
+
+/// Drop every key but the active one
+///
+/// `in_use` are the key ids still found on disk; a retired key that another process
+/// wrote with mid-rotation is kept, and the rotation has to be re-run.
+pub fn finish_rotation(db_dir: &Path, in_use: &HashSet<String>) -> Result<()> {
+    let mut header = read_header(db_dir)?.context("Memory store is not encrypted")?;
+    if let Some(stale) = header.keys.iter().find(|k| k.id != header.active && in_use.contains(&k.id)) {
+        bail!("Records are still encrypted with retired key {}; run the rotation again", stale.id);
+    }
+    let (active, retired): (Vec<KeySpec>, Vec<KeySpec>) =
+        header.keys.into_iter().partition(|k| k.id == header.active);
+    header.keys = active;
+    write_header(db_dir, &header)?;
+
+    for spec in retired {
+        remove_key_file(&spec);
+    }
+    Ok(())
+}
+
+/// Forget the keys once every record has been rewritten in plaintext
+pub fn remove(db_dir: &Path, in_use: &HashSet<String>) -> Result<()> {
+    if let Some(id) = in_use.iter().next() {
+        bail!("Records are still encrypted with key {}; run the command again", id);
+    }
+    if let Some(header) = read_header(db_dir)? {
+        std::fs::remove_file(db_dir.join(HEADER_FILE))
+            .context("Failed to remove encryption header")?;
+        for spec in &header.keys {
+            remove_key_file(spec);
+        }
+    }
+    Ok(())
+}
+
+/// Derive every key in the header; the active one is required
+fn unlock(header: &Header) -> Result<Cipher> {
+    if header.cipher != CIPHER_NAME {
+        bail!("Unsupported store cipher: {}", header.cipher);
+    }
+
+    let mut keys = HashMap::new();
+    for spec in &header.keys {
+        match load_key(spec) {
+            Ok(key) => {
+                keys.insert(spec.id.clone(), key);
+            }
+            Err(e) if spec.id == header.active => return Err(e),
+            Err(e) => warn!("Retired key {} is unavailable: {}", spec.id, e),
+        }
+    }
+
+    Ok(Cipher { keys, active: Some(header.active.clone()) })
+}
+
+/// Load or derive the key for `spec` and verify it against its check value
+///
+/// A passphrase key is derived again in every process, hooks included: about 25 ms
+/// at the default Argon2id parameters (19 MiB, 2 passes) on one server core, next to
+/// ~100 ms for the whole prompt hook. It is deliberately not cached, since a derived
+/// key on disk would be no better protected than a key file.
+fn load_key(spec: &KeySpec) -> Result<XChaCha20Poly1305> {
+    let candidates: Vec<[u8; KEY_LEN]> = match &spec.source {
+        KeySource::KeyFile => vec![read_key_file(&key_file_path(&spec.id)?)?],
+        KeySource::Passphrase { salt, m_cost, t_cost, p_cost } => {
+            let salt = BASE64.decode(salt).context("Malformed passphrase salt")?;
+            let params = Params::new(*m_cost, *t_cost, *p_cost, Some(KEY_LEN))
+                .map_err(|e| anyhow::anyhow!("Invalid key derivation parameters: {}", e))?;
+            // Mid-rotation the header can hold keys for both passphrases
+            [PASSPHRASE_ENV, NEW_PASSPHRASE_ENV]
+                .iter()
+                .filter_map(|var| std::env::var(var).ok())
+                .map(|passphrase| derive_key(&passphrase, &salt, params.clone()))
+                .collect::<Result<_>>()?
+        }
+    };
+
+    if candidates.is_empty() {
+        bail!("Memory store is encrypted with a passphrase; set {}", PASSPHRASE_ENV);
+    }
+
+    let check = Cipher { keys: HashMap::new(), active: None };
+    for bytes in candidates {
+        let key = XChaCha20Poly1305::new((&bytes).into());
+        let mut probe = check.decrypt_only();
+        probe.keys.insert(spec.id.clone(), key.clone());
+        if probe.decrypt(&spec.check).is_ok_and(|text| text.as_bytes() == KEY_CHECK) {
+            return Ok(key);
+        }
+    }
+
+    bail!("Wrong {} for encrypted memory store (key {})", spec.source.label(), spec.id)
+}
+
+/// Create a new key of the given kind, returning its header entry and the key
+fn create_key(key_source: &str, passphrase_env: &str) -> Result<(KeySpec, XChaCha20Poly1305)> {
+    let id = random_id();
+    let (source, bytes) = match key_source {
+        "key_file" => {
+            let mut bytes = [0u8; KEY_LEN];
+            OsRng.fill_bytes(&mut bytes);
+            write_key_file(&key_file_path(&id)?, &bytes)?;
+            (KeySource::KeyFile, bytes)
+        }
+        "passphrase" => {
+            let passphrase = std::env::var(passphrase_env)
+                .with_context(|| format!("Set {} to encrypt with a passphrase", passphrase_env))?;
+            if passphrase.is_empty() {
+                bail!("{} is empty", passphrase_env);
+            }
+            let mut salt = [0u8; 16];
+            OsRng.fill_bytes(&mut salt);
+            let params = Params::default();
+            let bytes = derive_key(&passphrase, &salt, params.clone())?;
+            let source = KeySource::Passphrase {
+                salt: BASE64.encode(salt),
+                m_cost: params.m_cost(),
+                t_cost: params.t_cost(),
+                p_cost: params.p_cost(),
+            };
+            (source, bytes)
+        }
+        other => bail!("Unknown encryption key_source `{}` (expected \"key_file\" or \"passphrase\")", other),
+    };
+
+    let key = XChaCha20Poly1305::new((&bytes).into());
+    let mut keys = HashMap::new();
+    keys.insert(id.clone(), key.clone());
+    let check = Cipher { keys, active: Some(id.clone()) }
+        .encrypt(std::str::from_utf8(KEY_CHECK).expect("ASCII key check"));
+
+    Ok((KeySpec { id, source, check }, key))
+}
+
+fn derive_key(passphrase: &str, salt: &[u8], params: Params) -> Result<[u8; KEY_LEN]> {
+    let mut key = [0u8; KEY_LEN];
+    Argon2::new(Algorithm::Argon2id, Version::V0x13, params)
+        .hash_password_into(passphrase.as_bytes(), salt, &mut key)
+        .map_err(|e| anyhow::anyhow!("Key derivation failed: {}", e))?;
+    Ok(key)
+}
+
+fn random_id() -> String {
+    let mut bytes = [0u8; 6];
+    OsRng.fill_bytes(&mut bytes);
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// Location of a managed key file
+fn key_file_path(id: &str) -> Result<PathBuf> {
+    Ok(config_dir()?.join("keys").join(format!("{}.key", id)))
+}
+
+fn read_key_file(path: &Path) -> Result<[u8; KEY_LEN]> {
+    let content = std::fs::read_to_string(path)
+        .with_context(|| format!("Failed to read key file {}", path.display()))?;
+    let bytes = BASE64.decode(content.trim())
+        .with_context(|| format!("Malformed key file {}", path.display()))?;
+    bytes.try_into()
+        .map_err(|_| anyhow::anyhow!("Key file {} does not hold a {}-byte key", path.display(), KEY_LEN))
+}
+
+fn write_key_file(path: &Path, key: &[u8]) -> Result<()> {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent).context("Failed to create key directory")?;
+    }
+    std::fs::write(path, BASE64.encode(key)).context("Failed to write key file")?;
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        std::fs::set_permissions(path, std::fs::Permissions::from_mode(0o600))
+            .context("Failed to restrict key file permissions")?;
+    }
+    Ok(())
+}
+
+fn remove_key_file(spec: &KeySpec) {
+    if let KeySource::KeyFile = spec.source {
+        if let Ok(path) = key_file_path(&spec.id) {
+            if let Err(e) = std::fs::remove_file(&path) {
+                warn!("Failed to remove retired key file {}: {}", path.display(), e);
+            }
+        }
+    }
+}
+
+fn read_header(db_dir: &Path) -> Result<Option<Header>> {
+    let path = db_dir.join(HEADER_FILE);
+    match std::fs::read(&path) {
+        Ok(content) => serde_json::from_slice(&content)
+            .map(Some)
+            .context("Failed to parse encryption header"),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e).context("Failed to read encryption header"),
+    }
+}
+
+fn write_header(db_dir: &Path, header: &Header) -> Result<()> {
+    std::fs::create_dir_all(db_dir).context("Failed to create storage directory")?;
+    let path = db_dir.join(HEADER_FILE);
+    let tmp_path = path.with_extension("json.tmp");
+    let content = serde_json::to_vec_pretty(header).context("Failed to serialize encryption header")?;
+    std::fs::write(&tmp_path, content).context("Failed to write encryption header")?;
+    std::fs::rename(&tmp_path, &path).context("Failed to install encryption header")?;
+    Ok(())
+}
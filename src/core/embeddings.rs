@@ -1,22 +1,290 @@
 use anyhow::Result;
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use tracing::{debug, warn};
 
-/// Placeholder for future ML embeddings functionality
-/// Currently not used since we're using simple JSON storage
-#[derive(Debug)]
-pub struct EmbeddingGenerator;
+use crate::core::hash::fnv1a;
+use crate::core::types::EmbeddingsConfig;
+
+/// An embedding vector tagged with the model that produced it
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Embedding {
+    pub model: String,
+    pub vector: Vec<f32>,
+}
+
+/// Something that can turn text into a fixed-size vector
+#[async_trait]
+pub trait EmbeddingProvider: Send + Sync {
+    /// Identifier stored alongside each vector; vectors from different models are never compared
+    fn model_id(&self) -> String;
+
+    async fn embed(&self, text: &str) -> Result<Vec<f32>>;
+
+    async fn is_available(&self) -> bool;
+}
+
+/// Offline vectorizer using the hashing trick over words and word bigrams
+///
+/// Each token is hashed into one of `dimension` buckets with a hash-derived sign,
+/// weighted by sublinear term frequency, and the result is L2-normalized. No model
+/// download or network access is required.
+pub struct HashingVectorizer {
+    dimension: usize,
+}
+
+impl HashingVectorizer {
+    pub fn new(dimension: usize) -> Self {
+        Self { dimension: dimension.max(1) }
+    }
+
+    pub fn vectorize(&self, text: &str) -> Vec<f32> {
+        let words: Vec<String> = text
+            .split(|c: char| !c.is_alphanumeric() && c != '_')
+            .filter(|w| w.len() > 1)
+            .map(|w| w.to_lowercase())
+            .collect();
+
+        let mut counts: HashMap<String, usize> = HashMap::new();
+        for word in &words {
+            *counts.entry(word.clone()).or_default() += 1;
+        }
+        for pair in words.windows(2) {
+            *counts.entry(format!("{} {}", pair[0], pair[1])).or_default() += 1;
+        }
+
+        let mut vector = vec![0.0f32; self.dimension];
+        for (token, count) in counts {
+            let hash = fnv1a(token.as_bytes());
+            let bucket = (hash % self.dimension as u64) as usize;
+            let sign = if hash >> 63 == 0 { 1.0 } else { -1.0 };
+            vector[bucket] += sign * (1.0 + (count as f32).ln());
+        }
+
+        normalize(&mut vector);
+        vector
+    }
+}
+
+#[async_trait]
+impl EmbeddingProvider for HashingVectorizer {
+    fn model_id(&self) -> String {
+        format!("hashing-{}", self.dimension)
+    }
+
+    async fn embed(&self, text: &str) -> Result<Vec<f32>> {
+        Ok(self.vectorize(text))
+    }
+
+    async fn is_available(&self) -> bool {
+        true
+    }
+}
+
+/// Client for an Ollama-compatible `/api/embeddings` endpoint
+#[cfg(feature = "ml")]
+pub struct OllamaProvider {
+    client: reqwest::Client,
+    base_url: String,
+    model: String,
+}
+
+#[cfg(feature = "ml")]
+impl OllamaProvider {
+    pub fn new(base_url: &str, model: &str) -> Result<Self> {
+        let client = reqwest::Client::builder()
+            .timeout(std::time::Duration::from_secs(5))
+            .build()?;
+
+        Ok(Self {
+            client,
+            base_url: base_url.trim_end_matches('/').to_string(),
+            model: model.to_string(),
+        })
+    }
+}
+
+#[cfg(feature = "ml")]
+#[async_trait]
+impl EmbeddingProvider for OllamaProvider {
+    fn model_id(&self) -> String {
+        self.model.clone()
+    }
+
+    async fn embed(&self, text: &str) -> Result<Vec<f32>> {
+        #[derive(Deserialize)]
+        struct EmbeddingResponse {
+            embedding: Vec<f32>,
+        }
+
+        let response: EmbeddingResponse = self.client
+            .post(format!("{}/api/embeddings", self.base_url))
+            .json(&serde_json::json!({ "model": self.model, "prompt": text }))
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+
+        if response.embedding.is_empty() {
+            anyhow::bail!("Embedding endpoint returned an empty vector");
+        }
+
+        let mut vector = response.embedding;
+        normalize(&mut vector);
+        Ok(vector)
+    }
+
+    async fn is_available(&self) -> bool {
+        self.client
+            .get(format!("{}/api/tags", self.base_url))
+            .timeout(std::time::Duration::from_millis(500))
+            .send()
+            .await
+            .map(|r| r.status().is_success())
+            .unwrap_or(false)
+    }
+}
+
+/// Embedding pipeline selected from `EmbeddingsConfig`
+///
+/// `provider = "simple"` disables embeddings (keyword search only), `"hashing"` uses
+/// the offline vectorizer, and `"ollama"` calls the configured endpoint when built
+/// with the `ml` feature. A failing remote provider falls back to hashing for the
+/// rest of the process.
+pub struct EmbeddingGenerator {
+    primary: Option<Box<dyn EmbeddingProvider>>,
+    fallback: HashingVectorizer,
+    primary_failed: AtomicBool,
+    enabled: bool,
+}
+
+impl std::fmt::Debug for EmbeddingGenerator {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("EmbeddingGenerator")
+            .field("primary", &self.primary.as_ref().map(|p| p.model_id()))
+            .field("enabled", &self.enabled)
+            .finish()
+    }
+}
 
 impl EmbeddingGenerator {
-    /// Create a new embedding generator (placeholder)
-    pub async fn new() -> Result<Self> {
-        Ok(Self)
+    /// Create a new embedding generator from configuration
+    pub async fn new(config: &EmbeddingsConfig) -> Result<Self> {
+        let fallback = HashingVectorizer::new(config.dimension);
+
+        let (primary, enabled): (Option<Box<dyn EmbeddingProvider>>, bool) =
+            match config.provider.as_str() {
+                "hashing" => (None, true),
+                "ollama" => (Self::remote_provider(config)?, true),
+                _ => (None, false),
+            };
+
+        Ok(Self {
+            primary,
+            fallback,
+            primary_failed: AtomicBool::new(false),
+            enabled,
+        })
     }
 
-    /// Check if Ollama is available (placeholder - always returns false)
+    #[cfg(feature = "ml")]
+    fn remote_provider(config: &EmbeddingsConfig) -> Result<Option<Box<dyn EmbeddingProvider>>> {
+        Ok(Some(Box::new(OllamaProvider::new(&config.base_url, &config.model)?)))
+    }
+
+    #[cfg(not(feature = "ml"))]
+    fn remote_provider(_config: &EmbeddingsConfig) -> Result<Option<Box<dyn EmbeddingProvider>>> {
+        warn!("Ollama embeddings require the `ml` feature; using the offline hashing vectorizer");
+        Ok(None)
+    }
+
+    /// Whether embeddings should be generated at all
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    /// Check if the Ollama endpoint is reachable
     pub async fn is_ollama_available(&self) -> bool {
-        false
+        match &self.primary {
+            Some(provider) => provider.is_available().await,
+            None => false,
+        }
+    }
+
+    /// Human-readable name of the provider currently in use
+    pub fn provider_name(&self) -> String {
+        if !self.enabled {
+            return "simple (keyword only)".to_string();
+        }
+        match &self.primary {
+            Some(provider) if !self.primary_failed.load(Ordering::Relaxed) => provider.model_id(),
+            _ => format!("{} (offline)", self.fallback.model_id()),
+        }
+    }
+
+    /// Model id of the offline vectorizer, which can always be computed on the fly
+    pub fn fallback_model_id(&self) -> String {
+        self.fallback.model_id()
+    }
+
+    /// Offline vector for `text`, regardless of the configured provider
+    pub fn fallback_embedding(&self, text: &str) -> Vec<f32> {
+        self.fallback.vectorize(text)
+    }
+
+    /// Generate an embedding, falling back to the offline vectorizer if the provider fails
+    pub async fn generate_embedding(&self, text: &str) -> Result<Embedding> {
+        if let Some(provider) = &self.primary {
+            if !self.primary_failed.load(Ordering::Relaxed) {
+                match provider.embed(text).await {
+                    Ok(vector) => {
+                        return Ok(Embedding { model: provider.model_id(), vector });
+                    }
+                    Err(e) => {
+                        warn!("Embedding provider failed, using offline vectorizer: {}", e);
+                        self.primary_failed.store(true, Ordering::Relaxed);
+                    }
+                }
+            }
+        }
+
+        debug!("Generating offline embedding");
+        Ok(Embedding {
+            model: self.fallback.model_id(),
+            vector: self.fallback.vectorize(text),
+        })
+    }
+}
+
+/// Cosine similarity between two vectors (0.0 when dimensions differ)
+pub fn cosine_similarity(a: &[f32], b: &[f32]) -> f32 {
+    if a.len() != b.len() || a.is_empty() {
+        return 0.0;
+    }
+
+    let dot: f32 = a.iter().zip(b).map(|(x, y)| x * y).sum();
+    let norm_a: f32 = a.iter().map(|x| x * x).sum::<f32>().sqrt();
+    let norm_b: f32 = b.iter().map(|x| x * x).sum::<f32>().sqrt();
+
+    if norm_a == 0.0 || norm_b == 0.0 {
+        0.0
+    } else {
+        dot / (norm_a * norm_b)
+    }
+}
+
+fn normalize(vector: &mut [f32]) {
+    let norm: f32 = vector.iter().map(|x| x * x).sum::<f32>().sqrt();
+    if norm > 0.0 {
+        for x in vector.iter_mut() {
+            *x /= norm;
+        }
     }
 }
 
 /// Type alias for compatibility
 #[allow(dead_code)]
-pub type EmbeddingsService = EmbeddingGenerator;
\ No newline at end of file
+pub type EmbeddingsService = EmbeddingGenerator;
@@ -0,0 +1,602 @@
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+use std::sync::atomic::{AtomicU64, Ordering};
+use tracing::{debug, warn};
+use uuid::Uuid;
+
+use crate::core::crypto::{self, Cipher};
+use crate::core::hash::fnv1a;
+use crate::core::passage::{self, exchange_text, Passage};
+use crate::core::types::Conversation;
+
+/// BM25 term-frequency saturation
+const K1: f32 = 1.2;
+
+/// BM25 document-length normalization
+const B: f32 = 0.75;
+
+/// Bump when tokenization changes so stale snapshots are rebuilt
+const INDEX_VERSION: u32 = 3;
+
+/// Trigram overlap (Dice coefficient) a term needs before edit distance is checked
+const MIN_TRIGRAM_SIMILARITY: f32 = 0.3;
+
+/// Words too common to carry any signal
+const STOPWORDS: &[&str] = &[
+    "a", "about", "after", "all", "also", "an", "and", "any", "are", "as", "at", "be",
+    "because", "been", "before", "but", "by", "can", "could", "did", "do", "does", "doing",
+    "for", "from", "had", "has", "have", "he", "her", "here", "him", "his", "how", "i",
+    "if", "in", "into", "is", "it", "its", "just", "me", "more", "my", "no", "not", "now",
+    "of", "on", "only", "or", "other", "our", "out", "over", "she", "should", "so", "some",
+    "such", "than", "that", "the", "their", "them", "then", "there", "these", "they", "this",
+    "those", "to", "too", "up", "us", "very", "was", "we", "were", "what", "when", "where",
+    "which", "while", "who", "why", "will", "with", "would", "you", "your",
+];
+
+/// Split text into normalized index terms: lowercased, stopwords dropped, stemmed
+pub fn tokenize(text: &str) -> Vec<String> {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(|word| word.to_lowercase())
+        .filter(|word| !STOPWORDS.contains(&word.as_str()))
+        .map(|word| stem(&word))
+        .collect()
+}
+
+/// Light suffix-stripping stemmer (plural and -ed/-ing/-ly forms)
+///
+/// Conservative on purpose: it never reduces a word below three characters, only
+/// strips a suffix when what remains has a vowel after its first letter ("embed" and
+/// "apply" stay whole) and only undoubles consonants ("freely" -> "free").
+pub fn stem(word: &str) -> String {
+    if word.len() <= 3 || !word.chars().all(|c| c.is_ascii_alphabetic()) {
+        return word.to_string();
+    }
+
+    let mut w = word.to_string();
+
+    // Plurals
+    if let Some(base) = w.strip_suffix("sses") {
+        w = format!("{}ss", base);
+    } else if let Some(base) = w.strip_suffix("ies") {
+        w = format!("{}y", base);
+    } else if w.ends_with('s') && !w.ends_with("ss") && !w.ends_with("us") && !w.ends_with("is") {
+        w.pop();
+    }
+
+    // Verb and adverb forms
+    for suffix in ["ingly", "edly", "ing", "ed", "ly"] {
+        if let Some(base) = w.strip_suffix(suffix) {
+            if base.len() >= 3 && base.chars().skip(1).any(is_vowel) {
+                w = base.to_string();
+                // "stopped" -> "stop", "running" -> "run"
+                let bytes = w.as_bytes();
+                let n = bytes.len();
+                if n >= 2
+                    && bytes[n - 1] == bytes[n - 2]
+                    && !is_vowel(bytes[n - 1] as char)
+                    && !matches!(bytes[n - 1], b'l' | b's' | b'z')
+                {
+                    w.pop();
+                }
+                break;
+            }
+        }
+    }
+
+    // Normalize a trailing "y" so "query"/"queries" and "retry"/"retried" agree
+    if w.len() > 3 && w.ends_with('y') {
+        w.pop();
+        w.push('i');
+    }
+
+    w
+}
+
+fn is_vowel(c: char) -> bool {
+    matches!(c, 'a' | 'e' | 'i' | 'o' | 'u' | 'y')
+}
+
+/// Byte ranges of the words in `text` whose index term is one of `terms`
+pub fn term_spans(text: &str, terms: &HashSet<String>) -> Vec<(usize, usize)> {
+    let mut spans = Vec::new();
+    let mut start = None;
+    for (i, c) in text.char_indices().chain(std::iter::once((text.len(), ' '))) {
+        match (c.is_alphanumeric(), start) {
+            (true, None) => start = Some(i),
+            (false, Some(s)) => {
+                let word = text[s..i].to_lowercase();
+                if !STOPWORDS.contains(&word.as_str()) && terms.contains(&stem(&word)) {
+                    spans.push((s, i));
+                }
+                start = None;
+            }
+            _ => {}
+        }
+    }
+    spans
+}
+
+/// Edits a query word of this many characters may be away from an indexed term
+fn max_edits(length: usize) -> usize {
+    match length {
+        0..=3 => 0,
+        4..=5 => 1,
+        6..=9 => 2,
+        _ => 3,
+    }
+}
+
+/// Padded character trigrams, so word edges count too
+fn trigrams(term: &str) -> HashSet<String> {
+    let padded: Vec<char> = format!("${}$", term).chars().collect();
+    padded.windows(3).map(|w| w.iter().collect()).collect()
+}
+
+/// Levenshtein distance over characters
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut row: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut diagonal = row[0];
+        row[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let substituted = diagonal + usize::from(ca != *cb);
+            diagonal = row[j + 1];
+            row[j + 1] = substituted.min(row[j] + 1).min(diagonal + 1);
+        }
+    }
+    row[b.len()]
+}
+
+/// Per-passage entry kept in the persisted snapshot
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct DocEntry {
+    passage: Passage,
+    /// Hash of the whole exchange, used to detect conversations rewritten in place
+    fingerprint: u64,
+    length: u32,
+    terms: HashMap<String, u32>,
+}
+
+/// A conversation's best-scoring passage
+#[derive(Debug, Clone, Copy)]
+pub struct Hit {
+    pub passage: Passage,
+    pub score: f32,
+}
+
+impl Hit {
+    pub fn conversation_id(&self) -> Uuid {
+        self.passage.conversation_id
+    }
+}
+
+#[derive(Deserialize)]
+struct IndexSnapshot {
+    version: u32,
+    docs: HashMap<Uuid, DocEntry>,
+}
+
+/// BM25 inverted index over conversation passages
+///
+/// Each passage (see `passage::split`) is its own document, and a conversation scores
+/// as its best passage. The snapshot on disk holds per-passage term frequencies;
+/// postings are rebuilt in memory on load. Conversations that changed since the
+/// snapshot was written are re-tokenized by `reconcile`, so the snapshot only needs
+/// saving occasionally.
+#[derive(Debug, Default)]
+pub struct InvertedIndex {
+    /// By passage id
+    docs: HashMap<Uuid, DocEntry>,
+    /// Passage ids by conversation id
+    passages: HashMap<Uuid, Vec<Uuid>>,
+    postings: HashMap<String, HashMap<Uuid, u32>>,
+    /// Vocabulary by trigram, for fuzzy lookups; rebuilt with the postings
+    trigrams: HashMap<String, HashSet<String>>,
+    total_length: u64,
+}
+
+impl InvertedIndex {
+    /// Load a snapshot, returning an empty index if it is missing or stale
+    pub async fn load(path: &Path, cipher: Option<&Cipher>) -> Self {
+        let content = match tokio::fs::read_to_string(path).await {
+            Ok(content) => content,
+            Err(_) => return Self::default(),
+        };
+        let content = match crypto::decode(cipher, &content) {
+            Ok(content) => content,
+            Err(e) => {
+                warn!("Ignoring unreadable search index: {}", e);
+                return Self::default();
+            }
+        };
+
+        match serde_json::from_str::<IndexSnapshot>(&content) {
+            Ok(snapshot) if snapshot.version == INDEX_VERSION => {
+                let mut index = Self::default();
+                for (id, entry) in snapshot.docs {
+                    index.insert_entry(id, entry);
+                }
+                debug!("Loaded search index with {} passages", index.docs.len());
+                index
+            }
+            Ok(_) => {
+                debug!("Search index snapshot is from an older version, rebuilding");
+                Self::default()
+            }
+            Err(e) => {
+                warn!("Ignoring unreadable search index: {}", e);
+                Self::default()
+            }
+        }
+    }
+
+    /// Write the snapshot via temp file + rename
+    pub async fn save(&self, path: &Path, cipher: Option<&Cipher>) -> Result<()> {
+        Self::write_snapshot(path, self.to_snapshot_bytes()?, cipher).await
+    }
+
+    /// Serialize the snapshot without touching the filesystem
+    pub fn to_snapshot_bytes(&self) -> Result<Vec<u8>> {
+        #[derive(Serialize)]
+        struct SnapshotRef<'a> {
+            version: u32,
+            docs: &'a HashMap<Uuid, DocEntry>,
+        }
+
+        serde_json::to_vec(&SnapshotRef { version: INDEX_VERSION, docs: &self.docs })
+            .context("Failed to serialize search index")
+    }
+
+    /// Install serialized snapshot bytes at `path`, encrypted if there is a cipher
+    pub async fn write_snapshot(path: &Path, content: Vec<u8>, cipher: Option<&Cipher>) -> Result<()> {
+        let content = match cipher {
+            Some(cipher) => {
+                let text = String::from_utf8(content).context("Search index is not valid UTF-8")?;
+                cipher.encrypt(&text).into_bytes()
+            }
+            None => content,
+        };
+        // Hooks and the web server may save at the same time; each writer gets its own
+        // temp file so one rename never installs another's half-written snapshot
+        static WRITES: AtomicU64 = AtomicU64::new(0);
+        let tmp_path = path.with_extension(format!(
+            "json.{}-{}.tmp",
+            std::process::id(),
+            WRITES.fetch_add(1, Ordering::Relaxed)
+        ));
+        if let Err(e) = tokio::fs::write(&tmp_path, content).await {
+            let _ = tokio::fs::remove_file(&tmp_path).await;
+            return Err(e).context("Failed to write search index");
+        }
+        tokio::fs::rename(&tmp_path, path).await
+            .context("Failed to install search index")?;
+
+        debug!("Saved search index snapshot to {:?}", path);
+        Ok(())
+    }
+
+    /// Bring the index in line with `conversations`, returning how many documents changed
+    pub fn reconcile(&mut self, conversations: &HashMap<Uuid, Conversation>) -> usize {
+        let stale: Vec<Uuid> = self.passages
+            .keys()
+            .filter(|id| !conversations.contains_key(id))
+            .copied()
+            .collect();
+        let mut changed = stale.len();
+        for id in stale {
+            self.remove(&id);
+        }
+
+        for conversation in conversations.values() {
+            let fingerprint = fingerprint(&exchange_text(conversation));
+            let current = self.passages
+                .get(&conversation.id)
+                .and_then(|ids| self.docs.get(ids.first()?))
+                .map(|d| d.fingerprint);
+            if current != Some(fingerprint) {
+                self.upsert(conversation);
+                changed += 1;
+            }
+        }
+
+        changed
+    }
+
+    /// Index (or re-index) a conversation, passage by passage
+    pub fn upsert(&mut self, conversation: &Conversation) {
+        self.remove(&conversation.id);
+
+        let text = exchange_text(conversation);
+        let fingerprint = fingerprint(&text);
+        for passage in passage::split(conversation) {
+            let tokens = tokenize(&text[passage.start..passage.end]);
+            let mut terms: HashMap<String, u32> = HashMap::new();
+            for token in &tokens {
+                *terms.entry(token.clone()).or_default() += 1;
+            }
+
+            self.insert_entry(passage.id(), DocEntry {
+                passage,
+                fingerprint,
+                length: tokens.len() as u32,
+                terms,
+            });
+        }
+    }
+
+    /// Drop a conversation's passages from the index
+    pub fn remove(&mut self, conversation_id: &Uuid) {
+        for id in self.passages.remove(conversation_id).unwrap_or_default() {
+            let entry = match self.docs.remove(&id) {
+                Some(entry) => entry,
+                None => continue,
+            };
+            self.total_length -= entry.length as u64;
+            for term in entry.terms.keys() {
+                if let Some(posting) = self.postings.get_mut(term) {
+                    posting.remove(&id);
+                    if posting.is_empty() {
+                        self.postings.remove(term);
+                        self.forget_term(term);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Drop everything
+    pub fn clear(&mut self) {
+        *self = Self::default();
+    }
+
+    /// Score every conversation with a passage containing at least one query term,
+    /// best first
+    pub fn search(&self, query: &str) -> Vec<Hit> {
+        let terms: HashSet<String> = tokenize(query).into_iter().collect();
+        let weighted: Vec<(String, f32)> = terms.into_iter().map(|term| (term, 1.0)).collect();
+        self.search_weighted(&weighted)
+    }
+
+    /// Indexed terms within a few typos of the query words, weighted by closeness
+    ///
+    /// Candidates come from the trigram index and are kept when their edit distance
+    /// fits the word length (none for words of three characters or fewer), so
+    /// "AuthServce" finds "AuthService". Exact terms weigh 1.0.
+    pub fn fuzzy_terms(&self, query: &str) -> Vec<(String, f32)> {
+        let mut weights: HashMap<String, f32> = HashMap::new();
+
+        for word in tokenize(query) {
+            let length = word.chars().count();
+            let allowed = max_edits(length);
+            let grams = trigrams(&word);
+
+            let mut shared: HashMap<&str, usize> = HashMap::new();
+            for gram in &grams {
+                for term in self.trigrams.get(gram).into_iter().flatten() {
+                    *shared.entry(term.as_str()).or_default() += 1;
+                }
+            }
+
+            for (term, count) in shared {
+                // A padded term of n characters has n trigrams
+                let dice = 2.0 * count as f32 / (grams.len() + term.chars().count()) as f32;
+                if dice < MIN_TRIGRAM_SIMILARITY {
+                    continue;
+                }
+                let distance = edit_distance(&word, term);
+                if distance > allowed {
+                    continue;
+                }
+                let weight = 1.0 - distance as f32 / length.max(term.chars().count()) as f32;
+                let best = weights.entry(term.to_string()).or_default();
+                *best = best.max(weight);
+            }
+        }
+
+        let mut terms: Vec<(String, f32)> = weights.into_iter().collect();
+        terms.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap_or(std::cmp::Ordering::Equal).then(a.0.cmp(&b.0)));
+        terms
+    }
+
+    /// BM25 over already-normalized terms, each contribution scaled by its weight
+    pub fn search_weighted(&self, terms: &[(String, f32)]) -> Vec<Hit> {
+        let n = self.docs.len() as f32;
+        if n == 0.0 {
+            return Vec::new();
+        }
+        let avg_length = (self.total_length as f32 / n).max(1.0);
+
+        let mut scores: HashMap<Uuid, f32> = HashMap::new();
+
+        for (term, weight) in terms {
+            let posting = match self.postings.get(term) {
+                Some(posting) => posting,
+                None => continue,
+            };
+
+            let df = posting.len() as f32;
+            let idf = (1.0 + (n - df + 0.5) / (df + 0.5)).ln();
+
+            for (id, tf) in posting {
+                let length = self.docs[id].length as f32;
+                let tf = *tf as f32;
+                let norm = tf * (K1 + 1.0) / (tf + K1 * (1.0 - B + B * length / avg_length));
+                *scores.entry(*id).or_default() += weight * idf * norm;
+            }
+        }
+
+        // A conversation counts once, as its best passage
+        let mut best: HashMap<Uuid, Hit> = HashMap::new();
+        for (id, score) in scores {
+            let passage = self.docs[&id].passage;
+            let hit = best.entry(passage.conversation_id).or_insert(Hit { passage, score });
+            if score > hit.score || (score == hit.score && passage.ordinal < hit.passage.ordinal) {
+                *hit = Hit { passage, score };
+            }
+        }
+
+        let mut ranked: Vec<Hit> = best.into_values().collect();
+        ranked.sort_by(|a, b| b.score.partial_cmp(&a.score).unwrap_or(std::cmp::Ordering::Equal));
+        ranked
+    }
+
+    fn insert_entry(&mut self, id: Uuid, entry: DocEntry) {
+        self.total_length += entry.length as u64;
+        for (term, tf) in &entry.terms {
+            let posting = self.postings.entry(term.clone()).or_default();
+            if posting.is_empty() {
+                for gram in trigrams(term) {
+                    self.trigrams.entry(gram).or_default().insert(term.clone());
+                }
+            }
+            posting.insert(id, *tf);
+        }
+        self.passages.entry(entry.passage.conversation_id).or_default().push(id);
+        self.docs.insert(id, entry);
+    }
+
+    fn forget_term(&mut self, term: &str) {
+        for gram in trigrams(term) {
+            if let Some(terms) = self.trigrams.get_mut(&gram) {
+                terms.remove(term);
+                if terms.is_empty() {
+                    self.trigrams.remove(&gram);
+                }
+            }
+        }
+    }
+}
+
+fn fingerprint(text: &str) -> u64 {
+    fnv1a(text.as_bytes())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn index(conversations: &[Conversation]) -> InvertedIndex {
+        let mut index = InvertedIndex::default();
+        for conversation in conversations {
+            index.upsert(conversation);
+        }
+        index
+    }
+
+    fn sample(user: &str, assistant: &str) -> Conversation {
+        Conversation::sample("s", "2026-09-20T10:00:00Z", user, assistant)
+    }
+
+    #[test]
+    fn stem_folds_inflections_together() {
+        for (words, expected) in [
+            (&["stop", "stops", "stopped", "stopping"][..], "stop"),
+            (&["run", "runs", "running"][..], "run"),
+            (&["query", "queries", "queried"][..], "queri"),
+            (&["embed", "embeds", "embedded", "embedding"][..], "embed"),
+            (&["need", "needs", "needed", "needing"][..], "need"),
+            (&["apply", "applies", "applied", "applying"][..], "appli"),
+            (&["free", "frees", "freely"][..], "free"),
+            (&["class", "classes"][..], "class"),
+        ] {
+            for word in words {
+                assert_eq!(stem(word), expected, "{}", word);
+            }
+        }
+    }
+
+    #[test]
+    fn stem_leaves_short_and_non_alphabetic_words_alone() {
+        for word in ["bus", "status", "analysis", "string", "utf8", "sha256", "ed"] {
+            assert_eq!(stem(word), word);
+        }
+        assert_eq!(stem("calls"), "call");
+    }
+
+    #[test]
+    fn tokenize_lowercases_drops_stopwords_and_splits_identifiers() {
+        assert_eq!(tokenize("How does the Parser handle_hook tokens?"), ["parser", "handle", "hook", "token"]);
+        assert!(tokenize("what is it").is_empty());
+    }
+
+    #[test]
+    fn bm25_prefers_rarer_terms_and_higher_frequency() {
+        let common = sample("deploy the service", "deploy notes");
+        let rare = sample("deploy the service", "rollback notes");
+        let repeated = sample("rollback rollback", "rollback again after the failed deploy");
+        let index = index(&[common.clone(), rare.clone(), repeated.clone()]);
+
+        let ranked: Vec<Uuid> = index.search("deploy rollback").iter().map(Hit::conversation_id).collect();
+        assert_eq!(ranked, [repeated.id, rare.id, common.id]);
+
+        let hits = index.search("rollback");
+        assert_eq!(hits.len(), 2);
+        assert!(hits[0].score > hits[1].score);
+        assert!(index.search("kubernetes").is_empty());
+    }
+
+    #[test]
+    fn bm25_normalizes_for_passage_length() {
+        let short = sample("cache invalidation", "use a version key");
+        let long = sample("cache invalidation", &format!("use a version key {}", "and more unrelated filler words ".repeat(20)));
+        let hits = index(&[short.clone(), long]).search("invalidation");
+        assert_eq!(hits[0].conversation_id(), short.id);
+        assert!(hits[0].score > hits[1].score);
+    }
+
+    #[test]
+    fn weights_scale_term_contributions() {
+        let conversation = sample("tracing spans", "spans nest");
+        let index = index(std::slice::from_ref(&conversation));
+        let full = index.search_weighted(&[("span".to_string(), 1.0)])[0].score;
+        let half = index.search_weighted(&[("span".to_string(), 0.5)])[0].score;
+        assert!((full - 2.0 * half).abs() < 1e-6, "{} vs {}", full, half);
+    }
+
+    #[test]
+    fn remove_and_reconcile_keep_postings_in_step() {
+        let kept = sample("borrow checker", "lifetimes");
+        let dropped = sample("async runtime", "tokio");
+        let mut index = index(&[kept.clone(), dropped.clone()]);
+
+        index.remove(&dropped.id);
+        assert!(index.search("tokio").is_empty());
+        assert!(index.fuzzy_terms("tokoi").is_empty());
+
+        let mut edited = kept.clone();
+        edited.assistant_response = "lifetimes and tokio".to_string();
+        let conversations = HashMap::from([(edited.id, edited.clone())]);
+        assert_eq!(index.reconcile(&conversations), 1);
+        assert_eq!(index.reconcile(&conversations), 0);
+        assert_eq!(index.search("tokio")[0].conversation_id(), kept.id);
+    }
+
+    #[test]
+    fn fuzzy_terms_allow_typos_by_length() {
+        let index = index(&[sample("AuthService login", "the AuthService validates tokens")]);
+        let terms = index.fuzzy_terms("authservce");
+        assert_eq!(terms.first().map(|t| t.0.as_str()), Some("authservice"));
+        assert!(terms[0].1 < 1.0);
+        assert!(index.fuzzy_terms("lgn").is_empty());
+    }
+
+    #[tokio::test]
+    async fn snapshot_round_trips_and_leaves_no_temp_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("index.json");
+        let conversation = sample("snapshot test", "persisted postings");
+        index(std::slice::from_ref(&conversation)).save(&path, None).await.unwrap();
+
+        let loaded = InvertedIndex::load(&path, None).await;
+        assert_eq!(loaded.search("postings")[0].conversation_id(), conversation.id);
+        let names: Vec<String> = std::fs::read_dir(dir.path())
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+            .collect();
+        assert_eq!(names, ["index.json"]);
+    }
+}
@@ -0,0 +1,397 @@
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use fs2::FileExt;
+use serde::{Deserialize, Serialize};
+use std::fs::File;
+use std::path::{Path, PathBuf};
+use tracing::{debug, warn};
+
+use crate::core::hash::fnv1a;
+use crate::core::memory::{Memory, StoreOutcome};
+use crate::core::parser::{conversation_from_turn, parse_transcript};
+use crate::core::redact::Redactor;
+use crate::core::transcript::{self, Turn};
+use crate::core::types::Conversation;
+
+/// Bumped when parsing starts extracting more from a turn, so transcripts
+/// ingested by an older version are read again from the start
+const PARSER_VERSION: u32 = 1;
+
+/// How far a transcript file has been ingested
+///
+/// For JSONL transcripts `offset` points at the prompt line of the last turn seen,
+/// not the end of the file: that turn may still grow, so it is re-read on the next
+/// run and stored again (same deterministic id) if its content changed
+/// (`tail_fingerprint`).
+/// Other formats are re-imported only when the whole file's fingerprint changes.
+#[derive(Debug, Serialize, Deserialize)]
+struct Checkpoint {
+    path: String,
+    offset: u64,
+    /// `uuid` of the line at `offset`, to detect files rewritten under us
+    last_uuid: Option<String>,
+    tail_fingerprint: Option<u64>,
+    updated_at: Option<DateTime<Utc>>,
+    /// `PARSER_VERSION` the file was read with (0 before versions were recorded)
+    #[serde(default)]
+    parser_version: u32,
+}
+
+impl Default for Checkpoint {
+    fn default() -> Self {
+        Self {
+            path: String::new(),
+            offset: 0,
+            last_uuid: None,
+            tail_fingerprint: None,
+            updated_at: None,
+            parser_version: PARSER_VERSION,
+        }
+    }
+}
+
+/// Ingest whatever is new in `transcript_path`, returning how many conversations were stored
+///
+/// Every conversation passes through `redactor` before it reaches the store.
+pub async fn ingest_transcript(memory: &Memory, redactor: &Redactor, transcript_path: &Path) -> Result<usize> {
+    let path = transcript_path.canonicalize().unwrap_or_else(|_| transcript_path.to_path_buf());
+    let checkpoint_dir = memory.checkpoint_dir();
+    tokio::fs::create_dir_all(checkpoint_dir).await
+        .context("Failed to create checkpoint directory")?;
+
+    // Serialize concurrent hook runs so a turn is never ingested twice
+    let lock = File::create(checkpoint_dir.join(".lock"))
+        .context("Failed to open checkpoint lock file")?;
+    lock.lock_exclusive().context("Failed to lock checkpoints")?;
+
+    let checkpoint_path = checkpoint_file(checkpoint_dir, &path);
+    let checkpoint = load_checkpoint(&checkpoint_path, &path).await;
+
+    let content = tokio::fs::read_to_string(&path).await
+        .with_context(|| format!("Failed to read transcript {}", path.display()))?;
+
+    let (stored, next) = if transcript::is_jsonl_transcript(&content) {
+        ingest_jsonl(memory, redactor, &path, &content, checkpoint).await?
+    } else {
+        ingest_whole_file(memory, redactor, &path, &content, checkpoint).await?
+    };
+
+    save_checkpoint(&checkpoint_path, &next).await?;
+    debug!("Ingested {} conversations from {} (offset {})", stored, path.display(), next.offset);
+    Ok(stored)
+}
+
+async fn ingest_jsonl(
+    memory: &Memory,
+    redactor: &Redactor,
+    path: &Path,
+    content: &str,
+    mut checkpoint: Checkpoint,
+) -> Result<(usize, Checkpoint)> {
+    if !resumes_cleanly(content, &checkpoint) {
+        debug!("Transcript {} changed since its checkpoint, re-reading from the start", path.display());
+        checkpoint = Checkpoint { path: checkpoint.path, ..Checkpoint::default() };
+    }
+
+    // Only complete lines; a line still being written is picked up next time
+    let start = checkpoint.offset as usize;
+    let end = content.rfind('\n').map_or(0, |i| i + 1).max(start);
+    let lines = transcript::parse_lines_at(&content[start..end], checkpoint.offset);
+    let turns = transcript::assemble_turns(&lines);
+
+    let source = path.to_string_lossy();
+    let mut next = Checkpoint {
+        path: checkpoint.path.clone(),
+        offset: end as u64,
+        updated_at: Some(Utc::now()),
+        ..Checkpoint::default()
+    };
+    let mut stored = 0;
+
+    for turn in turns {
+        let is_tail = checkpoint.last_uuid.is_some() && turn.uuid == checkpoint.last_uuid;
+        next.offset = turn.offset;
+        next.last_uuid = turn.uuid.clone();
+        next.tail_fingerprint = None;
+
+        if turn.is_empty() {
+            continue;
+        }
+
+        // Checked before converting the turn, so an unchanged tail costs no
+        // tokenizer load or symbol scan
+        let fingerprint = turn_fingerprint(&turn);
+        next.tail_fingerprint = Some(fingerprint);
+        if is_tail && checkpoint.tail_fingerprint == Some(fingerprint) {
+            continue;
+        }
+
+        let conversation = conversation_from_turn(turn, &source);
+
+        if store_redacted(memory, redactor, conversation).await? {
+            stored += 1;
+        }
+    }
+
+    Ok((stored, next))
+}
+
+async fn ingest_whole_file(
+    memory: &Memory,
+    redactor: &Redactor,
+    path: &Path,
+    content: &str,
+    checkpoint: Checkpoint,
+) -> Result<(usize, Checkpoint)> {
+    let fingerprint = fnv1a(content.as_bytes());
+    if checkpoint.tail_fingerprint == Some(fingerprint) {
+        return Ok((0, checkpoint));
+    }
+
+    let conversations = parse_transcript(&path.to_string_lossy()).await
+        .context("Failed to parse transcript")?;
+    let mut stored = 0;
+    for conversation in conversations {
+        if store_redacted(memory, redactor, conversation).await? {
+            stored += 1;
+        }
+    }
+
+    let next = Checkpoint {
+        path: checkpoint.path,
+        offset: content.len() as u64,
+        tail_fingerprint: Some(fingerprint),
+        updated_at: Some(Utc::now()),
+        ..Checkpoint::default()
+    };
+    Ok((stored, next))
+}
+
+/// Redact and store a conversation, returning whether anything was written
+async fn store_redacted(memory: &Memory, redactor: &Redactor, mut conversation: Conversation) -> Result<bool> {
+    let report = redactor.redact_conversation(&mut conversation);
+    if !report.is_empty() {
+        debug!("Redacted {} secrets from conversation", report.total());
+        // Content-derived ids must be computed from what is actually stored
+        conversation.id = conversation.stable_id();
+    }
+
+    let outcome = memory.store_conversation(&conversation).await
+        .context("Failed to store conversation")?;
+    Ok(outcome != StoreOutcome::Unchanged)
+}
+
+/// Whether the line at the checkpoint offset is still the one we stopped at
+fn resumes_cleanly(content: &str, checkpoint: &Checkpoint) -> bool {
+    let offset = checkpoint.offset as usize;
+    if offset == 0 {
+        return true;
+    }
+    if offset > content.len() || !content.is_char_boundary(offset) {
+        return false;
+    }
+    // The checkpoint always sits at the start of a line
+    if !content[..offset].ends_with('\n') {
+        return false;
+    }
+
+    match &checkpoint.last_uuid {
+        Some(uuid) => transcript::parse_lines_at(first_line(&content[offset..]), checkpoint.offset)
+            .first()
+            .is_some_and(|line| line.uuid.as_ref() == Some(uuid)),
+        None => true,
+    }
+}
+
+fn first_line(content: &str) -> &str {
+    content.split_inclusive('\n').next().unwrap_or("")
+}
+
+/// What a turn said and which tools it ran, to tell whether it grew since last time
+fn turn_fingerprint(turn: &Turn) -> u64 {
+    let calls: Vec<&str> = turn.tool_calls.iter().map(|call| call.id.as_str()).collect();
+    fnv1a(format!("{}\n{}\n{}", turn.prompt, turn.response_text(), calls.join(",")).as_bytes())
+}
+
+fn checkpoint_file(dir: &Path, transcript_path: &Path) -> PathBuf {
+    let key = fnv1a(transcript_path.to_string_lossy().as_bytes());
+    dir.join(format!("{:016x}.json", key))
+}
+
+async fn load_checkpoint(checkpoint_path: &Path, transcript_path: &Path) -> Checkpoint {
+    let path = transcript_path.to_string_lossy().to_string();
+    let checkpoint = match tokio::fs::read(checkpoint_path).await {
+        Ok(content) => serde_json::from_slice::<Checkpoint>(&content).unwrap_or_else(|e| {
+            warn!("Ignoring unreadable checkpoint {:?}: {}", checkpoint_path, e);
+            Checkpoint::default()
+        }),
+        Err(_) => Checkpoint::default(),
+    };
+
+    // Guard against hash collisions between transcript paths, and start over when
+    // the parser has changed since
+    if checkpoint.path == path && checkpoint.parser_version == PARSER_VERSION {
+        checkpoint
+    } else {
+        Checkpoint { path, ..Checkpoint::default() }
+    }
+}
+
+async fn save_checkpoint(checkpoint_path: &Path, checkpoint: &Checkpoint) -> Result<()> {
+    let content = serde_json::to_vec_pretty(checkpoint)
+        .context("Failed to serialize checkpoint")?;
+    let tmp_path = checkpoint_path.with_extension("json.tmp");
+    tokio::fs::write(&tmp_path, content).await
+        .context("Failed to write checkpoint")?;
+    tokio::fs::rename(&tmp_path, checkpoint_path).await
+        .context("Failed to install checkpoint")?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::config::default_config;
+
+    const BASIC: &str = include_str!("../../tests/fixtures/transcript_basic.jsonl");
+
+    struct Scratch {
+        _dir: tempfile::TempDir,
+        memory: Memory,
+        redactor: Redactor,
+        transcript: PathBuf,
+    }
+
+    impl Scratch {
+        async fn new() -> Self {
+            let dir = tempfile::tempdir().unwrap();
+            let mut config = default_config();
+            config.database.path = dir.path().join("db").to_string_lossy().to_string();
+            Self {
+                memory: Memory::new(&config).await.unwrap(),
+                redactor: Redactor::new(&config.redaction).unwrap(),
+                transcript: dir.path().join("session-a.jsonl"),
+                _dir: dir,
+            }
+        }
+
+        async fn ingest(&self, content: &str) -> usize {
+            std::fs::write(&self.transcript, content).unwrap();
+            ingest_transcript(&self.memory, &self.redactor, &self.transcript).await.unwrap()
+        }
+
+        async fn prompts(&self) -> Vec<String> {
+            let mut prompts: Vec<String> = self.memory.all_conversations().await.unwrap()
+                .into_iter()
+                .map(|c| c.user_message)
+                .collect();
+            prompts.sort();
+            prompts
+        }
+    }
+
+    /// The first `n` lines of the basic transcript
+    fn lines(n: usize) -> String {
+        BASIC.lines().take(n).map(|line| format!("{}\n", line)).collect()
+    }
+
+    #[tokio::test]
+    async fn rerun_without_changes_stores_nothing() {
+        let scratch = Scratch::new().await;
+        assert_eq!(scratch.ingest(BASIC).await, 2);
+        assert_eq!(scratch.ingest(BASIC).await, 0);
+        assert_eq!(scratch.prompts().await.len(), 2);
+    }
+
+    #[tokio::test]
+    async fn appended_turn_is_stored_on_its_own() {
+        let scratch = Scratch::new().await;
+        assert_eq!(scratch.ingest(&lines(2)).await, 1);
+        assert_eq!(scratch.ingest(&lines(4)).await, 1);
+        assert_eq!(scratch.prompts().await, [
+            "How does the auth module validate login tokens?",
+            "Which database migrations are still pending?",
+        ]);
+    }
+
+    #[tokio::test]
+    async fn growing_tail_turn_is_stored_again() {
+        let scratch = Scratch::new().await;
+        // The prompt alone, then its answer, then an unfinished line
+        assert_eq!(scratch.ingest(&lines(3)).await, 1);
+        assert_eq!(scratch.ingest(&lines(4)).await, 1);
+        let follow_up = lines(4)
+            + r#"{"type":"assistant","sessionId":"session-a","uuid":"a-3","parentUuid":"a-2","cwd":"/work/app","timestamp":"2026-09-01T10:05:09.000Z","message":{"role":"assistant","content":[{"type":"text","text":"Run them with make migrate."}]}}"#
+            + "\n";
+        assert_eq!(scratch.ingest(&follow_up).await, 1);
+        let partial = follow_up.clone() + r#"{"type":"user","sessionId":"session-a","uuid":"u-3""#;
+        assert_eq!(scratch.ingest(&partial).await, 0);
+
+        let conversations = scratch.memory.all_conversations().await.unwrap();
+        let pending = conversations.iter().find(|c| c.user_message.starts_with("Which database")).unwrap();
+        assert!(pending.assistant_response.contains("make migrate"), "{:?}", pending);
+        assert_eq!(conversations.len(), 2);
+    }
+
+    #[tokio::test]
+    async fn rewritten_tail_is_read_again_from_the_start() {
+        let scratch = Scratch::new().await;
+        assert_eq!(scratch.ingest(BASIC).await, 2);
+
+        let rewritten = BASIC
+            .replace("\"u-2\"", "\"u-9\"")
+            .replace("Which database migrations are still pending?", "Which feature flags are still on?");
+        assert_eq!(scratch.ingest(&rewritten).await, 1);
+        assert!(scratch.prompts().await.contains(&"Which feature flags are still on?".to_string()));
+    }
+
+    #[tokio::test]
+    async fn truncated_file_is_read_again_from_the_start() {
+        let scratch = Scratch::new().await;
+        assert_eq!(scratch.ingest(BASIC).await, 2);
+
+        // Shorter than the checkpoint offset: nothing new, and no error
+        assert_eq!(scratch.ingest(&lines(2)).await, 0);
+        assert_eq!(scratch.ingest(BASIC).await, 0);
+        assert_eq!(scratch.prompts().await.len(), 2);
+    }
+
+    #[tokio::test]
+    async fn parser_upgrade_reads_the_file_again() {
+        let scratch = Scratch::new().await;
+        assert_eq!(scratch.ingest(BASIC).await, 2);
+
+        let path = checkpoint_file(scratch.memory.checkpoint_dir(), &scratch.transcript.canonicalize().unwrap());
+        let mut checkpoint: serde_json::Value = serde_json::from_slice(&std::fs::read(&path).unwrap()).unwrap();
+        assert_eq!(checkpoint["parser_version"], PARSER_VERSION);
+        checkpoint["parser_version"] = 0.into();
+        checkpoint["offset"] = BASIC.len().into();
+        checkpoint["last_uuid"] = serde_json::Value::Null;
+        std::fs::write(&path, checkpoint.to_string()).unwrap();
+
+        // Read from the start again, though the same content stores nothing new
+        assert_eq!(scratch.ingest(BASIC).await, 0);
+        let checkpoint: Checkpoint = serde_json::from_slice(&std::fs::read(&path).unwrap()).unwrap();
+        assert_eq!(checkpoint.parser_version, PARSER_VERSION);
+        assert_eq!(checkpoint.last_uuid.as_deref(), Some("u-2"));
+    }
+
+    #[tokio::test]
+    async fn pins_survive_reading_the_file_again() {
+        let scratch = Scratch::new().await;
+        assert_eq!(scratch.ingest(BASIC).await, 2);
+        let id = scratch.memory.all_conversations().await.unwrap()[0].id;
+        assert!(scratch.memory.set_pinned(&id, true).await.unwrap());
+
+        // An outdated parser version forces a full re-read
+        let path = checkpoint_file(scratch.memory.checkpoint_dir(), &scratch.transcript.canonicalize().unwrap());
+        let mut checkpoint: serde_json::Value = serde_json::from_slice(&std::fs::read(&path).unwrap()).unwrap();
+        checkpoint["parser_version"] = 0.into();
+        std::fs::write(&path, checkpoint.to_string()).unwrap();
+
+        assert_eq!(scratch.ingest(BASIC).await, 0);
+        let conversations = scratch.memory.all_conversations().await.unwrap();
+        assert!(conversations.iter().find(|c| c.id == id).unwrap().metadata.pinned);
+        assert!(!scratch.memory.set_pinned(&id, true).await.unwrap());
+    }
+}
@@ -1,92 +1,497 @@
 use anyhow::{Context, Result};
-use std::collections::HashMap;
-use std::path::PathBuf;
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::{Arc, Mutex};
-use tracing::{debug, info};
+use tracing::{debug, info, warn};
 use uuid::Uuid;
 
-use crate::core::types::{Conversation, SearchResult, DatabaseConfig};
+use crate::core::crypto::{self, Cipher};
+use crate::core::embeddings::{cosine_similarity, Embedding, EmbeddingGenerator};
+use crate::core::index::{term_spans, tokenize, InvertedIndex};
+use crate::core::passage::{self, exchange_offset, exchange_text, Passage};
+use crate::core::preview::preview;
+use crate::core::query::{Expr, Role, Search};
+use crate::core::ranking;
+use crate::core::session::SessionIndex;
+use crate::core::store::{LogRecord, SegmentStore, Snapshot};
+use crate::core::types::{Config, Conversation, RankingConfig, SearchResult, Session, SessionDigest, TextMatch};
+use chrono::{DateTime, Utc};
+use regex::Regex;
 
-/// Simple file-based storage for development/testing
+/// Upper bound on the text sent to the embedding provider per conversation
+const MAX_EMBEDDING_CHARS: usize = 8000;
+
+/// Characters of the question and answer shown in a result snippet
+const SNIPPET_QUESTION_CHARS: usize = 100;
+const SNIPPET_ANSWER_CHARS: usize = 200;
+
+/// Re-save the search index snapshot once this many documents drifted from it
+const INDEX_SAVE_THRESHOLD: usize = 64;
+
+/// What `Memory::store_conversation` did with a conversation
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StoreOutcome {
+    Inserted,
+    Updated,
+    /// An identical copy was already stored; nothing was written
+    Unchanged,
+}
+
+/// File-based conversation memory backed by an append-only segment store
 pub struct Memory {
     conversations: Arc<Mutex<HashMap<Uuid, Conversation>>>,
-    storage_path: PathBuf,
+    embeddings: Mutex<HashMap<Uuid, Embedding>>,
+    digests: Mutex<HashMap<String, SessionDigest>>,
+    sessions: Mutex<SessionIndex>,
+    index: Mutex<InvertedIndex>,
+    index_path: PathBuf,
+    base_path: PathBuf,
+    cipher: Option<Arc<Cipher>>,
+    checkpoint_dir: PathBuf,
+    unsaved_index_changes: AtomicUsize,
+    store: SegmentStore,
+    dead_records: AtomicUsize,
+    generator: EmbeddingGenerator,
+    relevance_threshold: f32,
+    ranking: RankingConfig,
 }
 
 impl Memory {
     /// Create a new memory instance
-    pub async fn new(config: &DatabaseConfig) -> Result<Self> {
-        let storage_path = PathBuf::from(&config.path).join("conversations.json");
+    pub async fn new(config: &Config) -> Result<Self> {
+        let base_path = PathBuf::from(&config.database.path);
         
         // Ensure the directory exists
-        if let Some(parent) = storage_path.parent() {
-            tokio::fs::create_dir_all(parent).await
-                .context("Failed to create storage directory")?;
+        tokio::fs::create_dir_all(&base_path).await
+            .context("Failed to create storage directory")?;
+        
+        // Unlock the store, setting up encryption first if the config just enabled it
+        let (cipher, pending_rewrite) = crypto::open(&base_path, &config.encryption)?;
+        let cipher = cipher.map(Arc::new);
+        
+        // Replay the segment log (migrating the old single-file format if needed)
+        let (store, snapshot) = SegmentStore::open(
+            base_path.join("segments"),
+            &base_path.join("conversations.json"),
+            cipher.clone(),
+        ).await?;
+        
+        debug!("Loaded {} conversations from segment store", snapshot.conversations.len());
+        
+        // Catch the index snapshot up with whatever was appended since it was saved
+        let index_path = base_path.join("index.json");
+        let mut index = InvertedIndex::load(&index_path, cipher.as_deref()).await;
+        let drift = index.reconcile(&snapshot.conversations);
+        if drift >= INDEX_SAVE_THRESHOLD {
+            index.save(&index_path, cipher.as_deref()).await?;
         }
         
-        // Load existing conversations from file
-        let conversations = Self::load_from_file(&storage_path).await?;
+        let generator = EmbeddingGenerator::new(&config.embeddings).await?;
+        let sessions = SessionIndex::build(&snapshot.conversations);
         
-        Ok(Self {
-            conversations: Arc::new(Mutex::new(conversations)),
-            storage_path,
-        })
+        let memory = Self {
+            conversations: Arc::new(Mutex::new(snapshot.conversations)),
+            embeddings: Mutex::new(snapshot.embeddings),
+            digests: Mutex::new(snapshot.digests),
+            sessions: Mutex::new(sessions),
+            index: Mutex::new(index),
+            index_path,
+            base_path: base_path.clone(),
+            cipher,
+            checkpoint_dir: base_path.join("checkpoints"),
+            unsaved_index_changes: AtomicUsize::new(if drift >= INDEX_SAVE_THRESHOLD { 0 } else { drift }),
+            store,
+            dead_records: AtomicUsize::new(snapshot.dead_records),
+            generator,
+            relevance_threshold: config.context.relevance_threshold,
+            ranking: config.ranking.clone(),
+        };
+        
+        // Existing plaintext records are rewritten under the new key, and the
+        // plaintext copy kept by the legacy migration goes with them
+        if pending_rewrite {
+            memory.compact().await?;
+            SegmentStore::remove_legacy_backup(&base_path.join("conversations.json")).await?;
+            crypto::finish_rewrite(&base_path)?;
+        }
+        
+        Ok(memory)
     }
     
-    /// Store a conversation in memory and save to file
-    pub async fn store_conversation(&self, conversation: &Conversation) -> Result<()> {
-        {
+    /// Insert or update a conversation by id and append it to the log
+    ///
+    /// Storing an exact copy of what is already there is a no-op. Metadata the
+    /// user set on the stored copy (the pin) carries over, so re-reading a
+    /// transcript never undoes it; use `set_pinned` to change it.
+    pub async fn store_conversation(&self, conversation: &Conversation) -> Result<StoreOutcome> {
+        let mut conversation = conversation.clone();
+        let replaced = {
             let mut conversations = self.conversations.lock().unwrap();
-            conversations.insert(conversation.id, conversation.clone());
+            if let Some(existing) = conversations.get(&conversation.id) {
+                conversation.metadata.pinned |= existing.metadata.pinned;
+                if *existing == conversation {
+                    debug!("Conversation {} unchanged, skipping", conversation.id);
+                    return Ok(StoreOutcome::Unchanged);
+                }
+            }
+            let previous = conversations.insert(conversation.id, conversation.clone());
+            self.sessions.lock().unwrap().upsert(previous.as_ref(), &conversation, &conversations);
             debug!("Stored conversation {} in memory", conversation.id);
+            previous.is_some()
+        };
+        
+        self.store.append(&LogRecord::Put { conversation: Box::new(conversation.clone()) }).await?;
+        self.index.lock().unwrap().upsert(&conversation);
+        self.unsaved_index_changes.fetch_add(1, Ordering::Relaxed);
+        if replaced {
+            self.dead_records.fetch_add(1, Ordering::Relaxed);
+        }
+        if self.embeddings.lock().unwrap().remove(&conversation.id).is_some() {
+            self.dead_records.fetch_add(1, Ordering::Relaxed);
         }
         
-        // Save to file
-        self.save_to_file().await?;
-        Ok(())
+        if self.generator.is_enabled() {
+            match self.generator.generate_embedding(&embedding_text(&conversation)).await {
+                Ok(embedding) => {
+                    self.store.append(&LogRecord::Embedding {
+                        id: conversation.id,
+                        embedding: embedding.clone(),
+                    }).await?;
+                    self.embeddings.lock().unwrap().insert(conversation.id, embedding);
+                }
+                Err(e) => warn!("Failed to embed conversation {}: {}", conversation.id, e),
+            }
+        }
+        
+        self.compact_if_needed().await?;
+        Ok(if replaced { StoreOutcome::Updated } else { StoreOutcome::Inserted })
+    }
+    
+    /// Pin or unpin a stored conversation, returning whether it changed
+    pub async fn set_pinned(&self, id: &Uuid, pinned: bool) -> Result<bool> {
+        let conversation = {
+            let mut conversations = self.conversations.lock().unwrap();
+            let Some(conversation) = conversations.get_mut(id) else {
+                return Ok(false);
+            };
+            if conversation.metadata.pinned == pinned {
+                return Ok(false);
+            }
+            conversation.metadata.pinned = pinned;
+            conversation.clone()
+        };
+        
+        // Only metadata changed, so the index stays valid. Replaying the put drops
+        // the embedding, which is written again after it.
+        let id = conversation.id;
+        self.store.append(&LogRecord::Put { conversation: Box::new(conversation) }).await?;
+        self.dead_records.fetch_add(1, Ordering::Relaxed);
+        let embedding = self.embeddings.lock().unwrap().get(&id).cloned();
+        if let Some(embedding) = embedding {
+            self.store.append(&LogRecord::Embedding { id, embedding }).await?;
+            self.dead_records.fetch_add(1, Ordering::Relaxed);
+        }
+        self.compact_if_needed().await?;
+        Ok(true)
+    }
+    
+    /// Remove a conversation, returning whether it existed
+    pub async fn delete_conversation(&self, id: &Uuid) -> Result<bool> {
+        {
+            let mut conversations = self.conversations.lock().unwrap();
+            match conversations.remove(id) {
+                Some(removed) => self.sessions.lock().unwrap().remove(&removed, &conversations),
+                None => return Ok(false),
+            }
+        }
+        
+        self.store.append(&LogRecord::Delete { id: *id }).await?;
+        self.index.lock().unwrap().remove(id);
+        self.unsaved_index_changes.fetch_add(1, Ordering::Relaxed);
+        
+        // The delete record and the put it cancels, plus any embedding
+        let mut dead = 2;
+        if self.embeddings.lock().unwrap().remove(id).is_some() {
+            dead += 1;
+        }
+        self.dead_records.fetch_add(dead, Ordering::Relaxed);
+        
+        debug!("Deleted conversation {}", id);
+        self.compact_if_needed().await?;
+        Ok(true)
+    }
+    
+    /// Insert or replace the digest of a session
+    pub async fn store_digest(&self, digest: &SessionDigest) -> Result<StoreOutcome> {
+        let replaced = {
+            let mut digests = self.digests.lock().unwrap();
+            if digests.get(&digest.session_id) == Some(digest) {
+                return Ok(StoreOutcome::Unchanged);
+            }
+            digests.insert(digest.session_id.clone(), digest.clone()).is_some()
+        };
+        
+        self.store.append(&LogRecord::Digest { digest: Box::new(digest.clone()) }).await?;
+        if replaced {
+            self.dead_records.fetch_add(1, Ordering::Relaxed);
+        }
+        
+        debug!("Stored digest for session {}", digest.session_id);
+        self.compact_if_needed().await?;
+        Ok(if replaced { StoreOutcome::Updated } else { StoreOutcome::Inserted })
+    }
+    
+    /// Digest of a session, if one has been written
+    pub fn session_digest(&self, session_id: &str) -> Option<SessionDigest> {
+        self.digests.lock().unwrap().get(session_id).cloned()
+    }
+    
+    /// Every stored session digest
+    pub fn session_digests(&self) -> Vec<SessionDigest> {
+        self.digests.lock().unwrap().values().cloned().collect()
+    }
+    
+    /// Remove the digest of a session, returning whether it existed
+    pub async fn delete_digest(&self, session_id: &str) -> Result<bool> {
+        if self.digests.lock().unwrap().remove(session_id).is_none() {
+            return Ok(false);
+        }
+        
+        self.store.append(&LogRecord::DeleteDigest { session_id: session_id.to_string() }).await?;
+        self.dead_records.fetch_add(2, Ordering::Relaxed);
+        
+        debug!("Deleted digest for session {}", session_id);
+        self.compact_if_needed().await?;
+        Ok(true)
+    }
+    
+    /// Every session with stored conversations, most recently active first
+    pub fn sessions(&self) -> Vec<Session> {
+        self.sessions.lock().unwrap().list()
     }
     
-    /// Search for relevant conversations using simple text matching
-    pub async fn search(&self, query: &str, limit: usize) -> Result<Vec<SearchResult>> {
+    /// A session by exact id
+    pub fn session(&self, session_id: &str) -> Option<Session> {
+        self.sessions.lock().unwrap().get(session_id).cloned()
+    }
+    
+    /// Conversations captured in a session, oldest first
+    pub fn session_conversations(&self, session_id: &str) -> Vec<Conversation> {
+        let conversations = self.conversations.lock().unwrap();
+        let mut turns: Vec<Conversation> = self
+            .sessions
+            .lock()
+            .unwrap()
+            .members(session_id)
+            .iter()
+            .filter_map(|id| conversations.get(id).cloned())
+            .collect();
+        turns.sort_by_key(|c| c.timestamp);
+        turns
+    }
+    
+    /// Remove a session's conversations and digest, returning how many
+    /// conversations were deleted
+    pub async fn delete_session(&self, session_id: &str) -> Result<usize> {
+        let ids = self.sessions.lock().unwrap().members(session_id);
+        let mut deleted = 0;
+        for id in &ids {
+            if self.delete_conversation(id).await? {
+                deleted += 1;
+            }
+        }
+        self.delete_digest(session_id).await?;
+        Ok(deleted)
+    }
+    
+    /// Search for relevant conversations
+    ///
+    /// Fuses keyword (BM25) ranking, embedding similarity when embeddings are enabled
+    /// (hits below `relevance_threshold` don't count) and recency; see
+    /// `ranking::fuse`. Phrases, filters and negations in `query` are applied either way.
+    pub async fn search(&self, query: &Expr, limit: usize) -> Result<Vec<SearchResult>> {
+        let text = query.ranking_text();
+        let (keyword, passages) = self.keyword_ranking(query, &text);
+        let semantic = if self.generator.is_enabled() && !text.trim().is_empty() {
+            self.semantic_ranking(query, &text).await?
+        } else {
+            Vec::new()
+        };
+        debug!("Query {:?}: {} keyword and {} semantic hits", text, keyword.len(), semantic.len());
+        
+        let terms: HashSet<String> = tokenize(&text).into_iter().collect();
+        Ok(self.fuse(&keyword, &semantic, &passages, limit, |conversation| term_matches(conversation, &terms)))
+    }
+    
+    /// Search in whichever mode `search` was parsed for
+    pub async fn run_search(&self, search: &Search, limit: usize) -> Result<Vec<SearchResult>> {
+        match search {
+            Search::Keyword(query) => self.search(query, limit).await,
+            Search::Fuzzy(query) => Ok(self.fuzzy_search(query, limit)),
+            Search::Regex(regex) => Ok(self.regex_search(regex, limit)),
+        }
+    }
+    
+    /// Conversations by cosine similarity to the query embedding, at or above
+    /// `relevance_threshold`
+    async fn semantic_ranking(&self, query: &Expr, text: &str) -> Result<Vec<(Uuid, f32)>> {
+        let query_embedding = self.generator.generate_embedding(text).await?;
+        // Offline vectors are cheap, so conversations stored without one (or under
+        // another model) can still be compared on the fly
+        let offline = query_embedding.model == self.generator.fallback_model_id();
+        
         let conversations = self.conversations.lock().unwrap();
-        let mut results = Vec::new();
-        let query_lower = query.to_lowercase();
+        let embeddings = self.embeddings.lock().unwrap();
+        let mut ranked = Vec::new();
         
         for conversation in conversations.values() {
-            let user_msg_lower = conversation.user_message.to_lowercase();
-            let assistant_msg_lower = conversation.assistant_response.to_lowercase();
-            
-            // Simple score based on keyword matches
-            let mut score = 0.0;
-            let query_words: Vec<&str> = query_lower.split_whitespace().collect();
-            
-            for word in query_words {
-                if user_msg_lower.contains(word) {
-                    score += 0.5;
+            if !query.is_plain_text() && !query.matches(conversation, true) {
+                continue;
+            }
+            let score = match embeddings.get(&conversation.id) {
+                Some(stored) if stored.model == query_embedding.model => {
+                    cosine_similarity(&query_embedding.vector, &stored.vector)
                 }
-                if assistant_msg_lower.contains(word) {
-                    score += 0.3;
+                _ if offline => {
+                    let vector = self.generator.fallback_embedding(&embedding_text(conversation));
+                    cosine_similarity(&query_embedding.vector, &vector)
                 }
-            }
+                _ => continue,
+            };
             
-            if score > 0.0 {
-                let snippet = self.create_snippet(conversation);
-                results.push(SearchResult {
-                    conversation: conversation.clone(),
-                    score,
-                    snippet,
-                });
+            if score >= self.relevance_threshold {
+                ranked.push((conversation.id, score));
+            }
+        }
+        
+        ranked.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap_or(std::cmp::Ordering::Equal));
+        Ok(ranked)
+    }
+    
+    /// Conversations by the BM25 score of their best passage, and those passages
+    ///
+    /// Plain text only ranks; a structured query first selects the conversations it
+    /// matches, and those without ranking terms come newest first.
+    fn keyword_ranking(&self, query: &Expr, text: &str) -> (Vec<(Uuid, f32)>, HashMap<Uuid, Passage>) {
+        let hits = self.index.lock().unwrap().search(text);
+        let passages: HashMap<Uuid, Passage> = hits.iter().map(|hit| (hit.conversation_id(), hit.passage)).collect();
+        if query.is_plain_text() {
+            return (hits.iter().map(|hit| (hit.conversation_id(), hit.score)).collect(), passages);
+        }
+        
+        let conversations = self.conversations.lock().unwrap();
+        let scores: HashMap<Uuid, f32> = hits.iter().map(|hit| (hit.conversation_id(), hit.score)).collect();
+        let unranked = if text.trim().is_empty() { 1.0 } else { 0.0 };
+        let mut hits: Vec<(&Conversation, f32)> = conversations
+            .values()
+            .filter(|conversation| query.matches(conversation, false))
+            .map(|conversation| (conversation, scores.get(&conversation.id).copied().unwrap_or(unranked)))
+            .collect();
+        hits.sort_by(|a, b| {
+            b.1.partial_cmp(&a.1)
+                .unwrap_or(std::cmp::Ordering::Equal)
+                .then(b.0.timestamp.cmp(&a.0.timestamp))
+        });
+        let ranked = hits.into_iter().map(|(conversation, score)| (conversation.id, score)).collect();
+        (ranked, passages)
+    }
+    
+    /// Rank with BM25 over the indexed terms within a few typos of the query words
+    ///
+    /// Phrases, filters and negations still apply exactly; a query with nothing to
+    /// rank on is answered like a keyword search.
+    fn fuzzy_search(&self, query: &Expr, limit: usize) -> Vec<SearchResult> {
+        let text = query.ranking_text();
+        let (terms, hits) = {
+            let index = self.index.lock().unwrap();
+            let terms = index.fuzzy_terms(&text);
+            let hits = index.search_weighted(&terms);
+            (terms, hits)
+        };
+        debug!("Fuzzy terms for {:?}: {:?}", text, terms);
+        
+        let (ranked, passages) = if terms.is_empty() && tokenize(&text).is_empty() {
+            self.keyword_ranking(query, &text)
+        } else {
+            let conversations = self.conversations.lock().unwrap();
+            let hits: Vec<_> = hits
+                .into_iter()
+                .filter(|hit| {
+                    query.is_plain_text()
+                        || conversations.get(&hit.conversation_id()).is_some_and(|c| query.matches(c, true))
+                })
+                .collect();
+            (
+                hits.iter().map(|hit| (hit.conversation_id(), hit.score)).collect(),
+                hits.iter().map(|hit| (hit.conversation_id(), hit.passage)).collect(),
+            )
+        };
+        
+        let terms: HashSet<String> = terms.into_iter().map(|(term, _)| term).collect();
+        self.fuse(&ranked, &[], &passages, limit, |conversation| term_matches(conversation, &terms))
+    }
+    
+    /// Every conversation the pattern matches, ranked by how often it matches, with
+    /// the passage of its first match
+    fn regex_search(&self, regex: &Regex, limit: usize) -> Vec<SearchResult> {
+        let mut ranked = Vec::new();
+        let mut passages = HashMap::new();
+        {
+            let conversations = self.conversations.lock().unwrap();
+            for conversation in conversations.values() {
+                let matches = regex_matches(conversation, regex);
+                let first = match matches.first() {
+                    Some(first) => first,
+                    None => continue,
+                };
+                let offset = exchange_offset(conversation, first.role, first.start);
+                if let Some(passage) = passage::containing(&passage::split(conversation), offset) {
+                    passages.insert(conversation.id, passage);
+                }
+                ranked.push((conversation.id, matches.len() as f32));
             }
         }
+        ranked.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap_or(std::cmp::Ordering::Equal));
         
-        // Sort by score (highest first)
-        results.sort_by(|a, b| b.score.partial_cmp(&a.score).unwrap_or(std::cmp::Ordering::Equal));
+        self.fuse(&ranked, &[], &passages, limit, |conversation| regex_matches(conversation, regex))
+    }
+    
+    /// Fuse the signal rankings and build the top `limit` results
+    fn fuse(
+        &self,
+        keyword: &[(Uuid, f32)],
+        semantic: &[(Uuid, f32)],
+        passages: &HashMap<Uuid, Passage>,
+        limit: usize,
+        matches: impl Fn(&Conversation) -> Vec<TextMatch>,
+    ) -> Vec<SearchResult> {
+        let conversations = self.conversations.lock().unwrap();
+        let timestamps: HashMap<Uuid, DateTime<Utc>> = keyword
+            .iter()
+            .chain(semantic)
+            .filter_map(|(id, _)| Some((*id, conversations.get(id)?.timestamp)))
+            .collect();
         
-        // Limit results
-        results.truncate(limit);
+        let results: Vec<SearchResult> = ranking::fuse(keyword, semantic, &timestamps, &self.ranking, Utc::now())
+            .into_iter()
+            .filter_map(|fused| Some((conversations.get(&fused.id)?, fused)))
+            .take(limit)
+            .map(|(conversation, fused)| {
+                let passage = passages.get(&conversation.id).copied();
+                SearchResult {
+                    conversation: conversation.clone(),
+                    score: fused.score,
+                    snippet: self.create_snippet(conversation, passage.as_ref()),
+                    matches: matches(conversation),
+                    signals: fused.signals,
+                    passage,
+                }
+            })
+            .collect();
         
-        debug!("Found {} search results for query: {}", results.len(), query);
-        Ok(results)
+        debug!("Returning {} of {} ranked results", results.len(), timestamps.len());
+        results
     }
     
     /// Get conversation count
@@ -101,14 +506,70 @@ impl Memory {
         {
             let mut conversations = self.conversations.lock().unwrap();
             conversations.clear();
+            self.embeddings.lock().unwrap().clear();
+            self.digests.lock().unwrap().clear();
+            *self.sessions.lock().unwrap() = SessionIndex::default();
             info!("Memory cleared");
         }
         
-        // Save empty state to file
-        self.save_to_file().await?;
+        self.store.clear().await?;
+        self.dead_records.store(0, Ordering::Relaxed);
+        self.rebuild_index().await?;
+
+        // Forget ingestion progress so transcripts can be imported again
+        match tokio::fs::remove_dir_all(&self.checkpoint_dir).await {
+            Ok(()) => {}
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(e) => return Err(e).context("Failed to remove transcript checkpoints"),
+        }
         Ok(())
     }
 
+    /// Approximate bytes each conversation occupies in a compacted log (its record
+    /// plus its embedding)
+    pub async fn record_sizes(&self) -> HashMap<Uuid, u64> {
+        let conversations = self.conversations.lock().unwrap();
+        let embeddings = self.embeddings.lock().unwrap();
+        
+        conversations
+            .values()
+            .map(|conversation| {
+                let mut size = serde_json::to_vec(conversation).map_or(0, |v| v.len());
+                if let Some(embedding) = embeddings.get(&conversation.id) {
+                    size += serde_json::to_vec(embedding).map_or(0, |v| v.len());
+                }
+                (conversation.id, size as u64)
+            })
+            .collect()
+    }
+    
+    /// Directory holding the segments, index and encryption header
+    pub fn base_path(&self) -> &Path {
+        &self.base_path
+    }
+
+    /// Cipher the store is read with, if it is encrypted
+    pub fn cipher(&self) -> Option<&Cipher> {
+        self.cipher.as_deref()
+    }
+
+    /// Rewrite every record (and the index) under `cipher`, or in plaintext with `None`
+    pub async fn reencrypt(&mut self, cipher: Option<Arc<Cipher>>) -> Result<()> {
+        self.store.set_cipher(cipher.clone());
+        self.cipher = cipher;
+        self.compact().await
+    }
+
+    /// Ids of the keys records on disk are encrypted with
+    pub async fn encryption_keys_in_use(&self) -> Result<HashSet<String>> {
+        self.store.key_ids_in_use().await
+    }
+
+    /// Directory holding per-transcript ingestion checkpoints
+    pub fn checkpoint_dir(&self) -> &Path {
+        &self.checkpoint_dir
+    }
+
 
     /// Retorna todas as conversas salvas
     pub async fn all_conversations(&self) -> Result<Vec<Conversation>> {
@@ -117,62 +578,115 @@ impl Memory {
     }
 
     /// Create a snippet from a conversation for display
-    fn create_snippet(&self, conversation: &Conversation) -> String {
-        let user_preview = if conversation.user_message.len() > 100 {
-            format!("{}...", &conversation.user_message[..100])
-        } else {
-            conversation.user_message.clone()
-        };
-
-        let assistant_preview = if conversation.assistant_response.len() > 200 {
-            format!("{}...", &conversation.assistant_response[..200])
-        } else {
-            conversation.assistant_response.clone()
+    ///
+    /// When a later passage of a long answer matched, the answer preview starts there.
+    fn create_snippet(&self, conversation: &Conversation, passage: Option<&Passage>) -> String {
+        let answer = match passage {
+            Some(passage) if passage.starts_mid_answer(conversation) => {
+                format!("…{}", passage.side(conversation, Role::Assistant).trim_start())
+            }
+            _ => conversation.assistant_response.clone(),
         };
 
-        format!("User: {}\nAssistant: {}", user_preview, assistant_preview)
+        format!(
+            "User: {}\nAssistant: {}",
+            preview(&conversation.user_message, SNIPPET_QUESTION_CHARS),
+            preview(&answer, SNIPPET_ANSWER_CHARS)
+        )
     }
     
-    /// Load conversations from JSON file
-    async fn load_from_file(storage_path: &PathBuf) -> Result<HashMap<Uuid, Conversation>> {
-        if !storage_path.exists() {
-            debug!("Storage file does not exist, starting with empty memory");
-            return Ok(HashMap::new());
-        }
-        
-        let content = tokio::fs::read_to_string(storage_path).await
-            .context("Failed to read storage file")?;
-        
-        if content.trim().is_empty() {
-            return Ok(HashMap::new());
-        }
-        
-        let conversations: Vec<Conversation> = serde_json::from_str(&content)
-            .context("Failed to parse storage file")?;
+    /// Rewrite the log so only live records remain on disk
+    pub async fn compact(&self) -> Result<()> {
+        let snapshot = self.store.compact().await?;
+        self.install_snapshot(snapshot).await
+    }
+    
+    /// Fold the log into a single segment once enough dead records pile up
+    async fn compact_if_needed(&self) -> Result<()> {
+        let live = self.conversations.lock().unwrap().len();
+        let dead = self.dead_records.load(Ordering::Relaxed);
         
-        let mut map = HashMap::new();
-        for conversation in conversations {
-            map.insert(conversation.id, conversation);
+        if let Some(snapshot) = self.store.maybe_compact(live, dead).await? {
+            self.install_snapshot(snapshot).await?;
+        } else if self.unsaved_index_changes.load(Ordering::Relaxed) >= INDEX_SAVE_THRESHOLD {
+            self.save_index().await?;
         }
         
-        debug!("Loaded {} conversations from storage file", map.len());
-        Ok(map)
+        Ok(())
+    }
+    
+    /// Adopt a freshly compacted snapshot, which includes anything other
+    /// processes appended while we were running
+    async fn install_snapshot(&self, snapshot: Snapshot) -> Result<()> {
+        *self.sessions.lock().unwrap() = SessionIndex::build(&snapshot.conversations);
+        *self.conversations.lock().unwrap() = snapshot.conversations;
+        *self.embeddings.lock().unwrap() = snapshot.embeddings;
+        *self.digests.lock().unwrap() = snapshot.digests;
+        self.dead_records.store(0, Ordering::Relaxed);
+        self.rebuild_index().await
     }
     
-    /// Save conversations to JSON file
-    async fn save_to_file(&self) -> Result<()> {
-        let json_content = {
+    /// Re-tokenize every conversation and persist the fresh index
+    async fn rebuild_index(&self) -> Result<()> {
+        {
             let conversations = self.conversations.lock().unwrap();
-            let conversations_vec: Vec<&Conversation> = conversations.values().collect();
-            
-            serde_json::to_string_pretty(&conversations_vec)
-                .context("Failed to serialize conversations")?
+            let mut index = self.index.lock().unwrap();
+            index.clear();
+            index.reconcile(&conversations);
+        }
+        self.save_index().await
+    }
+    
+    async fn save_index(&self) -> Result<()> {
+        // Serialize under the lock, but write without holding it across the await
+        let snapshot = {
+            let index = self.index.lock().unwrap();
+            index.to_snapshot_bytes()?
         };
-        
-        tokio::fs::write(&self.storage_path, json_content).await
-            .context("Failed to write storage file")?;
-        
-        debug!("Saved conversations to storage file");
+        InvertedIndex::write_snapshot(&self.index_path, snapshot, self.cipher.as_deref()).await?;
+        self.unsaved_index_changes.store(0, Ordering::Relaxed);
         Ok(())
     }
-}
\ No newline at end of file
+}
+
+/// Both sides of a conversation, in the order they were said
+fn sides(conversation: &Conversation) -> [(Role, &str); 2] {
+    [
+        (Role::User, conversation.user_message.as_str()),
+        (Role::Assistant, conversation.assistant_response.as_str()),
+    ]
+}
+
+/// Words whose index term is one of `terms`
+fn term_matches(conversation: &Conversation, terms: &HashSet<String>) -> Vec<TextMatch> {
+    if terms.is_empty() {
+        return Vec::new();
+    }
+    sides(conversation)
+        .into_iter()
+        .flat_map(|(role, text)| {
+            term_spans(text, terms).into_iter().map(move |(start, end)| TextMatch { role, start, end })
+        })
+        .collect()
+}
+
+/// Non-empty matches of `regex`
+fn regex_matches(conversation: &Conversation, regex: &Regex) -> Vec<TextMatch> {
+    sides(conversation)
+        .into_iter()
+        .flat_map(|(role, text)| {
+            regex
+                .find_iter(text)
+                .filter(|m| !m.is_empty())
+                .map(move |m| TextMatch { role, start: m.start(), end: m.end() })
+        })
+        .collect()
+}
+
+/// Text used to embed a conversation, capped at `MAX_EMBEDDING_CHARS`
+fn embedding_text(conversation: &Conversation) -> String {
+    exchange_text(conversation)
+        .chars()
+        .take(MAX_EMBEDDING_CHARS)
+        .collect()
+}
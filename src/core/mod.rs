@@ -1,9 +1,27 @@
+pub mod code;
 pub mod config;
+pub mod context;
+pub mod crypto;
 pub mod memory_simple;
 pub mod memory {
     pub use super::memory_simple::*;
 }
 pub mod embeddings;
+pub mod hash;
+pub mod index;
+pub mod ingest;
 pub mod parser;
+pub mod passage;
+pub mod preview;
+pub mod query;
+pub mod ranking;
+pub mod redact;
+pub mod retention;
+pub mod session;
+pub mod session_state;
+pub mod store;
+pub mod summarize;
+pub mod tokenizer;
+pub mod transcript;
 pub mod types;
 pub mod validation;
\ No newline at end of file
@@ -1,9 +1,11 @@
 use anyhow::Result;
+use crate::core::code::CodeRefs;
+use crate::core::tokenizer::estimate_tokens;
+use crate::core::transcript::{self, Turn};
 use crate::core::types::*;
 use chrono::{DateTime, Utc};
 use std::path::Path;
 use uuid::Uuid;
-use serde_json::Value;
 
 /// Parse a Claude Code transcript file
 pub async fn parse_transcript(transcript_path: &str) -> Result<Vec<Conversation>> {
@@ -54,8 +56,11 @@ fn extract_conversations_from_transcript(
             }
             "assistant" => {
                 if let Some(user_msg) = current_user_message.take() {
-                    let conversation = Conversation {
-                        id: Uuid::new_v4(),
+                    let mut code = CodeRefs::default();
+                    code.scan_markdown(&user_msg);
+                    code.scan_markdown(&message.content);
+                    let mut conversation = Conversation {
+                        id: Uuid::nil(),
                         timestamp: parse_timestamp(&message.timestamp)?,
                         user_message: user_msg.clone(),
                         assistant_response: message.content.clone(),
@@ -65,8 +70,17 @@ fn extract_conversations_from_transcript(
                             tags: extract_tags(&user_msg),
                             token_count: estimate_token_count(&user_msg, &message.content),
                             embedding_model: None,
+                            source_uuid: None,
+                            git_branch: None,
+                            tools_used: Vec::new(),
+                            files_touched: Vec::new(),
+                            files_mentioned: code.files,
+                            languages: code.languages,
+                            symbols: code.symbols,
+                            pinned: false,
                         },
                     };
+                    conversation.id = conversation.stable_id();
                     
                     conversations.push(conversation);
                 }
@@ -80,58 +94,71 @@ fn extract_conversations_from_transcript(
     Ok(conversations)
 }
 
-/// Novo: Parse Claude Code JSONL (um objeto por linha, tipo user/assistant)
+/// Parse a Claude Code JSONL transcript (one `user`/`assistant` entry per line)
+///
+/// Every text block, tool call and tool result is folded into the turn it belongs
+/// to, so each conversation covers a full prompt and reply.
 pub async fn parse_claude_jsonl_transcript(transcript_path: &str) -> Result<Vec<Conversation>> {
     let content = tokio::fs::read_to_string(transcript_path).await?;
-    let mut conversations = Vec::new();
-    let mut current_user_message: Option<String> = None;
-    let mut session_id: Option<String> = None;
+    let lines = transcript::parse_lines(&content);
 
-    for line in content.lines() {
-        if line.trim().is_empty() {
-            continue;
-        }
-        let v: Value = match serde_json::from_str(line) {
-            Ok(val) => val,
-            Err(_) => continue,
-        };
-        let msg_type = v.get("type").and_then(|t| t.as_str()).unwrap_or("");
-        if msg_type == "user" {
-            if let Some(msg) = v.get("message").and_then(|m| m.get("content")).and_then(|c| c.as_str()) {
-                current_user_message = Some(msg.to_string());
-                session_id = v.get("sessionId").and_then(|s| s.as_str()).map(|s| s.to_string());
-            }
-        } else if msg_type == "assistant" {
-            if let (Some(user_msg), Some(msg)) = (
-                current_user_message.take(),
-                v.get("message").and_then(|m| m.get("content")).and_then(|c| {
-                    if c.is_string() {
-                        c.as_str().map(|s| s.to_string())
-                    } else if c.is_array() {
-                        c.as_array().and_then(|arr| arr.get(0)).and_then(|obj| obj.get("text")).and_then(|t| t.as_str()).map(|s| s.to_string())
-                    } else {
-                        None
-                    }
-                }),
-            ) {
-                let conversation = Conversation {
-                    id: Uuid::new_v4(),
-                    timestamp: Utc::now(),
-                    user_message: user_msg,
-                    assistant_response: msg,
-                    metadata: ConversationMetadata {
-                        session_id: session_id.clone(),
-                        project_path: None,
-                        tags: vec![],
-                        token_count: 0,
-                        embedding_model: None,
-                    },
-                };
-                conversations.push(conversation);
-            }
+    Ok(transcript::assemble_turns(&lines)
+        .into_iter()
+        .filter(|turn| !turn.is_empty())
+        .map(|turn| conversation_from_turn(turn, transcript_path))
+        .collect())
+}
+
+/// Convert an assembled transcript turn into a stored conversation
+pub fn conversation_from_turn(turn: Turn, source_path: &str) -> Conversation {
+    let mut assistant_response = turn.response_text();
+    let tools_used = turn.tools_used();
+    let files_touched = turn.files_touched();
+
+    let mut code = CodeRefs::default();
+    code.scan_markdown(&turn.prompt);
+    code.scan_markdown(&assistant_response);
+    for call in &turn.tool_calls {
+        code.scan_tool_call(call);
+    }
+    code.files.retain(|file| !files_touched.contains(file));
+
+    // Pure tool-use turns still leave a searchable trace of what was done
+    if assistant_response.is_empty() {
+        assistant_response = format!("[used {}]", tools_used.join(", "));
+        if !files_touched.is_empty() {
+            assistant_response.push_str(&format!(" on {}", files_touched.join(", ")));
         }
     }
-    Ok(conversations)
+
+    let mut tags = extract_tags(&turn.prompt);
+    if turn.sidechain {
+        tags.push("subagent".to_string());
+    }
+
+    let mut conversation = Conversation {
+        id: Uuid::nil(),
+        timestamp: turn.ended_at.or(turn.started_at).unwrap_or_else(Utc::now),
+        metadata: ConversationMetadata {
+            session_id: turn.session_id,
+            project_path: turn.cwd.or_else(|| detect_project_path(source_path)),
+            tags,
+            token_count: estimate_token_count(&turn.prompt, &assistant_response),
+            embedding_model: None,
+            source_uuid: turn.uuid,
+            git_branch: turn.git_branch,
+            tools_used,
+            files_touched,
+            files_mentioned: code.files,
+            languages: code.languages,
+            symbols: code.symbols,
+            pinned: false,
+        },
+        user_message: turn.prompt,
+        assistant_response,
+    };
+    conversation.id = conversation.stable_id();
+    conversation
 }
 
 /// Parse timestamp from various formats
@@ -202,8 +229,94 @@ fn extract_tags(content: &str) -> Vec<String> {
     tags
 }
 
-/// Estimate token count for text
+/// Estimate token count for an exchange, without loading the BPE vocabulary
 fn estimate_token_count(user_msg: &str, assistant_msg: &str) -> usize {
-    // Rough estimation: 4 characters per token
-    (user_msg.len() + assistant_msg.len()) / 4
-}
\ No newline at end of file
+    estimate_tokens(user_msg) + estimate_tokens(assistant_msg)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fixture(name: &str) -> String {
+        format!("{}/tests/fixtures/{}", env!("CARGO_MANIFEST_DIR"), name)
+    }
+
+    #[tokio::test]
+    async fn jsonl_fixture_becomes_one_conversation_per_turn() {
+        let conversations = parse_transcript(&fixture("transcript_basic.jsonl")).await.unwrap();
+        assert_eq!(conversations.len(), 2);
+
+        let first = &conversations[0];
+        assert_eq!(first.user_message, "How does the auth module validate login tokens?");
+        assert_eq!(first.assistant_response, "The auth middleware verifies the JWT signature before each request.");
+        assert_eq!(first.timestamp.to_rfc3339(), "2026-09-01T10:00:04+00:00");
+        assert_eq!(first.metadata.session_id.as_deref(), Some("session-a"));
+        assert_eq!(first.metadata.project_path.as_deref(), Some("/work/app"));
+        assert_eq!(first.metadata.source_uuid.as_deref(), Some("u-1"));
+        assert!(first.metadata.token_count > 0);
+        assert_eq!(first.id, first.stable_id());
+    }
+
+    #[tokio::test]
+    async fn tool_calls_and_code_end_up_in_the_metadata() {
+        let conversations = parse_claude_jsonl_transcript(&fixture("transcript_code.jsonl")).await.unwrap();
+        assert_eq!(conversations.len(), 4);
+
+        let edit = &conversations[0].metadata;
+        assert_eq!(edit.tools_used, ["Edit"]);
+        assert_eq!(edit.files_touched, ["/work/app/src/commands/hook.rs"]);
+        assert!(edit.symbols.contains(&"handle_hook".to_string()), "{:?}", edit.symbols);
+
+        let read = &conversations[1].metadata;
+        assert_eq!(read.languages, ["rust"]);
+        assert!(read.files_mentioned.contains(&"src/core/transcript.rs".to_string()), "{:?}", read.files_mentioned);
+        assert!(read.symbols.contains(&"parse_lines".to_string()), "{:?}", read.symbols);
+
+        let grep = &conversations[2].metadata;
+        assert_eq!(grep.tools_used, ["Grep"]);
+        assert_eq!(grep.languages, ["python"]);
+        // Touched files are not listed again as mentioned
+        assert!(!grep.files_mentioned.contains(&"src/core/parser.rs".to_string()));
+    }
+
+    #[tokio::test]
+    async fn subagent_transcript_is_tagged() {
+        let conversations = parse_transcript(&fixture("transcript_subagent.jsonl")).await.unwrap();
+        assert_eq!(conversations.len(), 1);
+        assert!(conversations[0].metadata.tags.contains(&"subagent".to_string()));
+    }
+
+    #[test]
+    fn pure_tool_use_turn_describes_what_was_done() {
+        let lines = transcript::parse_lines(concat!(
+            r#"{"type":"user","uuid":"u-1","message":{"role":"user","content":"Format the crate"}}"#, "\n",
+            r#"{"type":"assistant","uuid":"a-1","message":{"role":"assistant","content":[{"type":"tool_use","id":"t-1","name":"Bash","input":{"command":"cargo fmt"}},{"type":"tool_use","id":"t-2","name":"Read","input":{"file_path":"src/main.rs"}}]}}"#, "\n",
+        ));
+        let turn = transcript::assemble_turns(&lines).remove(0);
+        let conversation = conversation_from_turn(turn, "/tmp/session.jsonl");
+        assert_eq!(conversation.assistant_response, "[used Bash, Read] on src/main.rs");
+    }
+
+    #[tokio::test]
+    async fn json_transcript_pairs_user_and_assistant_messages() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("transcript.json");
+        let transcript = serde_json::json!({
+            "session_id": "session-j",
+            "messages": [
+                { "role": "system", "content": "ignored" },
+                { "role": "user", "content": "What does `Memory::search` return?" },
+                { "role": "assistant", "content": "Ranked hits.", "timestamp": "2026-09-02T08:00:00Z" },
+                { "role": "assistant", "content": "An unpaired reply is dropped." },
+            ],
+        });
+        std::fs::write(&path, transcript.to_string()).unwrap();
+
+        let conversations = parse_transcript(path.to_str().unwrap()).await.unwrap();
+        assert_eq!(conversations.len(), 1);
+        assert_eq!(conversations[0].assistant_response, "Ranked hits.");
+        assert_eq!(conversations[0].metadata.session_id.as_deref(), Some("session-j"));
+        assert!(conversations[0].metadata.symbols.contains(&"Memory::search".to_string()));
+    }
+}
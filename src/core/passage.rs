@@ -0,0 +1,360 @@
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::core::query::Role;
+use crate::core::types::Conversation;
+
+/// Exchanges up to this many bytes stay a single passage
+const PASSAGE_BYTES: usize = 1200;
+
+/// Trailing text of a passage repeated at the start of the next one, so a match
+/// that straddles the boundary is still found in one piece
+const OVERLAP_BYTES: usize = 200;
+
+/// Code blocks are kept whole up to this size, then split between lines
+const MAX_CODE_BLOCK_BYTES: usize = 4 * PASSAGE_BYTES;
+
+/// A heading starts a new passage once the current one is at least this full
+const MIN_SECTION_BYTES: usize = PASSAGE_BYTES / 4;
+
+/// A retrievable slice of an exchange
+///
+/// Offsets are bytes into the exchange text: the user message, a newline, then the
+/// assistant response (see `exchange_text`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Passage {
+    /// The conversation the passage was cut from
+    pub conversation_id: Uuid,
+    /// Position among the conversation's passages, from 0
+    pub ordinal: usize,
+    /// How many passages the conversation has
+    pub count: usize,
+    pub start: usize,
+    pub end: usize,
+}
+
+impl Passage {
+    /// Stable id, derived from the conversation id and the ordinal
+    pub fn id(&self) -> Uuid {
+        Uuid::new_v5(&self.conversation_id, self.ordinal.to_string().as_bytes())
+    }
+
+    /// The part of the passage said by `role`, empty if it lies entirely on the other side
+    pub fn side<'a>(&self, conversation: &'a Conversation, role: Role) -> &'a str {
+        let user_len = conversation.user_message.len();
+        let (message, offset) = match role {
+            Role::User => (conversation.user_message.as_str(), 0),
+            Role::Assistant => (conversation.assistant_response.as_str(), user_len + 1),
+        };
+        let start = self.start.saturating_sub(offset).min(message.len());
+        let end = self.end.saturating_sub(offset).min(message.len());
+        message.get(start..end).unwrap_or("")
+    }
+
+    /// Whether the passage starts after the beginning of the assistant response
+    pub fn starts_mid_answer(&self, conversation: &Conversation) -> bool {
+        self.start > conversation.user_message.len() + 1
+    }
+}
+
+/// Text an exchange is indexed and embedded as
+pub fn exchange_text(conversation: &Conversation) -> String {
+    format!("{}\n{}", conversation.user_message, conversation.assistant_response)
+}
+
+/// Offset into the exchange text of a byte offset in one of the messages
+pub fn exchange_offset(conversation: &Conversation, role: Role, offset: usize) -> usize {
+    match role {
+        Role::User => offset,
+        Role::Assistant => conversation.user_message.len() + 1 + offset,
+    }
+}
+
+/// Cut an exchange into overlapping passages
+///
+/// Short exchanges are one passage. Longer ones are packed paragraph by paragraph:
+/// fenced code blocks are never split unless they exceed `MAX_CODE_BLOCK_BYTES`, and
+/// a markdown heading starts a new passage (without overlap) once the current one
+/// has some substance.
+pub fn split(conversation: &Conversation) -> Vec<Passage> {
+    let text = exchange_text(conversation);
+    let ranges = if text.len() <= PASSAGE_BYTES {
+        vec![(0, text.len())]
+    } else {
+        pack(&text, &blocks(&text))
+    };
+
+    let count = ranges.len();
+    ranges
+        .into_iter()
+        .enumerate()
+        .map(|(ordinal, (start, end))| Passage {
+            conversation_id: conversation.id,
+            ordinal,
+            count,
+            start,
+            end,
+        })
+        .collect()
+}
+
+/// The passage holding exchange offset `offset`, preferring the earliest one when
+/// passages overlap
+pub fn containing(passages: &[Passage], offset: usize) -> Option<Passage> {
+    passages
+        .iter()
+        .find(|p| p.start <= offset && offset < p.end)
+        .or(passages.last())
+        .copied()
+}
+
+/// A paragraph, heading section start or fenced code block
+#[derive(Debug, Clone, Copy)]
+struct Block {
+    start: usize,
+    end: usize,
+    heading: bool,
+}
+
+/// Split text into blocks at blank lines, headings and code fences, cutting any block
+/// that is too large to pack
+fn blocks(text: &str) -> Vec<Block> {
+    let mut blocks = Vec::new();
+    let mut paragraph: Option<(usize, bool)> = None;
+    let mut fence: Option<usize> = None;
+    let mut offset = 0;
+
+    let flush = |blocks: &mut Vec<Block>, start: usize, end: usize, heading: bool, max: usize| {
+        for (i, (s, e)) in split_oversized(text, start, end, max).into_iter().enumerate() {
+            blocks.push(Block { start: s, end: e, heading: heading && i == 0 });
+        }
+    };
+
+    for line in text.split_inclusive('\n') {
+        let line_start = offset;
+        offset += line.len();
+        let trimmed = line.trim_start();
+        let is_fence = trimmed.starts_with("```") || trimmed.starts_with("~~~");
+
+        if let Some(start) = fence {
+            if is_fence {
+                flush(&mut blocks, start, offset, false, MAX_CODE_BLOCK_BYTES);
+                fence = None;
+            }
+            continue;
+        }
+
+        if is_fence || is_heading(trimmed) || line.trim().is_empty() {
+            if let Some((start, heading)) = paragraph.take() {
+                flush(&mut blocks, start, line_start, heading, PASSAGE_BYTES);
+            }
+        }
+        if is_fence {
+            fence = Some(line_start);
+        } else if is_heading(trimmed) {
+            paragraph = Some((line_start, true));
+        } else if !line.trim().is_empty() && paragraph.is_none() {
+            paragraph = Some((line_start, false));
+        }
+    }
+
+    // An unclosed fence runs to the end
+    if let Some(start) = fence {
+        flush(&mut blocks, start, text.len(), false, MAX_CODE_BLOCK_BYTES);
+    }
+    if let Some((start, heading)) = paragraph {
+        flush(&mut blocks, start, text.len(), heading, PASSAGE_BYTES);
+    }
+    blocks
+}
+
+fn is_heading(line: &str) -> bool {
+    let hashes = line.chars().take_while(|c| *c == '#').count();
+    (1..=6).contains(&hashes) && line[hashes..].starts_with([' ', '\t'])
+}
+
+/// `start..end` cut between lines (or, for a single huge line, at whitespace) into
+/// pieces of at most `max` bytes
+fn split_oversized(text: &str, start: usize, end: usize, max: usize) -> Vec<(usize, usize)> {
+    let mut pieces = Vec::new();
+    let mut piece_start = start;
+    let mut piece_end = start;
+
+    for line in text[start..end].split_inclusive('\n') {
+        let line_end = piece_end + line.len();
+        if line_end - piece_start <= max {
+            piece_end = line_end;
+            continue;
+        }
+        if piece_end > piece_start {
+            pieces.push((piece_start, piece_end));
+            piece_start = piece_end;
+        }
+        // The line alone may still be too long
+        while line_end - piece_start > max {
+            let cut = cut_point(text, piece_start, piece_start + max);
+            pieces.push((piece_start, cut));
+            piece_start = cut;
+        }
+        piece_end = line_end;
+    }
+    if piece_end > piece_start {
+        pieces.push((piece_start, piece_end));
+    }
+    pieces
+}
+
+/// Last whitespace (or char boundary) at or before `limit`, past `start`
+fn cut_point(text: &str, start: usize, limit: usize) -> usize {
+    let mut limit = limit.min(text.len());
+    while !text.is_char_boundary(limit) {
+        limit -= 1;
+    }
+    match text[start..limit].rfind(char::is_whitespace) {
+        Some(i) if i > 0 => start + i + text[start + i..].chars().next().map_or(1, char::len_utf8),
+        _ if limit > start => limit,
+        // Not even one character fits; take one anyway
+        _ => start + text[start..].chars().next().map_or(1, char::len_utf8),
+    }
+}
+
+/// Greedily pack blocks into passages of about `PASSAGE_BYTES`
+fn pack(text: &str, blocks: &[Block]) -> Vec<(usize, usize)> {
+    let mut passages = Vec::new();
+    let mut current: Option<(usize, usize)> = None;
+    // Index of the first block that went into the current passage
+    let mut first = 0;
+
+    for (i, block) in blocks.iter().enumerate() {
+        let (start, end) = match current {
+            Some(range) => range,
+            None => {
+                current = Some((block.start, block.end));
+                first = i;
+                continue;
+            }
+        };
+
+        let new_section = block.heading && end - start >= MIN_SECTION_BYTES;
+        if block.end - start <= PASSAGE_BYTES && !new_section {
+            current = Some((start, block.end));
+            continue;
+        }
+
+        passages.push((start, end));
+        let mut next_start = block.start;
+        let mut next_first = i;
+        if !new_section {
+            // Repeat trailing blocks of the passage just closed, up to the overlap
+            for j in (first + 1..i).rev() {
+                if end - blocks[j].start > OVERLAP_BYTES {
+                    break;
+                }
+                next_start = blocks[j].start;
+                next_first = j;
+            }
+            // The last block is too big to repeat whole: repeat its tail
+            if next_start == block.start {
+                next_start = overlap_start(text, start, end);
+            }
+        }
+        current = Some((next_start, block.end));
+        first = next_first;
+    }
+
+    if let Some(range) = current {
+        passages.push(range);
+    }
+    if passages.is_empty() {
+        passages.push((0, 0));
+    }
+    passages
+}
+
+/// Where the last `OVERLAP_BYTES` of `start..end` begin, moved forward to a word start
+/// (or at least a char boundary)
+fn overlap_start(text: &str, start: usize, end: usize) -> usize {
+    let mut tail = end.saturating_sub(OVERLAP_BYTES).max(start);
+    while !text.is_char_boundary(tail) {
+        tail += 1;
+    }
+    match text[tail..end].find(char::is_whitespace) {
+        Some(i) => {
+            let word = tail + i + text[tail + i..].chars().next().map_or(1, char::len_utf8);
+            if word < end { word } else { tail }
+        }
+        None => tail,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn passages(answer: &str) -> (Conversation, Vec<Passage>) {
+        let conversation = Conversation::sample("session-a", "2026-09-01T10:00:00Z", "Explain it.", answer);
+        let passages = split(&conversation);
+        (conversation, passages)
+    }
+
+    fn text<'a>(conversation: &'a Conversation, passage: &Passage) -> &'a str {
+        passage.side(conversation, Role::Assistant)
+    }
+
+    #[test]
+    fn short_exchange_is_one_passage() {
+        let (conversation, found) = passages("Short answer.");
+        assert_eq!(found.len(), 1);
+        assert_eq!((found[0].start, found[0].end), (0, exchange_text(&conversation).len()));
+    }
+
+    #[test]
+    fn one_long_paragraph_is_cut_with_overlapping_tails() {
+        // No blank lines, and multi-byte characters everywhere
+        let answer = "Der Überprüfungsschritt läuft für jede Anfrage erneut. ".repeat(80);
+        let (conversation, found) = passages(&answer);
+        assert!(found.len() > 2, "{:?}", found);
+
+        for pair in found.windows(2) {
+            let overlap = pair[0].end.saturating_sub(pair[1].start);
+            assert!(overlap > 0 && overlap <= OVERLAP_BYTES, "{:?}", pair);
+            assert!(text(&conversation, &pair[1]).starts_with(|c: char| !c.is_whitespace()), "{:?}", pair);
+        }
+        assert_eq!(found.last().unwrap().end, exchange_text(&conversation).len());
+    }
+
+    #[test]
+    fn small_trailing_paragraphs_are_repeated_whole() {
+        let answer = format!("{}\n\nA short closing note.\n\n{}", "a ".repeat(500), "b ".repeat(500));
+        let (conversation, found) = passages(&answer);
+        assert_eq!(found.len(), 2);
+        assert!(text(&conversation, &found[1]).starts_with("A short closing note."));
+    }
+
+    #[test]
+    fn headings_start_passages_without_overlap() {
+        let answer = format!("# Deploy\n\n{}\n\n# Rollback\n\n{}", "deploy step. ".repeat(60), "rollback step. ".repeat(60));
+        let (conversation, found) = passages(&answer);
+        assert_eq!(found.len(), 2);
+        assert!(found[0].end <= found[1].start, "{:?}", found);
+        assert!(text(&conversation, &found[1]).starts_with("# Rollback"));
+    }
+
+    #[test]
+    fn code_blocks_stay_whole() {
+        let code = format!("```rust\n{}```\n", "let value = compute(input);\n".repeat(60));
+        let answer = format!("{}\n\n{}\n\nAfter the block.", "intro words ".repeat(60), code);
+        let (conversation, found) = passages(&answer);
+        let holding: Vec<&Passage> = found.iter().filter(|p| text(&conversation, p).contains("```rust")).collect();
+        assert!(holding.iter().any(|p| text(&conversation, p).contains(&code)), "{:?}", found);
+    }
+
+    #[test]
+    fn containing_prefers_the_earliest_overlapping_passage() {
+        let (_, found) = passages(&"word ".repeat(600));
+        let shared = found[1].start;
+        assert_eq!(containing(&found, shared).unwrap().ordinal, 0);
+        assert_eq!(containing(&found, found[1].end).unwrap().ordinal, 2);
+        assert_eq!(containing(&found, usize::MAX).unwrap().ordinal, found.len() - 1);
+    }
+}
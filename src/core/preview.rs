@@ -0,0 +1,30 @@
+/// The first `max_chars` characters of `text`, marked when cut
+pub fn preview(text: &str, max_chars: usize) -> String {
+    match text.char_indices().nth(max_chars) {
+        Some((end, _)) => format!("{}...", &text[..end]),
+        None => text.to_string(),
+    }
+}
+
+/// The first line of `text`, trimmed and cut like [`preview`]
+pub fn line_preview(text: &str, max_chars: usize) -> String {
+    preview(text.lines().next().unwrap_or("").trim(), max_chars)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cuts_on_characters_not_bytes() {
+        assert_eq!(preview("açaí bowl", 4), "açaí...");
+        assert_eq!(preview("açaí", 4), "açaí");
+        assert_eq!(preview("", 4), "");
+    }
+
+    #[test]
+    fn line_preview_keeps_the_first_line() {
+        assert_eq!(line_preview("  fix the login flow  \nsecond line", 60), "fix the login flow");
+        assert_eq!(line_preview("fix the login flow", 3), "fix...");
+    }
+}
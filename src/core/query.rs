@@ -0,0 +1,588 @@
+use anyhow::{bail, Context, Result};
+use chrono::{DateTime, Duration, NaiveDate, Utc};
+use regex::{Regex, RegexBuilder};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::iter::Peekable;
+use std::str::Chars;
+
+use crate::core::code::path_matches;
+use crate::core::index::tokenize;
+use crate::core::types::Conversation;
+
+/// Side of an exchange that text terms are matched against
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Role {
+    User,
+    Assistant,
+}
+
+/// How the text of a search is interpreted
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SearchMode {
+    /// Query syntax with exact (stemmed) word matching
+    #[default]
+    Keyword,
+    /// Query syntax, with words also matching indexed terms a few typos away
+    Fuzzy,
+    /// The whole text is a regular expression run over every message
+    Regex,
+}
+
+/// A search, parsed according to its mode
+#[derive(Debug, Clone)]
+pub enum Search {
+    Keyword(Expr),
+    Fuzzy(Expr),
+    Regex(Regex),
+}
+
+impl Search {
+    pub fn parse(text: &str, mode: SearchMode) -> Result<Self> {
+        Ok(match mode {
+            SearchMode::Keyword => Search::Keyword(Expr::parse(text)?),
+            SearchMode::Fuzzy => Search::Fuzzy(Expr::parse(text)?),
+            SearchMode::Regex => Search::Regex(compile_regex(text)?),
+        })
+    }
+}
+
+/// Upper bound on a compiled search pattern, so a pathological one fails fast
+const MAX_REGEX_SIZE: usize = 1 << 20;
+
+/// Compile a search pattern; case-insensitive unless it opts out with `(?-i)`
+pub fn compile_regex(pattern: &str) -> Result<Regex> {
+    if pattern.is_empty() {
+        bail!("Empty regular expression");
+    }
+    RegexBuilder::new(pattern)
+        .case_insensitive(true)
+        .size_limit(MAX_REGEX_SIZE)
+        .build()
+        .context("Invalid regular expression")
+}
+
+/// A `field:value` restriction
+#[derive(Debug, Clone, PartialEq)]
+pub enum Filter {
+    /// Conversation carries this tag (case-insensitive)
+    Tag(String),
+    /// Session id starts with this prefix
+    Session(String),
+    /// Captured before this instant
+    Before(DateTime<Utc>),
+    /// Captured at or after this instant
+    After(DateTime<Utc>),
+    /// Match text terms against this side only; always true on its own
+    Role(Role),
+    /// Identifier defined, called or named in the exchange (case-insensitive)
+    Symbol(String),
+    /// File touched by a tool or named in the exchange, by path suffix
+    File(String),
+    /// Fenced code block in this language (case-insensitive)
+    Lang(String),
+}
+
+/// Parsed search query
+///
+/// Bare words written next to each other form one `Terms` node that matches when
+/// any of them occurs, so a plain sentence ranks like before. Phrases, filters,
+/// negations and explicit `AND`/`OR` constrain the results; `AND` binds tighter
+/// than `OR`, and clauses without an operator between them must all hold.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Expr {
+    Terms(Vec<String>),
+    Phrase(String),
+    Filter(Filter),
+    Not(Box<Expr>),
+    And(Vec<Expr>),
+    Or(Vec<Expr>),
+}
+
+impl Expr {
+    /// Parse the query syntax, e.g. `tag:rust "borrow checker" -unsafe after:2026-09-01`
+    pub fn parse(query: &str) -> Result<Self> {
+        let tokens = lex(query)?;
+        let mut parser = Parser { tokens, pos: 0 };
+        let expr = parser.parse_or()?;
+        match parser.tokens.get(parser.pos) {
+            None => Ok(expr),
+            Some(Token::RParen) => bail!("Unmatched ')' in query"),
+            Some(token) => bail!("Unexpected {:?} in query", token),
+        }
+    }
+
+    /// Free text taken literally, with no query syntax (used for prompts)
+    pub fn text(text: &str) -> Self {
+        Expr::Terms(text.split_whitespace().map(str::to_string).collect())
+    }
+
+    /// Query matching every conversation
+    pub fn all() -> Self {
+        Expr::And(Vec::new())
+    }
+
+    /// Words that rank results: every term and phrase that is not negated
+    pub fn ranking_text(&self) -> String {
+        let mut words = Vec::new();
+        self.collect_ranking_words(&mut words);
+        words.join(" ")
+    }
+
+    /// A run of bare words and nothing else, so keyword ranking alone decides
+    pub fn is_plain_text(&self) -> bool {
+        matches!(self, Expr::Terms(_))
+    }
+
+    /// Whether `conversation` satisfies the query
+    ///
+    /// With `loose`, terms that are not negated always match: semantic search
+    /// decides relevance itself and only needs phrases and filters checked.
+    pub fn matches(&self, conversation: &Conversation, loose: bool) -> bool {
+        let roles = self.roles();
+        let doc = Doc::new(conversation, &roles);
+        self.eval(&doc, loose)
+    }
+
+    fn eval(&self, doc: &Doc, loose: bool) -> bool {
+        match self {
+            Expr::Terms(words) => {
+                if loose {
+                    return true;
+                }
+                let tokens: Vec<String> = words.iter().flat_map(|w| tokenize(w)).collect();
+                // Only stopwords: nothing to require
+                tokens.is_empty() || tokens.iter().any(|t| doc.tokens.contains(t))
+            }
+            Expr::Phrase(phrase) => doc.text.contains(&normalize(phrase)),
+            Expr::Filter(filter) => filter.matches(doc.conversation),
+            // Negated terms are always checked strictly
+            Expr::Not(inner) => !inner.eval(doc, false),
+            Expr::And(items) => items.iter().all(|item| item.eval(doc, loose)),
+            Expr::Or(items) => items.iter().any(|item| item.eval(doc, loose)),
+        }
+    }
+
+    fn roles(&self) -> HashSet<Role> {
+        let mut roles = HashSet::new();
+        self.visit(&mut |expr| {
+            if let Expr::Filter(Filter::Role(role)) = expr {
+                roles.insert(*role);
+            }
+        });
+        roles
+    }
+
+    fn visit(&self, f: &mut impl FnMut(&Expr)) {
+        f(self);
+        match self {
+            Expr::Not(inner) => inner.visit(f),
+            Expr::And(items) | Expr::Or(items) => items.iter().for_each(|item| item.visit(f)),
+            _ => {}
+        }
+    }
+
+    fn collect_ranking_words(&self, words: &mut Vec<String>) {
+        match self {
+            Expr::Terms(terms) => words.extend(terms.iter().cloned()),
+            Expr::Phrase(phrase) => words.push(phrase.clone()),
+            Expr::And(items) | Expr::Or(items) => {
+                items.iter().for_each(|item| item.collect_ranking_words(words));
+            }
+            Expr::Filter(_) | Expr::Not(_) => {}
+        }
+    }
+}
+
+impl Filter {
+    fn parse(field: &str, value: &str) -> Result<Self> {
+        if value.is_empty() {
+            bail!("{}: needs a value", field);
+        }
+        Ok(match field {
+            "tag" => Filter::Tag(value.to_string()),
+            "session" => Filter::Session(value.to_string()),
+            "before" => Filter::Before(parse_instant(value, false)?),
+            "after" => Filter::After(parse_instant(value, true)?),
+            "role" => match value.to_lowercase().as_str() {
+                "user" => Filter::Role(Role::User),
+                "assistant" => Filter::Role(Role::Assistant),
+                _ => bail!("role: must be user or assistant, not {}", value),
+            },
+            "symbol" => Filter::Symbol(value.trim_end_matches("()").to_string()),
+            "file" => Filter::File(value.to_string()),
+            "lang" => Filter::Lang(value.to_string()),
+            _ => bail!("Unknown search field {}:", field),
+        })
+    }
+
+    fn matches(&self, conversation: &Conversation) -> bool {
+        let metadata = &conversation.metadata;
+        match self {
+            Filter::Tag(tag) => metadata.tags.iter().any(|t| t.eq_ignore_ascii_case(tag)),
+            Filter::Session(prefix) => metadata.session_id.as_deref().is_some_and(|id| id.starts_with(prefix.as_str())),
+            Filter::Before(instant) => conversation.timestamp < *instant,
+            Filter::After(instant) => conversation.timestamp >= *instant,
+            Filter::Role(_) => true,
+            Filter::Symbol(name) => metadata.symbols.iter().any(|s| s.eq_ignore_ascii_case(name)),
+            Filter::File(path) => metadata
+                .files_touched
+                .iter()
+                .chain(&metadata.files_mentioned)
+                .any(|file| path_matches(file, path)),
+            Filter::Lang(language) => metadata.languages.iter().any(|l| l.eq_ignore_ascii_case(language)),
+        }
+    }
+}
+
+/// Field names recognized before a `:`; anything else (`std::fs`, URLs) is a word
+const FIELDS: &[&str] = &["tag", "session", "before", "after", "role", "symbol", "file", "lang"];
+
+/// An RFC 3339 instant or a `YYYY-MM-DD` day
+///
+/// A bare day means its start for `before:` and its end for `after:`, so both
+/// exclude the day itself.
+fn parse_instant(value: &str, end_of_day: bool) -> Result<DateTime<Utc>> {
+    if let Ok(instant) = DateTime::parse_from_rfc3339(value) {
+        return Ok(instant.with_timezone(&Utc));
+    }
+    let day = NaiveDate::parse_from_str(value, "%Y-%m-%d")
+        .with_context(|| format!("Invalid date {} (use YYYY-MM-DD or RFC 3339)", value))?;
+    let start = day.and_hms_opt(0, 0, 0).expect("midnight exists").and_utc();
+    Ok(if end_of_day { start + Duration::days(1) } else { start })
+}
+
+/// Lowercased text with whitespace runs collapsed, for phrase matching
+fn normalize(text: &str) -> String {
+    text.split_whitespace().collect::<Vec<_>>().join(" ").to_lowercase()
+}
+
+/// The searchable side(s) of a conversation
+struct Doc<'a> {
+    conversation: &'a Conversation,
+    text: String,
+    tokens: HashSet<String>,
+}
+
+impl<'a> Doc<'a> {
+    fn new(conversation: &'a Conversation, roles: &HashSet<Role>) -> Self {
+        let mut text = String::new();
+        if roles.is_empty() || roles.contains(&Role::User) {
+            text.push_str(&conversation.user_message);
+            text.push('\n');
+        }
+        if roles.is_empty() || roles.contains(&Role::Assistant) {
+            text.push_str(&conversation.assistant_response);
+        }
+        Self {
+            conversation,
+            tokens: tokenize(&text).into_iter().collect(),
+            text: normalize(&text),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    LParen,
+    RParen,
+    Not,
+    And,
+    Or,
+    Word(String),
+    Phrase(String),
+    Filter(Filter),
+}
+
+fn lex(query: &str) -> Result<Vec<Token>> {
+    let mut tokens = Vec::new();
+    let mut chars = query.chars().peekable();
+
+    while let Some(&c) = chars.peek() {
+        match c {
+            c if c.is_whitespace() => {
+                chars.next();
+            }
+            '(' => {
+                chars.next();
+                tokens.push(Token::LParen);
+            }
+            ')' => {
+                chars.next();
+                tokens.push(Token::RParen);
+            }
+            '"' => {
+                chars.next();
+                tokens.push(Token::Phrase(read_quoted(&mut chars)?));
+            }
+            '-' => {
+                chars.next();
+                // A lone or trailing dash is just a word
+                match chars.peek() {
+                    Some(next) if !next.is_whitespace() && *next != ')' => tokens.push(Token::Not),
+                    _ => tokens.push(Token::Word("-".to_string())),
+                }
+            }
+            _ => {
+                let word = read_word(&mut chars);
+                let field = word.split_once(':').filter(|(name, _)| FIELDS.contains(&name.to_lowercase().as_str()));
+                let token = match field {
+                    Some((name, value)) => {
+                        let value = if value.is_empty() && chars.peek() == Some(&'"') {
+                            chars.next();
+                            read_quoted(&mut chars)?
+                        } else {
+                            // In `symbol:name()` the parentheses are part of the value, not a group
+                            let call = chars.clone().take(2).eq("()".chars());
+                            if call && name.eq_ignore_ascii_case("symbol") && !value.is_empty() {
+                                chars.nth(1);
+                            }
+                            value.to_string()
+                        };
+                        Token::Filter(Filter::parse(&name.to_lowercase(), &value)?)
+                    }
+                    None => match word.as_str() {
+                        "AND" => Token::And,
+                        "OR" => Token::Or,
+                        "NOT" => Token::Not,
+                        _ => Token::Word(word),
+                    },
+                };
+                tokens.push(token);
+            }
+        }
+    }
+    Ok(tokens)
+}
+
+fn read_quoted(chars: &mut Peekable<Chars>) -> Result<String> {
+    let mut text = String::new();
+    for c in chars.by_ref() {
+        if c == '"' {
+            return Ok(text);
+        }
+        text.push(c);
+    }
+    bail!("Unterminated quote in query")
+}
+
+fn read_word(chars: &mut Peekable<Chars>) -> String {
+    let mut word = String::new();
+    while let Some(&c) = chars.peek() {
+        if c.is_whitespace() || c == '(' || c == ')' || c == '"' {
+            break;
+        }
+        word.push(c);
+        chars.next();
+    }
+    word
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        token
+    }
+
+    fn parse_or(&mut self) -> Result<Expr> {
+        let mut items = vec![self.parse_and()?];
+        while self.peek() == Some(&Token::Or) {
+            self.next();
+            let item = self.parse_and()?;
+            if item == Expr::all() || items.last() == Some(&Expr::all()) {
+                bail!("OR needs a term on both sides");
+            }
+            items.push(item);
+        }
+        Ok(if items.len() == 1 { items.remove(0) } else { Expr::Or(items) })
+    }
+
+    fn parse_and(&mut self) -> Result<Expr> {
+        let mut items: Vec<Expr> = Vec::new();
+        // Bare words join the preceding run unless an explicit AND separates them
+        let mut joinable = false;
+
+        loop {
+            match self.peek() {
+                None | Some(Token::RParen) | Some(Token::Or) => break,
+                Some(Token::And) => {
+                    self.next();
+                    if items.is_empty() || matches!(self.peek(), None | Some(Token::RParen) | Some(Token::Or)) {
+                        bail!("AND needs a term on both sides");
+                    }
+                    joinable = false;
+                }
+                Some(Token::Word(_)) => {
+                    let word = match self.next() {
+                        Some(Token::Word(word)) => word,
+                        _ => unreachable!(),
+                    };
+                    match items.last_mut() {
+                        Some(Expr::Terms(words)) if joinable => words.push(word),
+                        _ => items.push(Expr::Terms(vec![word])),
+                    }
+                    joinable = true;
+                }
+                Some(_) => {
+                    items.push(self.parse_unary()?);
+                    joinable = false;
+                }
+            }
+        }
+
+        Ok(if items.len() == 1 { items.remove(0) } else { Expr::And(items) })
+    }
+
+    fn parse_unary(&mut self) -> Result<Expr> {
+        match self.next() {
+            Some(Token::Not) => match self.peek() {
+                None | Some(Token::RParen) | Some(Token::Or) | Some(Token::And) => bail!("NOT needs a term after it"),
+                _ => Ok(Expr::Not(Box::new(self.parse_unary()?))),
+            },
+            Some(Token::LParen) => {
+                let expr = self.parse_or()?;
+                match self.next() {
+                    Some(Token::RParen) => Ok(expr),
+                    _ => bail!("Missing ')' in query"),
+                }
+            }
+            Some(Token::Word(word)) => Ok(Expr::Terms(vec![word])),
+            Some(Token::Phrase(phrase)) => Ok(Expr::Phrase(phrase)),
+            Some(Token::Filter(filter)) => Ok(Expr::Filter(filter)),
+            Some(token) => bail!("Unexpected {:?} in query", token),
+            None => bail!("Query ends too early"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn terms(words: &[&str]) -> Expr {
+        Expr::Terms(words.iter().map(|w| w.to_string()).collect())
+    }
+
+    fn parse(query: &str) -> Expr {
+        Expr::parse(query).unwrap_or_else(|e| panic!("{:?}: {}", query, e))
+    }
+
+    fn error(query: &str) -> String {
+        Expr::parse(query).unwrap_err().to_string()
+    }
+
+    fn instant(text: &str) -> DateTime<Utc> {
+        text.parse().unwrap()
+    }
+
+    #[test]
+    fn lexer_recognizes_fields_operators_and_quotes() {
+        assert_eq!(lex(r#"TAG:rust tag:"two words" std::fs https://x.io"#).unwrap(), [
+            Token::Filter(Filter::Tag("rust".to_string())),
+            Token::Filter(Filter::Tag("two words".to_string())),
+            Token::Word("std::fs".to_string()),
+            Token::Word("https://x.io".to_string()),
+        ]);
+        assert_eq!(lex("(a)-b - c-").unwrap(), [
+            Token::LParen,
+            Token::Word("a".to_string()),
+            Token::RParen,
+            Token::Not,
+            Token::Word("b".to_string()),
+            Token::Word("-".to_string()),
+            Token::Word("c-".to_string()),
+        ]);
+        // Operators are case-sensitive, so lowercase ones stay words
+        assert_eq!(lex("AND or NOT").unwrap(), [Token::And, Token::Word("or".to_string()), Token::Not]);
+    }
+
+    #[test]
+    fn symbol_calls_are_not_groups() {
+        let symbol = |name: &str| Token::Filter(Filter::Symbol(name.to_string()));
+        assert_eq!(lex("symbol:parse()").unwrap(), [symbol("parse")]);
+        assert_eq!(lex("symbol:Memory::search() x").unwrap(), [
+            symbol("Memory::search"),
+            Token::Word("x".to_string()),
+        ]);
+        assert_eq!(lex(r#"symbol:"parse()""#).unwrap(), [symbol("parse")]);
+        // Only right after the value
+        let tag = Token::Filter(Filter::Tag("a".to_string()));
+        assert_eq!(lex("tag:a()").unwrap(), [tag, Token::LParen, Token::RParen]);
+    }
+
+    #[test]
+    fn bare_words_form_one_run() {
+        assert_eq!(parse("a b c"), terms(&["a", "b", "c"]));
+        assert_eq!(parse(r#"a "p q" b"#), Expr::And(vec![terms(&["a"]), Expr::Phrase("p q".to_string()), terms(&["b"])]));
+        assert_eq!(parse("a AND b c"), Expr::And(vec![terms(&["a"]), terms(&["b", "c"])]));
+    }
+
+    #[test]
+    fn and_binds_tighter_than_or() {
+        assert_eq!(parse("a OR b c"), Expr::Or(vec![terms(&["a"]), terms(&["b", "c"])]));
+        assert_eq!(
+            parse("a AND b OR c"),
+            Expr::Or(vec![Expr::And(vec![terms(&["a"]), terms(&["b"])]), terms(&["c"])]),
+        );
+        assert_eq!(
+            parse("a AND (b OR c)"),
+            Expr::And(vec![terms(&["a"]), Expr::Or(vec![terms(&["b"]), terms(&["c"])])]),
+        );
+    }
+
+    #[test]
+    fn negation_applies_to_the_next_clause() {
+        assert_eq!(parse("-x"), Expr::Not(Box::new(terms(&["x"]))));
+        assert_eq!(parse("NOT (a OR b)"), Expr::Not(Box::new(Expr::Or(vec![terms(&["a"]), terms(&["b"])]))));
+        assert_eq!(
+            parse("a -tag:rust b"),
+            Expr::And(vec![
+                terms(&["a"]),
+                Expr::Not(Box::new(Expr::Filter(Filter::Tag("rust".to_string())))),
+                terms(&["b"]),
+            ]),
+        );
+        assert_eq!(parse("a -tag:rust b").ranking_text(), "a b");
+    }
+
+    #[test]
+    fn bare_days_exclude_the_day_itself() {
+        assert_eq!(parse("after:2026-09-01"), Expr::Filter(Filter::After(instant("2026-09-02T00:00:00Z"))));
+        assert_eq!(parse("before:2026-09-01"), Expr::Filter(Filter::Before(instant("2026-09-01T00:00:00Z"))));
+        assert_eq!(
+            parse("after:2026-09-01T10:30:00+02:00"),
+            Expr::Filter(Filter::After(instant("2026-09-01T08:30:00Z"))),
+        );
+
+        let query = parse("after:2026-09-01");
+        let late = Conversation::sample("s", "2026-09-01T23:59:59Z", "q", "a");
+        let next = Conversation::sample("s", "2026-09-02T00:00:00Z", "q", "a");
+        assert!(!query.matches(&late, false));
+        assert!(query.matches(&next, false));
+    }
+
+    #[test]
+    fn malformed_queries_explain_themselves() {
+        assert!(error("a)").contains("Unmatched ')'"));
+        assert!(error("(a").contains("Missing ')'"));
+        assert!(error("\"open").contains("Unterminated quote"));
+        assert!(error("a OR").contains("OR needs a term"));
+        assert!(error("AND a").contains("AND needs a term"));
+        assert!(error("a NOT").contains("NOT needs a term"));
+        assert!(error("tag:").contains("needs a value"));
+        assert!(error("role:system").contains("must be user or assistant"));
+        assert!(format!("{:#}", Expr::parse("before:yesterday").unwrap_err()).contains("Invalid date yesterday"));
+    }
+}
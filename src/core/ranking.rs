@@ -0,0 +1,120 @@
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::collections::HashMap;
+use uuid::Uuid;
+
+use crate::core::types::RankingConfig;
+
+/// One signal's part in a fused score
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct SignalScore {
+    /// The signal's own measure: BM25 (or regex match count), cosine similarity, or
+    /// the recency decay factor
+    pub raw: f32,
+    /// 1-based place among the candidates this signal ranked; ties share a place
+    pub rank: usize,
+    /// What the signal added to the fused score
+    pub contribution: f32,
+}
+
+/// Why a search hit scored what it did; a signal is `None` when it did not rank it
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize)]
+pub struct Signals {
+    pub keyword: Option<SignalScore>,
+    pub semantic: Option<SignalScore>,
+    pub recency: Option<SignalScore>,
+}
+
+/// A candidate with its fused score
+#[derive(Debug, Clone, Copy)]
+pub struct Fused {
+    pub id: Uuid,
+    pub score: f32,
+    pub signals: Signals,
+}
+
+/// Merge keyword and semantic rankings with reciprocal rank fusion
+///
+/// Each list is best first. A candidate gains `weight / (rrf_k + rank)` from every
+/// list it appears in, plus a recency boost of `recency_weight * decay / (rrf_k + 1)`
+/// where `decay` halves every `recency_half_life_days`: at most what a first place
+/// is worth, so recency reorders close calls without adding candidates of its own.
+/// Ties go to the newer conversation.
+pub fn fuse(
+    keyword: &[(Uuid, f32)],
+    semantic: &[(Uuid, f32)],
+    timestamps: &HashMap<Uuid, DateTime<Utc>>,
+    config: &RankingConfig,
+    now: DateTime<Utc>,
+) -> Vec<Fused> {
+    let rrf_k = config.rrf_k as f32;
+    let recency_weight = config.recency_weight as f32;
+    let half_life_days = config.recency_half_life_days as f32;
+    let mut fused: Vec<Fused> = Vec::new();
+    let mut positions: HashMap<Uuid, usize> = HashMap::new();
+
+    for (list, weight, is_keyword) in [
+        (keyword, config.keyword_weight as f32, true),
+        (semantic, config.semantic_weight as f32, false),
+    ] {
+        for (rank, (id, raw)) in competition_ranks(list) {
+            let contribution = weight / (rrf_k + rank as f32);
+            let position = *positions.entry(id).or_insert_with(|| {
+                fused.push(Fused { id, score: 0.0, signals: Signals::default() });
+                fused.len() - 1
+            });
+            let entry = &mut fused[position];
+            entry.score += contribution;
+            let signal = Some(SignalScore { raw, rank, contribution });
+            if is_keyword {
+                entry.signals.keyword = signal;
+            } else {
+                entry.signals.semantic = signal;
+            }
+        }
+    }
+
+    if recency_weight > 0.0 && half_life_days > 0.0 {
+        let mut by_age: Vec<(Uuid, f32)> = fused
+            .iter()
+            .filter_map(|f| Some((f.id, decay(*timestamps.get(&f.id)?, now, half_life_days))))
+            .collect();
+        by_age.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap_or(std::cmp::Ordering::Equal));
+
+        for (rank, (id, raw)) in competition_ranks(&by_age) {
+            let contribution = recency_weight * raw / (rrf_k + 1.0);
+            let entry = &mut fused[positions[&id]];
+            entry.score += contribution;
+            entry.signals.recency = Some(SignalScore { raw, rank, contribution });
+        }
+    }
+
+    fused.sort_by(|a, b| {
+        b.score
+            .partial_cmp(&a.score)
+            .unwrap_or(std::cmp::Ordering::Equal)
+            .then_with(|| timestamps.get(&b.id).cmp(&timestamps.get(&a.id)))
+    });
+    fused
+}
+
+/// 1.0 now, 0.5 one half-life ago; future timestamps count as now
+fn decay(timestamp: DateTime<Utc>, now: DateTime<Utc>, half_life_days: f32) -> f32 {
+    let age_days = (now - timestamp).num_seconds().max(0) as f32 / 86_400.0;
+    0.5f32.powf(age_days / half_life_days)
+}
+
+/// Items of a best-first list with their place, equal scores sharing one ("1224")
+fn competition_ranks(list: &[(Uuid, f32)]) -> Vec<(usize, (Uuid, f32))> {
+    let mut ranked = Vec::with_capacity(list.len());
+    let mut rank = 0;
+    let mut previous = None;
+    for (i, &(id, raw)) in list.iter().enumerate() {
+        if previous != Some(raw) {
+            rank = i + 1;
+            previous = Some(raw);
+        }
+        ranked.push((rank, (id, raw)));
+    }
+    ranked
+}
@@ -0,0 +1,274 @@
+use anyhow::{Context, Result};
+use chrono::{DateTime, Duration, Utc};
+use std::collections::{HashMap, HashSet};
+use tracing::debug;
+use uuid::Uuid;
+
+use crate::core::memory::Memory;
+use crate::core::types::{Conversation, RetentionConfig, SessionDigest};
+
+/// Which retention limit a conversation is pruned under
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PruneReason {
+    Age,
+    Count,
+    Size,
+}
+
+impl PruneReason {
+    pub fn label(&self) -> &'static str {
+        match self {
+            PruneReason::Age => "older than max_age_days",
+            PruneReason::Count => "over max_conversations",
+            PruneReason::Size => "over max_store_mb",
+        }
+    }
+}
+
+/// A conversation the retention rules would delete
+pub struct PruneCandidate {
+    pub conversation: Conversation,
+    pub reason: PruneReason,
+}
+
+/// Everything the retention rules would delete
+#[derive(Default)]
+pub struct RetentionPlan {
+    /// Oldest first
+    pub conversations: Vec<PruneCandidate>,
+    /// Sessions whose digest goes, because none of their conversations are left
+    pub digests: Vec<String>,
+}
+
+impl RetentionPlan {
+    pub fn is_empty(&self) -> bool {
+        self.conversations.is_empty() && self.digests.is_empty()
+    }
+}
+
+/// Whether any retention limit is set
+pub fn is_configured(config: &RetentionConfig) -> bool {
+    config.max_age_days.is_some() || config.max_conversations.is_some() || config.max_store_mb.is_some()
+}
+
+/// Work out what `config` prunes from `memory`
+pub async fn plan_for(memory: &Memory, config: &RetentionConfig) -> Result<RetentionPlan> {
+    let conversations = memory.all_conversations().await?;
+    let sizes = memory.record_sizes().await;
+    Ok(plan(conversations, &memory.session_digests(), &sizes, config, Utc::now()))
+}
+
+/// Delete whatever `config` prunes, returning how many conversations were removed
+pub async fn apply(memory: &Memory, config: &RetentionConfig) -> Result<usize> {
+    if !is_configured(config) {
+        return Ok(0);
+    }
+
+    let plan = plan_for(memory, config).await?;
+    execute(memory, &plan).await?;
+
+    if !plan.is_empty() {
+        // Pruned text should not linger in old segments
+        memory.compact().await.context("Failed to compact memory store")?;
+        debug!(
+            "Pruned {} conversations and {} digests under retention rules",
+            plan.conversations.len(),
+            plan.digests.len()
+        );
+    }
+    Ok(plan.conversations.len())
+}
+
+/// Delete the conversations and digests of a plan
+pub async fn execute(memory: &Memory, plan: &RetentionPlan) -> Result<()> {
+    for candidate in &plan.conversations {
+        memory.delete_conversation(&candidate.conversation.id).await
+            .context("Failed to delete pruned conversation")?;
+    }
+    for session_id in &plan.digests {
+        memory.delete_digest(session_id).await
+            .context("Failed to delete pruned session digest")?;
+    }
+    Ok(())
+}
+
+/// Apply the limits in order: age, then count, then size
+///
+/// Count and size drop the oldest conversations first. Pinned conversations are
+/// never candidates while `keep_pinned` is set, but still count toward the limits,
+/// so a store full of pinned conversations can stay over them. A session's digest
+/// goes once none of its conversations are left.
+pub fn plan(
+    mut conversations: Vec<Conversation>,
+    digests: &[SessionDigest],
+    sizes: &HashMap<Uuid, u64>,
+    config: &RetentionConfig,
+    now: DateTime<Utc>,
+) -> RetentionPlan {
+    conversations.sort_by_key(|c| c.timestamp);
+    let sessions: Vec<(Uuid, Option<String>)> =
+        conversations.iter().map(|c| (c.id, c.metadata.session_id.clone())).collect();
+
+    let mut pruned = Vec::new();
+    let mut kept = Vec::with_capacity(conversations.len());
+    let cutoff = config.max_age_days.map(|days| now - Duration::days(i64::from(days)));
+
+    for conversation in conversations {
+        let prunable = !(config.keep_pinned && conversation.metadata.pinned);
+        if prunable && cutoff.is_some_and(|cutoff| conversation.timestamp < cutoff) {
+            pruned.push(PruneCandidate { conversation, reason: PruneReason::Age });
+        } else {
+            kept.push(conversation);
+        }
+    }
+
+    let mut count = kept.len();
+    let mut bytes: u64 = kept.iter().map(|c| sizes.get(&c.id).copied().unwrap_or(0)).sum();
+    let max_bytes = config.max_store_mb.map(|mb| mb * 1024 * 1024);
+
+    for conversation in kept {
+        if config.keep_pinned && conversation.metadata.pinned {
+            continue;
+        }
+        let reason = if config.max_conversations.is_some_and(|max| count > max) {
+            PruneReason::Count
+        } else if max_bytes.is_some_and(|max| bytes > max) {
+            PruneReason::Size
+        } else {
+            break;
+        };
+
+        count -= 1;
+        bytes -= sizes.get(&conversation.id).copied().unwrap_or(0);
+        pruned.push(PruneCandidate { conversation, reason });
+    }
+
+    let pruned_ids: HashSet<Uuid> = pruned.iter().map(|c| c.conversation.id).collect();
+    let live_sessions: HashSet<String> = sessions
+        .into_iter()
+        .filter(|(id, _)| !pruned_ids.contains(id))
+        .filter_map(|(_, session)| session)
+        .collect();
+    let mut digests: Vec<String> = digests
+        .iter()
+        .filter(|d| !live_sessions.contains(&d.session_id))
+        .map(|d| d.session_id.clone())
+        .collect();
+    digests.sort();
+
+    RetentionPlan { conversations: pruned, digests }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NOW: &str = "2026-10-01T00:00:00Z";
+
+    fn conversation(session: &str, days_ago: i64, pinned: bool) -> Conversation {
+        let timestamp = NOW.parse::<DateTime<Utc>>().unwrap() - Duration::days(days_ago);
+        let mut conversation =
+            Conversation::sample(session, &timestamp.to_rfc3339(), &format!("{} days ago", days_ago), "answer");
+        conversation.metadata.pinned = pinned;
+        conversation
+    }
+
+    fn digest(session: &str) -> SessionDigest {
+        let at: DateTime<Utc> = NOW.parse().unwrap();
+        SessionDigest {
+            session_id: session.to_string(),
+            started_at: at,
+            ended_at: at,
+            conversation_count: 1,
+            topics: Vec::new(),
+            decisions: Vec::new(),
+            files_touched: Vec::new(),
+            todos: Vec::new(),
+            summarizer: "extractive".to_string(),
+            source_fingerprint: 0,
+            created_at: at,
+        }
+    }
+
+    fn run(conversations: &[Conversation], digests: &[SessionDigest], sizes: &HashMap<Uuid, u64>, config: &RetentionConfig) -> RetentionPlan {
+        plan(conversations.to_vec(), digests, sizes, config, NOW.parse().unwrap())
+    }
+
+    /// `(days ago, reason)` of each pruned conversation
+    fn pruned(plan: &RetentionPlan) -> Vec<(String, PruneReason)> {
+        plan.conversations.iter().map(|c| (c.conversation.user_message.clone(), c.reason)).collect()
+    }
+
+    #[test]
+    fn nothing_is_pruned_without_limits() {
+        let config = RetentionConfig::default();
+        assert!(!is_configured(&config));
+        let conversations = [conversation("a", 1000, false)];
+        assert!(run(&conversations, &[digest("a")], &HashMap::new(), &config).is_empty());
+    }
+
+    #[test]
+    fn age_limit_prunes_older_conversations() {
+        let config = RetentionConfig { max_age_days: Some(30), ..Default::default() };
+        let conversations = [conversation("a", 10, false), conversation("a", 45, false), conversation("a", 31, false)];
+        let plan = run(&conversations, &[], &HashMap::new(), &config);
+        assert_eq!(pruned(&plan), [
+            ("45 days ago".to_string(), PruneReason::Age),
+            ("31 days ago".to_string(), PruneReason::Age),
+        ]);
+    }
+
+    #[test]
+    fn count_limit_prunes_the_oldest_first() {
+        let config = RetentionConfig { max_conversations: Some(2), ..Default::default() };
+        let conversations: Vec<Conversation> = (1..=4).map(|days| conversation("a", days, false)).collect();
+        let plan = run(&conversations, &[], &HashMap::new(), &config);
+        assert_eq!(pruned(&plan), [
+            ("4 days ago".to_string(), PruneReason::Count),
+            ("3 days ago".to_string(), PruneReason::Count),
+        ]);
+    }
+
+    #[test]
+    fn size_limit_counts_record_bytes() {
+        let config = RetentionConfig { max_store_mb: Some(1), ..Default::default() };
+        let conversations: Vec<Conversation> = (1..=3).map(|days| conversation("a", days, false)).collect();
+        let sizes: HashMap<Uuid, u64> = conversations.iter().map(|c| (c.id, 400 * 1024)).collect();
+        let plan = run(&conversations, &[], &sizes, &config);
+        assert_eq!(pruned(&plan), [("3 days ago".to_string(), PruneReason::Size)]);
+    }
+
+    #[test]
+    fn pinned_conversations_are_kept_but_still_count() {
+        let config = RetentionConfig { max_conversations: Some(1), max_age_days: Some(5), ..Default::default() };
+        let conversations = [conversation("a", 10, true), conversation("a", 3, false), conversation("a", 1, false)];
+        let plan = run(&conversations, &[], &HashMap::new(), &config);
+        // The pinned one survives the age limit and takes the only slot
+        assert_eq!(pruned(&plan), [
+            ("3 days ago".to_string(), PruneReason::Count),
+            ("1 days ago".to_string(), PruneReason::Count),
+        ]);
+
+        let config = RetentionConfig { keep_pinned: false, ..config };
+        let plan = run(&conversations, &[], &HashMap::new(), &config);
+        assert_eq!(pruned(&plan), [
+            ("10 days ago".to_string(), PruneReason::Age),
+            ("3 days ago".to_string(), PruneReason::Count),
+        ]);
+    }
+
+    #[test]
+    fn digests_go_with_the_last_conversation_of_their_session() {
+        let config = RetentionConfig { max_age_days: Some(30), ..Default::default() };
+        let conversations = [
+            conversation("old", 60, false),
+            conversation("mixed", 40, false),
+            conversation("mixed", 5, false),
+            conversation("pinned", 90, true),
+        ];
+        let digests = [digest("old"), digest("mixed"), digest("pinned"), digest("gone")];
+        let plan = run(&conversations, &digests, &HashMap::new(), &config);
+        assert_eq!(plan.conversations.len(), 2);
+        assert_eq!(plan.digests, ["gone", "old"]);
+    }
+}
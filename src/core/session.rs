@@ -0,0 +1,188 @@
+use std::collections::{HashMap, HashSet};
+use uuid::Uuid;
+
+use crate::core::types::{Conversation, Session};
+
+/// Sessions derived from stored conversations, kept current as they change
+///
+/// Each session remembers which conversations belong to it, so a change only
+/// re-aggregates that one session.
+#[derive(Debug, Default)]
+pub struct SessionIndex {
+    sessions: HashMap<String, Session>,
+    members: HashMap<String, HashSet<Uuid>>,
+}
+
+impl SessionIndex {
+    pub fn build(conversations: &HashMap<Uuid, Conversation>) -> Self {
+        let mut index = Self::default();
+        for conversation in conversations.values() {
+            if let Some(session_id) = &conversation.metadata.session_id {
+                index.members.entry(session_id.clone()).or_default().insert(conversation.id);
+            }
+        }
+        let session_ids: Vec<String> = index.members.keys().cloned().collect();
+        for session_id in session_ids {
+            index.refresh(&session_id, conversations);
+        }
+        index
+    }
+
+    /// Account for `conversation` having been stored, replacing `previous` if any
+    pub fn upsert(
+        &mut self,
+        previous: Option<&Conversation>,
+        conversation: &Conversation,
+        conversations: &HashMap<Uuid, Conversation>,
+    ) {
+        if let Some(previous) = previous {
+            self.remove(previous, conversations);
+        }
+        if let Some(session_id) = &conversation.metadata.session_id {
+            self.members.entry(session_id.clone()).or_default().insert(conversation.id);
+            self.refresh(session_id, conversations);
+        }
+    }
+
+    /// Account for `conversation` having been removed from `conversations`
+    pub fn remove(&mut self, conversation: &Conversation, conversations: &HashMap<Uuid, Conversation>) {
+        if let Some(session_id) = &conversation.metadata.session_id {
+            if let Some(members) = self.members.get_mut(session_id) {
+                members.remove(&conversation.id);
+            }
+            self.refresh(session_id, conversations);
+        }
+    }
+
+    pub fn get(&self, session_id: &str) -> Option<&Session> {
+        self.sessions.get(session_id)
+    }
+
+    /// Every session, most recently active first
+    pub fn list(&self) -> Vec<Session> {
+        let mut sessions: Vec<Session> = self.sessions.values().cloned().collect();
+        sessions.sort_by_key(|s| std::cmp::Reverse(s.ended_at));
+        sessions
+    }
+
+    /// Ids of the conversations captured in a session
+    pub fn members(&self, session_id: &str) -> Vec<Uuid> {
+        self.members.get(session_id).map(|m| m.iter().copied().collect()).unwrap_or_default()
+    }
+
+    /// Re-aggregate one session from its members
+    fn refresh(&mut self, session_id: &str, conversations: &HashMap<Uuid, Conversation>) {
+        let mut turns: Vec<&Conversation> = self
+            .members
+            .get(session_id)
+            .map(|ids| ids.iter().filter_map(|id| conversations.get(id)).collect())
+            .unwrap_or_default();
+
+        if turns.is_empty() {
+            self.sessions.remove(session_id);
+            self.members.remove(session_id);
+            return;
+        }
+        turns.sort_by_key(|c| c.timestamp);
+
+        let session = Session {
+            id: session_id.to_string(),
+            started_at: turns[0].timestamp,
+            ended_at: turns[turns.len() - 1].timestamp,
+            turn_count: turns.len(),
+            token_total: turns.iter().map(|c| c.metadata.token_count).sum(),
+            git_branch: turns.iter().rev().find_map(|c| c.metadata.git_branch.clone()),
+            cwd: turns.iter().rev().find_map(|c| c.metadata.project_path.clone()),
+        };
+        self.sessions.insert(session_id.to_string(), session);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn exchange(session: &str, timestamp: &str, prompt: &str, tokens: usize, branch: Option<&str>) -> Conversation {
+        let mut conversation = Conversation::sample(session, timestamp, prompt, "answer");
+        conversation.metadata.token_count = tokens;
+        conversation.metadata.git_branch = branch.map(str::to_string);
+        conversation.metadata.project_path = Some("/work/app".to_string());
+        conversation
+    }
+
+    fn by_id(conversations: &[Conversation]) -> HashMap<Uuid, Conversation> {
+        conversations.iter().map(|c| (c.id, c.clone())).collect()
+    }
+
+    #[test]
+    fn build_aggregates_each_session() {
+        let conversations = by_id(&[
+            exchange("a", "2026-09-01T10:05:00Z", "second", 20, None),
+            exchange("a", "2026-09-01T10:00:00Z", "first", 10, Some("main")),
+            exchange("b", "2026-09-02T09:00:00Z", "other", 5, Some("feature")),
+        ]);
+        let index = SessionIndex::build(&conversations);
+
+        let a = index.get("a").unwrap();
+        assert_eq!(a.turn_count, 2);
+        assert_eq!(a.token_total, 30);
+        assert_eq!(a.started_at.to_rfc3339(), "2026-09-01T10:00:00+00:00");
+        assert_eq!(a.ended_at.to_rfc3339(), "2026-09-01T10:05:00+00:00");
+        // Latest turn that knows its branch
+        assert_eq!(a.git_branch.as_deref(), Some("main"));
+        assert_eq!(a.cwd.as_deref(), Some("/work/app"));
+        assert_eq!(index.members("a").len(), 2);
+
+        let order: Vec<String> = index.list().into_iter().map(|s| s.id).collect();
+        assert_eq!(order, ["b", "a"]);
+    }
+
+    #[test]
+    fn conversations_without_a_session_are_not_indexed() {
+        let mut loose = exchange("a", "2026-09-01T10:00:00Z", "loose", 1, None);
+        loose.metadata.session_id = None;
+        let index = SessionIndex::build(&by_id(&[loose]));
+        assert!(index.list().is_empty());
+    }
+
+    #[test]
+    fn upsert_adds_and_moves_conversations() {
+        let first = exchange("a", "2026-09-01T10:00:00Z", "first", 10, None);
+        let mut conversations = by_id(std::slice::from_ref(&first));
+        let mut index = SessionIndex::build(&conversations);
+
+        let second = exchange("a", "2026-09-01T11:00:00Z", "second", 10, Some("main"));
+        conversations.insert(second.id, second.clone());
+        index.upsert(None, &second, &conversations);
+        assert_eq!(index.get("a").unwrap().turn_count, 2);
+        assert_eq!(index.get("a").unwrap().git_branch.as_deref(), Some("main"));
+
+        // Re-stored under another session: the old one shrinks, the new one appears
+        let mut moved = second.clone();
+        moved.metadata.session_id = Some("b".to_string());
+        conversations.insert(moved.id, moved.clone());
+        index.upsert(Some(&second), &moved, &conversations);
+        assert_eq!(index.get("a").unwrap().turn_count, 1);
+        assert_eq!(index.get("a").unwrap().git_branch, None);
+        assert_eq!(index.members("b"), [moved.id]);
+    }
+
+    #[test]
+    fn removing_the_last_conversation_drops_the_session() {
+        let first = exchange("a", "2026-09-01T10:00:00Z", "first", 10, None);
+        let second = exchange("a", "2026-09-01T11:00:00Z", "second", 10, None);
+        let mut conversations = by_id(&[first.clone(), second.clone()]);
+        let mut index = SessionIndex::build(&conversations);
+
+        conversations.remove(&second.id);
+        index.remove(&second, &conversations);
+        let session = index.get("a").unwrap();
+        assert_eq!((session.turn_count, session.ended_at), (1, first.timestamp));
+
+        conversations.remove(&first.id);
+        index.remove(&first, &conversations);
+        assert!(index.get("a").is_none());
+        assert!(index.members("a").is_empty());
+        assert!(index.list().is_empty());
+    }
+}
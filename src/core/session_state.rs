@@ -0,0 +1,521 @@
+use anyhow::{Context, Result};
+use chrono::{DateTime, Duration, Utc};
+use fs2::FileExt;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::fs::File;
+use std::path::{Path, PathBuf};
+use tracing::debug;
+use uuid::Uuid;
+
+use crate::core::hash::fnv1a;
+use crate::core::index::tokenize;
+use crate::core::types::HooksConfig;
+
+const STATE_FILE: &str = "session_state.json";
+const LOCK_FILE: &str = "session_state.lock";
+
+/// Per-session marker files written by earlier versions
+const LEGACY_MARKER_PREFIX: &str = "session_injected_";
+
+/// Remember at most this many injected conversation ids per session
+const MAX_TRACKED_CONVERSATIONS: usize = 200;
+
+/// A prompt sharing less than this fraction of terms with the last injected one
+/// is on a new topic
+const TOPIC_CHANGE_SIMILARITY: f32 = 0.15;
+
+/// Prompts with fewer terms than this never count as a topic change
+const MIN_TOPIC_TERMS: usize = 3;
+
+/// What the UserPromptSubmit hooks remember about a session
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SessionState {
+    pub first_seen: DateTime<Utc>,
+    pub last_seen: DateTime<Utc>,
+    pub last_injected_at: Option<DateTime<Utc>>,
+    pub injection_count: u32,
+    /// Conversations already injected into the session, oldest first
+    #[serde(default)]
+    pub injected_conversations: Vec<Uuid>,
+    /// Prompts submitted since the last injection
+    #[serde(default)]
+    pub turns_since_injection: u32,
+    /// Hashed terms of the prompt that triggered the last injection, so no prompt
+    /// text is kept outside the (possibly encrypted) store
+    #[serde(default)]
+    pub topic: Vec<u64>,
+    /// When the previous session was last recalled into this one, which is tracked
+    /// apart from prompt injections so recalling it never blocks relevant memory
+    #[serde(default)]
+    pub previous_session_at: Option<DateTime<Utc>>,
+}
+
+impl SessionState {
+    fn new(now: DateTime<Utc>) -> Self {
+        Self {
+            first_seen: now,
+            last_seen: now,
+            last_injected_at: None,
+            injection_count: 0,
+            injected_conversations: Vec::new(),
+            turns_since_injection: 0,
+            topic: Vec::new(),
+            previous_session_at: None,
+        }
+    }
+}
+
+/// Why memory gets injected
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InjectReason {
+    FirstPrompt,
+    TurnLimit,
+    TopicChange,
+    /// The previous session, recalled when this one starts
+    SessionStart,
+}
+
+/// A decision to inject, recorded when it is made so concurrent hooks do not both
+/// act on it, and handed back with `release` if nothing ends up injected
+#[derive(Debug, Clone, PartialEq)]
+pub struct Claim {
+    pub reason: InjectReason,
+    /// The session's state before the claim
+    before: SessionState,
+}
+
+/// Injection state of every active session, in `.off-context/session_state.json`
+///
+/// Each change is a locked read-modify-write (temp file + rename), so concurrent
+/// hook processes never both claim a session's first injection. Sessions idle
+/// for longer than `hooks.session_state_ttl_days` are dropped on write.
+pub struct SessionStateStore {
+    dir: PathBuf,
+}
+
+impl SessionStateStore {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self { dir: dir.into() }
+    }
+
+    /// Every tracked session
+    pub fn load(&self) -> Result<HashMap<String, SessionState>> {
+        let _lock = self.lock()?;
+        Ok(self.read()?.unwrap_or_default())
+    }
+
+    pub fn get(&self, session_id: &str) -> Result<Option<SessionState>> {
+        Ok(self.load()?.remove(session_id))
+    }
+
+    /// Register a prompt and decide whether it gets memory injected
+    ///
+    /// A positive decision is claimed right away; `release` it if building the
+    /// context comes up empty, so a later prompt tries again.
+    pub fn check_in(&self, session_id: &str, prompt: &str, policy: &HooksConfig) -> Result<Option<Claim>> {
+        let now = Utc::now();
+        let topic = topic_terms(prompt);
+
+        self.update(Some(policy.session_state_ttl_days), |sessions| {
+            let state = sessions
+                .entry(session_id.to_string())
+                .or_insert_with(|| SessionState::new(now));
+            state.last_seen = now;
+            let before = state.clone();
+
+            let reason = if state.last_injected_at.is_none() {
+                Some(InjectReason::FirstPrompt)
+            } else if policy
+                .reinject_after_turns
+                .is_some_and(|turns| state.turns_since_injection + 1 >= turns.max(1))
+            {
+                Some(InjectReason::TurnLimit)
+            } else if policy.reinject_on_topic_change && is_topic_change(&state.topic, &topic) {
+                Some(InjectReason::TopicChange)
+            } else {
+                None
+            };
+
+            match reason {
+                Some(_) => {
+                    state.last_injected_at = Some(now);
+                    state.injection_count += 1;
+                    state.turns_since_injection = 0;
+                    state.topic = topic;
+                }
+                None => state.turns_since_injection += 1,
+            }
+            reason.map(|reason| Claim { reason, before })
+        })
+    }
+
+    /// Decide whether the previous session is recalled when this one starts
+    ///
+    /// Yes the first time a session starts, and again with `refresh` (after a
+    /// compaction, which drops what was injected earlier, so those conversations
+    /// become eligible again). Prompt injections are not affected.
+    pub fn check_in_start(&self, session_id: &str, refresh: bool, policy: &HooksConfig) -> Result<Option<Claim>> {
+        let now = Utc::now();
+
+        self.update(Some(policy.session_state_ttl_days), |sessions| {
+            let state = sessions
+                .entry(session_id.to_string())
+                .or_insert_with(|| SessionState::new(now));
+            state.last_seen = now;
+
+            if state.previous_session_at.is_some() && !refresh {
+                return None;
+            }
+            if refresh {
+                state.injected_conversations.clear();
+            }
+            let before = state.clone();
+            state.previous_session_at = Some(now);
+            state.injection_count += 1;
+            Some(Claim { reason: InjectReason::SessionStart, before })
+        })
+    }
+
+    /// Undo a claim that injected nothing
+    ///
+    /// A released prompt claim still counts the prompt as a turn without injection.
+    pub fn release(&self, session_id: &str, claim: &Claim) -> Result<()> {
+        self.update(None, |sessions| {
+            if let Some(state) = sessions.get_mut(session_id) {
+                let before = &claim.before;
+                state.injection_count = state.injection_count.saturating_sub(1);
+                match claim.reason {
+                    InjectReason::SessionStart => state.previous_session_at = before.previous_session_at,
+                    _ => {
+                        state.last_injected_at = before.last_injected_at;
+                        state.turns_since_injection = before.turns_since_injection + 1;
+                        state.topic = before.topic.clone();
+                    }
+                }
+            }
+        })
+    }
+
+    /// Remember which conversations were injected into a session
+    pub fn record_injected(&self, session_id: &str, conversations: &[Uuid]) -> Result<()> {
+        if conversations.is_empty() {
+            return Ok(());
+        }
+        self.update(None, |sessions| {
+            if let Some(state) = sessions.get_mut(session_id) {
+                for id in conversations {
+                    if !state.injected_conversations.contains(id) {
+                        state.injected_conversations.push(*id);
+                    }
+                }
+                let excess = state.injected_conversations.len().saturating_sub(MAX_TRACKED_CONVERSATIONS);
+                state.injected_conversations.drain(..excess);
+            }
+        })
+    }
+
+    /// Drop a session's state, returning whether it was tracked
+    pub fn forget(&self, session_id: &str) -> Result<bool> {
+        self.update(None, |sessions| sessions.remove(session_id).is_some())
+    }
+
+    /// Drop every session's state (and any legacy marker files), returning how
+    /// many sessions were tracked
+    pub fn clear(&self) -> Result<usize> {
+        let _lock = self.lock()?;
+        let count = self.read()?.map_or(0, |sessions| sessions.len()) + remove_legacy_markers(&self.dir)?.len();
+
+        match std::fs::remove_file(self.dir.join(STATE_FILE)) {
+            Ok(()) => {}
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(e) => return Err(e).context("Failed to remove session state"),
+        }
+        Ok(count)
+    }
+
+    fn update<R>(&self, ttl_days: Option<u32>, f: impl FnOnce(&mut HashMap<String, SessionState>) -> R) -> Result<R> {
+        let _lock = self.lock()?;
+        let mut sessions = match self.read()? {
+            Some(sessions) => sessions,
+            None => migrate_legacy_markers(&self.dir)?,
+        };
+
+        let result = f(&mut sessions);
+
+        if let Some(days) = ttl_days {
+            let cutoff = Utc::now() - Duration::days(days as i64);
+            let before = sessions.len();
+            sessions.retain(|_, state| state.last_seen >= cutoff);
+            if sessions.len() < before {
+                debug!("Expired {} idle session states", before - sessions.len());
+            }
+        }
+
+        self.write(&sessions)?;
+        Ok(result)
+    }
+
+    fn read(&self) -> Result<Option<HashMap<String, SessionState>>> {
+        let content = match std::fs::read_to_string(self.dir.join(STATE_FILE)) {
+            Ok(content) => content,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e).context("Failed to read session state"),
+        };
+        // A damaged file only costs a repeated injection, never a failed hook
+        Ok(Some(serde_json::from_str(&content).unwrap_or_else(|e| {
+            debug!("Discarding unreadable session state: {}", e);
+            HashMap::new()
+        })))
+    }
+
+    fn write(&self, sessions: &HashMap<String, SessionState>) -> Result<()> {
+        let content = serde_json::to_vec_pretty(sessions).context("Failed to serialize session state")?;
+        let path = self.dir.join(STATE_FILE);
+        let tmp_path = path.with_extension("json.tmp");
+        std::fs::write(&tmp_path, content).context("Failed to write session state")?;
+        std::fs::rename(&tmp_path, &path).context("Failed to replace session state")?;
+        Ok(())
+    }
+
+    fn lock(&self) -> Result<File> {
+        std::fs::create_dir_all(&self.dir).context("Failed to create session state directory")?;
+        let file = File::create(self.dir.join(LOCK_FILE)).context("Failed to open session state lock file")?;
+        file.lock_exclusive().context("Failed to lock session state")?;
+        Ok(file)
+    }
+}
+
+fn topic_terms(prompt: &str) -> Vec<u64> {
+    let terms: HashSet<u64> = tokenize(prompt).iter().map(|t| fnv1a(t.as_bytes())).collect();
+    let mut terms: Vec<u64> = terms.into_iter().collect();
+    terms.sort_unstable();
+    terms
+}
+
+/// Jaccard similarity of the two term sets falls below `TOPIC_CHANGE_SIMILARITY`
+fn is_topic_change(previous: &[u64], current: &[u64]) -> bool {
+    if previous.len() < MIN_TOPIC_TERMS || current.len() < MIN_TOPIC_TERMS {
+        return false;
+    }
+    let shared = current.iter().filter(|t| previous.binary_search(t).is_ok()).count();
+    let union = previous.len() + current.len() - shared;
+    (shared as f32 / union as f32) < TOPIC_CHANGE_SIMILARITY
+}
+
+/// Turn `session_injected_<id>` marker files into state entries, removing them
+fn migrate_legacy_markers(dir: &Path) -> Result<HashMap<String, SessionState>> {
+    let mut sessions = HashMap::new();
+    for (session_id, marked_at) in remove_legacy_markers(dir)? {
+        let mut state = SessionState::new(marked_at);
+        state.last_injected_at = Some(marked_at);
+        state.injection_count = 1;
+        sessions.insert(session_id, state);
+    }
+    if !sessions.is_empty() {
+        debug!("Migrated {} legacy session marker files", sessions.len());
+    }
+    Ok(sessions)
+}
+
+fn remove_legacy_markers(dir: &Path) -> Result<Vec<(String, DateTime<Utc>)>> {
+    let entries = match std::fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e).context("Failed to read project config directory"),
+    };
+
+    let mut markers = Vec::new();
+    for entry in entries.flatten() {
+        let name = entry.file_name().to_string_lossy().to_string();
+        let session_id = match name.strip_prefix(LEGACY_MARKER_PREFIX) {
+            Some(session_id) => session_id,
+            None => continue,
+        };
+        let marked_at = entry
+            .metadata()
+            .and_then(|m| m.modified())
+            .map(DateTime::<Utc>::from)
+            .unwrap_or_else(|_| Utc::now());
+        std::fs::remove_file(entry.path())
+            .with_context(|| format!("Failed to remove {}", name))?;
+        markers.push((session_id.to_string(), marked_at));
+    }
+    Ok(markers)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn policy(reinject_after_turns: Option<u32>, reinject_on_topic_change: bool) -> HooksConfig {
+        HooksConfig {
+            enabled: true,
+            auto_inject: true,
+            prompt_output: crate::core::types::default_prompt_output(),
+            reinject_after_turns,
+            reinject_on_topic_change,
+            session_state_ttl_days: crate::core::types::default_session_state_ttl_days(),
+        }
+    }
+
+    fn reason(store: &SessionStateStore, prompt: &str, policy: &HooksConfig) -> Option<InjectReason> {
+        store.check_in("s", prompt, policy).unwrap().map(|claim| claim.reason)
+    }
+
+    #[test]
+    fn injects_once_per_session_by_default() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = SessionStateStore::new(dir.path());
+        let policy = policy(None, false);
+
+        assert_eq!(reason(&store, "deploy the auth service", &policy), Some(InjectReason::FirstPrompt));
+        for _ in 0..5 {
+            assert_eq!(reason(&store, "deploy the auth service", &policy), None);
+        }
+        let state = store.get("s").unwrap().unwrap();
+        assert_eq!(state.injection_count, 1);
+        assert_eq!(state.turns_since_injection, 5);
+    }
+
+    #[test]
+    fn reinjects_after_the_configured_number_of_turns() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = SessionStateStore::new(dir.path());
+        let policy = policy(Some(3), false);
+
+        let reasons: Vec<Option<InjectReason>> = (0..7).map(|_| reason(&store, "same prompt", &policy)).collect();
+        assert_eq!(reasons, [
+            Some(InjectReason::FirstPrompt),
+            None,
+            None,
+            Some(InjectReason::TurnLimit),
+            None,
+            None,
+            Some(InjectReason::TurnLimit),
+        ]);
+    }
+
+    #[test]
+    fn reinjects_when_the_topic_changes() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = SessionStateStore::new(dir.path());
+        let on_topic_change = policy(None, true);
+
+        assert!(reason(&store, "why are login tokens rejected after deploy", &on_topic_change).is_some());
+        assert_eq!(reason(&store, "login tokens still rejected after the deploy", &on_topic_change), None);
+        // Too short to tell
+        assert_eq!(reason(&store, "thanks", &on_topic_change), None);
+        assert_eq!(
+            reason(&store, "which database migrations are still pending", &on_topic_change),
+            Some(InjectReason::TopicChange)
+        );
+
+        // Without the policy a new topic changes nothing
+        assert_eq!(reason(&store, "rust borrow checker lifetimes", &policy(None, false)), None);
+    }
+
+    #[test]
+    fn topic_change_is_low_jaccard_similarity_over_enough_terms() {
+        let login = topic_terms("why are login tokens rejected after deploy");
+        assert!(!is_topic_change(&login, &topic_terms("login tokens rejected again")));
+        assert!(is_topic_change(&login, &topic_terms("pending database migrations list")));
+        assert!(!is_topic_change(&login, &topic_terms("migrations pending")));
+        assert!(!is_topic_change(&[], &topic_terms("pending database migrations list")));
+    }
+
+    #[test]
+    fn released_claim_lets_the_next_prompt_try_again() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = SessionStateStore::new(dir.path());
+        let policy = policy(None, false);
+
+        let claim = store.check_in("s", "deploy", &policy).unwrap().unwrap();
+        store.release("s", &claim).unwrap();
+        let state = store.get("s").unwrap().unwrap();
+        assert_eq!((state.last_injected_at, state.injection_count, state.turns_since_injection), (None, 0, 1));
+        assert_eq!(reason(&store, "deploy", &policy), Some(InjectReason::FirstPrompt));
+    }
+
+    #[test]
+    fn idle_sessions_expire() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = SessionStateStore::new(dir.path());
+        let mut policy = policy(None, false);
+        policy.session_state_ttl_days = 7;
+
+        let long_ago = Utc::now() - Duration::days(8);
+        let recent = Utc::now() - Duration::days(6);
+        store.write(&HashMap::from([
+            ("idle".to_string(), SessionState::new(long_ago)),
+            ("active".to_string(), SessionState::new(recent)),
+        ])).unwrap();
+
+        store.check_in("s", "deploy", &policy).unwrap();
+        let mut ids: Vec<String> = store.load().unwrap().into_keys().collect();
+        ids.sort();
+        assert_eq!(ids, ["active", "s"]);
+    }
+
+    #[test]
+    fn legacy_markers_are_migrated_and_removed() {
+        let dir = tempfile::tempdir().unwrap();
+        let marker = dir.path().join(format!("{}old-session", LEGACY_MARKER_PREFIX));
+        std::fs::write(&marker, "").unwrap();
+        let store = SessionStateStore::new(dir.path());
+
+        // The marked session already had its injection
+        assert_eq!(store.check_in("old-session", "deploy", &policy(None, false)).unwrap(), None);
+        assert!(!marker.exists());
+        let state = store.get("old-session").unwrap().unwrap();
+        assert!(state.last_injected_at.is_some());
+        assert_eq!(state.injection_count, 1);
+
+        std::fs::write(&marker, "").unwrap();
+        assert_eq!(store.clear().unwrap(), 2);
+        assert!(!marker.exists());
+        assert!(store.load().unwrap().is_empty());
+    }
+
+    #[test]
+    fn concurrent_first_prompts_claim_once() {
+        let dir = tempfile::tempdir().unwrap();
+        let policy = policy(None, false);
+
+        let claims: usize = std::thread::scope(|scope| {
+            let handles: Vec<_> = (0..8)
+                .map(|_| {
+                    scope.spawn(|| {
+                        let store = SessionStateStore::new(dir.path());
+                        store.check_in("s", "deploy", &policy).unwrap().is_some() as usize
+                    })
+                })
+                .collect();
+            handles.into_iter().map(|handle| handle.join().unwrap()).sum()
+        });
+
+        assert_eq!(claims, 1);
+        let state = SessionStateStore::new(dir.path()).get("s").unwrap().unwrap();
+        assert_eq!((state.injection_count, state.turns_since_injection), (1, 7));
+    }
+
+    #[test]
+    fn injected_conversations_are_deduplicated_and_capped() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = SessionStateStore::new(dir.path());
+        store.check_in("s", "deploy", &policy(None, false)).unwrap();
+
+        let ids: Vec<Uuid> = (0..MAX_TRACKED_CONVERSATIONS + 10).map(|_| Uuid::new_v4()).collect();
+        store.record_injected("s", &ids[..5]).unwrap();
+        store.record_injected("s", &ids[..5]).unwrap();
+        assert_eq!(store.get("s").unwrap().unwrap().injected_conversations, &ids[..5]);
+
+        store.record_injected("s", &ids).unwrap();
+        assert_eq!(store.get("s").unwrap().unwrap().injected_conversations, &ids[10..]);
+
+        // A session start after compaction makes them eligible again
+        let claim = store.check_in_start("s", true, &policy(None, false)).unwrap();
+        assert_eq!(claim.map(|claim| claim.reason), Some(InjectReason::SessionStart));
+        assert!(store.get("s").unwrap().unwrap().injected_conversations.is_empty());
+    }
+}
@@ -0,0 +1,542 @@
+use anyhow::{Context, Result};
+use fs2::FileExt;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::fs::File;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tokio::io::AsyncWriteExt;
+use tracing::{debug, info, warn};
+use uuid::Uuid;
+
+use crate::core::crypto::{self, Cipher};
+use crate::core::embeddings::Embedding;
+use crate::core::types::{Conversation, SessionDigest};
+
+/// Start a new segment once the active one grows past this size
+const SEGMENT_MAX_BYTES: u64 = 4 * 1024 * 1024;
+
+/// Compact once more segments than this have accumulated
+const MAX_SEGMENTS: usize = 8;
+
+/// Compact once this many superseded records are on disk (and they outnumber live ones)
+const COMPACT_MIN_DEAD: usize = 512;
+
+/// One line of the append-only log
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "op", rename_all = "snake_case")]
+pub enum LogRecord {
+    Put { conversation: Box<Conversation> },
+    Delete { id: Uuid },
+    Embedding { id: Uuid, embedding: Embedding },
+    Digest { digest: Box<SessionDigest> },
+    DeleteDigest { session_id: String },
+}
+
+/// State rebuilt by replaying every segment in order
+#[derive(Debug, Default)]
+pub struct Snapshot {
+    pub conversations: HashMap<Uuid, Conversation>,
+    pub embeddings: HashMap<Uuid, Embedding>,
+    /// Session digests by session id; they outlive the conversations they summarize
+    pub digests: HashMap<String, SessionDigest>,
+    /// Records on disk that no longer contribute to the live state
+    pub dead_records: usize,
+}
+
+impl Snapshot {
+    fn apply(&mut self, record: LogRecord) {
+        match record {
+            LogRecord::Put { conversation } => {
+                // A rewritten conversation invalidates its old embedding
+                if self.embeddings.remove(&conversation.id).is_some() {
+                    self.dead_records += 1;
+                }
+                if self.conversations.insert(conversation.id, *conversation).is_some() {
+                    self.dead_records += 1;
+                }
+            }
+            LogRecord::Delete { id } => {
+                // The delete record itself is dead weight, plus whatever it removed
+                self.dead_records += 1;
+                if self.conversations.remove(&id).is_some() {
+                    self.dead_records += 1;
+                }
+                if self.embeddings.remove(&id).is_some() {
+                    self.dead_records += 1;
+                }
+            }
+            LogRecord::Embedding { id, embedding } => {
+                // Orphaned (conversation already deleted) or superseded vectors are dead
+                let orphaned = !self.conversations.contains_key(&id);
+                if orphaned || self.embeddings.insert(id, embedding).is_some() {
+                    self.dead_records += 1;
+                }
+            }
+            LogRecord::Digest { digest } => {
+                if self.digests.insert(digest.session_id.clone(), *digest).is_some() {
+                    self.dead_records += 1;
+                }
+            }
+            LogRecord::DeleteDigest { session_id } => {
+                self.dead_records += 1;
+                if self.digests.remove(&session_id).is_some() {
+                    self.dead_records += 1;
+                }
+            }
+        }
+    }
+}
+
+/// Append-only JSONL segment store
+///
+/// Records are appended to numbered `segments/NNNNNNNN.jsonl` files and replayed in
+/// order on load. Compaction rewrites the live state into a fresh segment through a
+/// temp file + rename, so a crash at any point leaves a replayable directory. A lock
+/// file serializes writers across concurrent hook processes.
+///
+/// With a cipher, every line is encrypted on its own, so appends stay cheap and a
+/// torn line is still the only record lost.
+pub struct SegmentStore {
+    dir: PathBuf,
+    cipher: Option<Arc<Cipher>>,
+}
+
+impl SegmentStore {
+    /// Open the store at `dir`, migrating a legacy `conversations.json` if present
+    pub async fn open(dir: PathBuf, legacy_path: &Path, cipher: Option<Arc<Cipher>>) -> Result<(Self, Snapshot)> {
+        tokio::fs::create_dir_all(&dir).await
+            .context("Failed to create segment directory")?;
+
+        let store = Self { dir, cipher };
+
+        if legacy_path.exists() {
+            store.migrate_legacy(legacy_path).await?;
+        }
+
+        let snapshot = {
+            let _lock = store.lock_shared()?;
+            store.replay().await?
+        };
+
+        Ok((store, snapshot))
+    }
+
+    /// Switch the cipher used for new records; existing ones are rewritten by `compact`
+    pub fn set_cipher(&mut self, cipher: Option<Arc<Cipher>>) {
+        self.cipher = cipher;
+    }
+
+    /// Append a record to the active segment
+    pub async fn append(&self, record: &LogRecord) -> Result<()> {
+        let mut line = self.encode(record)?;
+        line.push('\n');
+
+        let _lock = self.lock_exclusive()?;
+
+        let ids = self.segment_ids().await?;
+        let mut active = ids.last().copied().unwrap_or(1);
+        let mut len = tokio::fs::metadata(self.segment_path(active)).await
+            .map(|m| m.len())
+            .unwrap_or(0);
+
+        if len >= SEGMENT_MAX_BYTES {
+            active += 1;
+            len = 0;
+            debug!("Rolling over to segment {}", active);
+        }
+
+        // A previous writer may have crashed mid-line; start on a fresh line so
+        // only the torn record is lost
+        if len > 0 && !self.ends_with_newline(active).await? {
+            line.insert(0, '\n');
+        }
+
+        let mut file = tokio::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(self.segment_path(active))
+            .await
+            .context("Failed to open segment for append")?;
+        file.write_all(line.as_bytes()).await
+            .context("Failed to append to segment")?;
+        file.flush().await?;
+
+        Ok(())
+    }
+
+    /// Compact if the log has grown too fragmented, returning the fresh snapshot
+    pub async fn maybe_compact(&self, live: usize, dead: usize) -> Result<Option<Snapshot>> {
+        let segments = self.segment_ids().await?.len();
+        let needs_compaction = segments > MAX_SEGMENTS
+            || (dead >= COMPACT_MIN_DEAD && dead > live);
+
+        if !needs_compaction {
+            return Ok(None);
+        }
+
+        Ok(Some(self.compact().await?))
+    }
+
+    /// Rewrite the live state into a single segment and drop the old ones
+    pub async fn compact(&self) -> Result<Snapshot> {
+        let _lock = self.lock_exclusive()?;
+
+        // Re-read from disk: other processes may have appended since we loaded
+        let mut snapshot = self.replay().await?;
+        let mut conversations: Vec<&Conversation> = snapshot.conversations.values().collect();
+        conversations.sort_by_key(|c| c.timestamp);
+
+        let mut records = Vec::with_capacity(
+            conversations.len() + snapshot.embeddings.len() + snapshot.digests.len(),
+        );
+        for conversation in conversations {
+            records.push(LogRecord::Put { conversation: Box::new(conversation.clone()) });
+            if let Some(embedding) = snapshot.embeddings.get(&conversation.id) {
+                records.push(LogRecord::Embedding { id: conversation.id, embedding: embedding.clone() });
+            }
+        }
+        let mut digests: Vec<&SessionDigest> = snapshot.digests.values().collect();
+        digests.sort_by_key(|d| d.ended_at);
+        records.extend(digests.into_iter().map(|d| LogRecord::Digest { digest: Box::new(d.clone()) }));
+        self.rewrite(records.into_iter()).await?;
+
+        info!("Compacted memory store to {} conversations", snapshot.conversations.len());
+        snapshot.dead_records = 0;
+        Ok(snapshot)
+    }
+
+    /// Drop every record
+    pub async fn clear(&self) -> Result<()> {
+        let _lock = self.lock_exclusive()?;
+        self.rewrite(std::iter::empty()).await
+    }
+
+    /// Write `records` into a new segment (temp file + rename) and remove older segments
+    async fn rewrite(&self, records: impl Iterator<Item = LogRecord>) -> Result<()> {
+        let old_ids = self.segment_ids().await?;
+        let new_id = old_ids.last().copied().unwrap_or(0) + 1;
+
+        let mut content = String::new();
+        for record in records {
+            content.push_str(&self.encode(&record)?);
+            content.push('\n');
+        }
+
+        let tmp_path = self.dir.join(format!("{:08}.jsonl.tmp", new_id));
+        {
+            let mut file = tokio::fs::File::create(&tmp_path).await
+                .context("Failed to create compaction file")?;
+            file.write_all(content.as_bytes()).await
+                .context("Failed to write compaction file")?;
+            file.sync_all().await
+                .context("Failed to sync compaction file")?;
+        }
+        tokio::fs::rename(&tmp_path, self.segment_path(new_id)).await
+            .context("Failed to install compacted segment")?;
+
+        // The new segment sorts last, so replay stays correct even if we crash here
+        for id in old_ids {
+            if let Err(e) = tokio::fs::remove_file(self.segment_path(id)).await {
+                warn!("Failed to remove old segment {}: {}", id, e);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Replay every segment in order
+    async fn replay(&self) -> Result<Snapshot> {
+        let mut snapshot = Snapshot::default();
+
+        for id in self.segment_ids().await? {
+            let path = self.segment_path(id);
+            let content = tokio::fs::read_to_string(&path).await
+                .with_context(|| format!("Failed to read segment {}", path.display()))?;
+
+            for (n, line) in content.lines().enumerate() {
+                if line.trim().is_empty() {
+                    continue;
+                }
+                // A missing key is fatal; a line that fails to decrypt is torn or corrupt
+                crypto::check_readable(self.cipher.as_deref(), line)?;
+                let decoded = crypto::decode(self.cipher.as_deref(), line)
+                    .and_then(|line| serde_json::from_str::<LogRecord>(&line).map_err(Into::into));
+                match decoded {
+                    Ok(record) => snapshot.apply(record),
+                    Err(e) => {
+                        warn!("Skipping unreadable record {}:{}: {}", path.display(), n + 1, e);
+                        snapshot.dead_records += 1;
+                    }
+                }
+            }
+        }
+
+        debug!(
+            "Replayed {} conversations ({} dead records)",
+            snapshot.conversations.len(),
+            snapshot.dead_records
+        );
+        Ok(snapshot)
+    }
+
+    /// Import a pre-segment `conversations.json` and set it aside
+    async fn migrate_legacy(&self, legacy_path: &Path) -> Result<()> {
+        let _lock = self.lock_exclusive()?;
+
+        // Another process may have migrated while we waited for the lock
+        if !legacy_path.exists() || !self.segment_ids().await?.is_empty() {
+            return Ok(());
+        }
+
+        let content = tokio::fs::read_to_string(legacy_path).await
+            .context("Failed to read legacy storage file")?;
+
+        let conversations: Vec<Conversation> = if content.trim().is_empty() {
+            Vec::new()
+        } else {
+            serde_json::from_str(&content)
+                .context("Failed to parse legacy storage file")?
+        };
+
+        let records = conversations
+            .into_iter()
+            .map(|conversation| LogRecord::Put { conversation: Box::new(conversation) });
+        self.rewrite(records).await?;
+
+        // An encrypted store keeps no plaintext backup
+        if self.cipher.is_some() {
+            tokio::fs::remove_file(legacy_path).await
+                .context("Failed to remove legacy storage file")?;
+            info!("Migrated legacy storage file to encrypted segment store");
+            return Ok(());
+        }
+
+        let backup = legacy_backup_path(legacy_path);
+        tokio::fs::rename(legacy_path, &backup).await
+            .context("Failed to move legacy storage file aside")?;
+
+        info!("Migrated legacy storage file to segment store (backup at {:?})", backup);
+        Ok(())
+    }
+
+    /// Delete the plaintext backup a legacy migration left, returning whether there was one
+    pub async fn remove_legacy_backup(legacy_path: &Path) -> Result<bool> {
+        let backup = legacy_backup_path(legacy_path);
+        match tokio::fs::remove_file(&backup).await {
+            Ok(()) => {
+                info!("Removed plaintext legacy backup {:?}", backup);
+                Ok(true)
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(false),
+            Err(e) => Err(e).context("Failed to remove plaintext legacy backup"),
+        }
+    }
+
+    /// Ids of the keys records on disk are encrypted with
+    pub async fn key_ids_in_use(&self) -> Result<HashSet<String>> {
+        let _lock = self.lock_shared()?;
+        let mut ids = HashSet::new();
+
+        for id in self.segment_ids().await? {
+            let path = self.segment_path(id);
+            let content = tokio::fs::read_to_string(&path).await
+                .with_context(|| format!("Failed to read segment {}", path.display()))?;
+            ids.extend(content.lines().filter_map(crypto::key_id).map(str::to_string));
+        }
+
+        Ok(ids)
+    }
+
+    fn encode(&self, record: &LogRecord) -> Result<String> {
+        let line = serde_json::to_string(record)
+            .context("Failed to serialize log record")?;
+        Ok(crypto::encode(self.cipher.as_deref(), &line))
+    }
+
+    async fn ends_with_newline(&self, id: u64) -> Result<bool> {
+        use tokio::io::{AsyncReadExt, AsyncSeekExt, SeekFrom};
+
+        let mut file = tokio::fs::File::open(self.segment_path(id)).await?;
+        file.seek(SeekFrom::End(-1)).await?;
+        let mut last = [0u8; 1];
+        file.read_exact(&mut last).await?;
+        Ok(last[0] == b'\n')
+    }
+
+    /// Sorted ids of every segment on disk
+    async fn segment_ids(&self) -> Result<Vec<u64>> {
+        let mut ids = Vec::new();
+        let mut entries = tokio::fs::read_dir(&self.dir).await
+            .context("Failed to list segment directory")?;
+
+        while let Some(entry) = entries.next_entry().await? {
+            let name = entry.file_name();
+            let name = name.to_string_lossy();
+            if let Some(id) = name.strip_suffix(".jsonl").and_then(|s| s.parse().ok()) {
+                ids.push(id);
+            }
+        }
+
+        ids.sort_unstable();
+        Ok(ids)
+    }
+
+    fn segment_path(&self, id: u64) -> PathBuf {
+        self.dir.join(format!("{:08}.jsonl", id))
+    }
+
+    fn lock_file(&self) -> Result<File> {
+        File::create(self.dir.join(".lock")).context("Failed to open store lock file")
+    }
+
+    fn lock_shared(&self) -> Result<File> {
+        let file = self.lock_file()?;
+        file.lock_shared().context("Failed to lock memory store")?;
+        Ok(file)
+    }
+
+    fn lock_exclusive(&self) -> Result<File> {
+        let file = self.lock_file()?;
+        file.lock_exclusive().context("Failed to lock memory store")?;
+        Ok(file)
+    }
+}
+
+/// Where a legacy migration sets the old single-file store aside
+pub fn legacy_backup_path(legacy_path: &Path) -> PathBuf {
+    legacy_path.with_extension("json.migrated")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn conversation(user_message: &str) -> Conversation {
+        Conversation::sample("session-a", "2026-10-01T12:00:00Z", user_message, "answer")
+    }
+
+    fn put(conversation: &Conversation) -> LogRecord {
+        LogRecord::Put { conversation: Box::new(conversation.clone()) }
+    }
+
+    fn embedding(id: Uuid) -> LogRecord {
+        LogRecord::Embedding { id, embedding: Embedding { model: "test".to_string(), vector: vec![1.0, 0.0] } }
+    }
+
+    async fn open(dir: &Path) -> (SegmentStore, Snapshot) {
+        SegmentStore::open(dir.join("segments"), &dir.join("conversations.json"), None).await.unwrap()
+    }
+
+    #[tokio::test]
+    async fn replay_applies_records_in_order() {
+        let dir = tempfile::tempdir().unwrap();
+        let (store, snapshot) = open(dir.path()).await;
+        assert!(snapshot.conversations.is_empty());
+
+        let kept = conversation("kept");
+        let mut rewritten = kept.clone();
+        rewritten.assistant_response = "rewritten".to_string();
+        let deleted = conversation("deleted");
+        for record in [put(&kept), embedding(kept.id), put(&deleted), embedding(deleted.id), put(&rewritten)] {
+            store.append(&record).await.unwrap();
+        }
+        store.append(&LogRecord::Delete { id: deleted.id }).await.unwrap();
+
+        let (_, snapshot) = open(dir.path()).await;
+        assert_eq!(snapshot.conversations.len(), 1);
+        assert_eq!(snapshot.conversations[&kept.id].assistant_response, "rewritten");
+        // The rewrite dropped the embedding of the old text
+        assert!(snapshot.embeddings.is_empty());
+        // Old put and embedding of `kept`, then the delete and everything it removed
+        assert_eq!(snapshot.dead_records, 5);
+    }
+
+    #[tokio::test]
+    async fn torn_line_loses_only_that_record() {
+        let dir = tempfile::tempdir().unwrap();
+        let (store, _) = open(dir.path()).await;
+        let first = conversation("first");
+        store.append(&put(&first)).await.unwrap();
+
+        // A writer died halfway through its line
+        let segment = store.segment_path(1);
+        let mut content = std::fs::read_to_string(&segment).unwrap();
+        content.push_str(r#"{"op":"put","conversation":{"id""#);
+        std::fs::write(&segment, content).unwrap();
+
+        let second = conversation("second");
+        store.append(&put(&second)).await.unwrap();
+
+        let (_, snapshot) = open(dir.path()).await;
+        assert!(snapshot.conversations.contains_key(&first.id));
+        assert!(snapshot.conversations.contains_key(&second.id));
+        assert_eq!(snapshot.dead_records, 1);
+    }
+
+    #[tokio::test]
+    async fn compaction_keeps_live_state_in_one_segment() {
+        let dir = tempfile::tempdir().unwrap();
+        let (store, _) = open(dir.path()).await;
+        let kept = conversation("kept");
+        let deleted = conversation("deleted");
+        for record in [put(&kept), embedding(kept.id), put(&deleted), LogRecord::Delete { id: deleted.id }] {
+            store.append(&record).await.unwrap();
+        }
+        let digest = SessionDigest {
+            session_id: "session-a".to_string(),
+            started_at: kept.timestamp,
+            ended_at: kept.timestamp,
+            conversation_count: 1,
+            topics: vec!["kept".to_string()],
+            decisions: Vec::new(),
+            files_touched: Vec::new(),
+            todos: Vec::new(),
+            summarizer: "extractive".to_string(),
+            source_fingerprint: 0,
+            created_at: kept.timestamp,
+        };
+        store.append(&LogRecord::Digest { digest: Box::new(digest) }).await.unwrap();
+
+        let snapshot = store.compact().await.unwrap();
+        assert_eq!(snapshot.dead_records, 0);
+        assert_eq!(store.segment_ids().await.unwrap(), vec![2]);
+
+        let (_, reloaded) = open(dir.path()).await;
+        assert_eq!(reloaded.conversations.keys().collect::<Vec<_>>(), vec![&kept.id]);
+        assert!(reloaded.embeddings.contains_key(&kept.id));
+        assert!(reloaded.digests.contains_key("session-a"));
+        assert_eq!(reloaded.dead_records, 0);
+    }
+
+    #[tokio::test]
+    async fn compaction_waits_for_fragmentation() {
+        let dir = tempfile::tempdir().unwrap();
+        let (store, _) = open(dir.path()).await;
+        store.append(&put(&conversation("only"))).await.unwrap();
+
+        assert!(store.maybe_compact(1, COMPACT_MIN_DEAD - 1).await.unwrap().is_none());
+        assert!(store.maybe_compact(COMPACT_MIN_DEAD, COMPACT_MIN_DEAD).await.unwrap().is_none());
+        assert!(store.maybe_compact(1, COMPACT_MIN_DEAD).await.unwrap().is_some());
+    }
+
+    #[tokio::test]
+    async fn legacy_file_is_migrated_once() {
+        let dir = tempfile::tempdir().unwrap();
+        let legacy = dir.path().join("conversations.json");
+        let old = conversation("old");
+        std::fs::write(&legacy, serde_json::to_string(&vec![old.clone()]).unwrap()).unwrap();
+
+        let (store, snapshot) = open(dir.path()).await;
+        assert!(snapshot.conversations.contains_key(&old.id));
+        assert!(!legacy.exists());
+        assert!(legacy_backup_path(&legacy).exists());
+
+        // A legacy file showing up next to existing segments is left alone
+        std::fs::write(&legacy, "[]").unwrap();
+        store.append(&put(&conversation("new"))).await.unwrap();
+        let (_, snapshot) = open(dir.path()).await;
+        assert_eq!(snapshot.conversations.len(), 2);
+        assert!(legacy.exists());
+    }
+}
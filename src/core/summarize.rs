@@ -0,0 +1,505 @@
+use anyhow::Result;
+use async_trait::async_trait;
+use chrono::{Duration, Utc};
+use tracing::{debug, warn};
+
+use crate::core::hash::fnv1a;
+use crate::core::memory::{Memory, StoreOutcome};
+use crate::core::types::{Conversation, SessionDigest, SummarizationConfig};
+
+/// Sentences in an answer that record a choice
+const DECISION_CUES: &[&str] = &[
+    "decided", "decision", "going with", "settled on", "switched to", "switch to",
+    "chose", "opted", "instead of", "we'll use", "i'll use", "will use", "root cause",
+    "the fix is", "the fix was", "fixed by",
+];
+
+/// Sentences in either side that leave work open
+const TODO_CUES: &[&str] = &[
+    "todo", "fixme", "next step", "still need", "remaining", "pending", "follow up",
+    "follow-up", "not yet", "left to do", "[ ]",
+];
+
+/// Longest line kept in a digest section
+const MAX_LINE_CHARS: usize = 160;
+
+/// Most files listed in a digest
+const MAX_FILES: usize = 20;
+
+/// The written part of a digest
+#[derive(Debug, Default)]
+pub struct DigestNotes {
+    pub topics: Vec<String>,
+    pub decisions: Vec<String>,
+    pub todos: Vec<String>,
+}
+
+/// Something that can condense a session's exchanges into notes
+#[async_trait]
+pub trait Summarizer: Send + Sync {
+    /// Identifier stored on each digest; changing summarizer refreshes digests
+    fn name(&self) -> String;
+
+    /// Summarize `conversations`, oldest first
+    async fn summarize(&self, conversations: &[Conversation]) -> Result<DigestNotes>;
+}
+
+/// Offline summarizer that picks out lines by keyword cues
+///
+/// Topics pair each prompt with the first sentence of its answer; decisions and TODOs
+/// are sentences containing `DECISION_CUES` / `TODO_CUES`, outside code blocks.
+pub struct ExtractiveSummarizer {
+    max_items: usize,
+}
+
+impl ExtractiveSummarizer {
+    pub fn new(max_items: usize) -> Self {
+        Self { max_items }
+    }
+}
+
+#[async_trait]
+impl Summarizer for ExtractiveSummarizer {
+    fn name(&self) -> String {
+        "extractive".to_string()
+    }
+
+    async fn summarize(&self, conversations: &[Conversation]) -> Result<DigestNotes> {
+        let mut notes = DigestNotes::default();
+
+        for conversation in conversations {
+            let request = first_line(&conversation.user_message);
+            if request.is_empty() {
+                continue;
+            }
+            let answer = sentences(&conversation.assistant_response);
+            let topic = match answer.first() {
+                Some(first) => format!("{} → {}", request, first),
+                None => request,
+            };
+            push_unique(&mut notes.topics, clip(&topic), self.max_items);
+
+            // The first sentence already made it into the topic
+            for sentence in answer.into_iter().skip(1) {
+                if has_cue(&sentence, DECISION_CUES) {
+                    push_unique(&mut notes.decisions, clip(&sentence), self.max_items);
+                } else if has_cue(&sentence, TODO_CUES) {
+                    push_unique(&mut notes.todos, clip(&sentence), self.max_items);
+                }
+            }
+            for sentence in sentences(&conversation.user_message) {
+                if has_cue(&sentence, TODO_CUES) {
+                    push_unique(&mut notes.todos, clip(&sentence), self.max_items);
+                }
+            }
+        }
+
+        Ok(notes)
+    }
+}
+
+/// Summarizer backed by an Ollama-compatible `/api/generate` endpoint
+#[cfg(feature = "ml")]
+pub struct LlmSummarizer {
+    client: reqwest::Client,
+    base_url: String,
+    model: String,
+    max_items: usize,
+}
+
+#[cfg(feature = "ml")]
+impl LlmSummarizer {
+    pub fn new(base_url: &str, model: &str, max_items: usize) -> Result<Self> {
+        let client = reqwest::Client::builder()
+            .timeout(std::time::Duration::from_secs(60))
+            .build()?;
+
+        Ok(Self {
+            client,
+            base_url: base_url.trim_end_matches('/').to_string(),
+            model: model.to_string(),
+            max_items,
+        })
+    }
+}
+
+#[cfg(feature = "ml")]
+#[async_trait]
+impl Summarizer for LlmSummarizer {
+    fn name(&self) -> String {
+        format!("llm:{}", self.model)
+    }
+
+    async fn summarize(&self, conversations: &[Conversation]) -> Result<DigestNotes> {
+        use serde::Deserialize;
+
+        #[derive(Deserialize)]
+        struct GenerateResponse {
+            response: String,
+        }
+
+        #[derive(Deserialize, Default)]
+        struct Notes {
+            #[serde(default)]
+            topics: Vec<String>,
+            #[serde(default)]
+            decisions: Vec<String>,
+            #[serde(default)]
+            todos: Vec<String>,
+        }
+
+        let mut transcript = String::new();
+        for conversation in conversations {
+            transcript.push_str(&format!(
+                "USER: {}\nASSISTANT: {}\n\n",
+                conversation.user_message, conversation.assistant_response
+            ));
+        }
+        let transcript: String = transcript.chars().take(24_000).collect();
+        let prompt = format!(
+            "Summarize this coding session as JSON with three arrays of short strings: \
+             \"topics\" (what was worked on), \"decisions\" (choices made and why), \
+             \"todos\" (work left open). At most {} items each. Reply with JSON only.\n\n{}",
+            self.max_items, transcript
+        );
+
+        let response: GenerateResponse = self.client
+            .post(format!("{}/api/generate", self.base_url))
+            .json(&serde_json::json!({
+                "model": self.model,
+                "prompt": prompt,
+                "stream": false,
+                "format": "json",
+            }))
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+
+        let notes: Notes = serde_json::from_str(&response.response)?;
+        let keep = |lines: Vec<String>| -> Vec<String> {
+            let mut kept = Vec::new();
+            for line in lines {
+                push_unique(&mut kept, clip(line.trim()), self.max_items);
+            }
+            kept
+        };
+
+        Ok(DigestNotes {
+            topics: keep(notes.topics),
+            decisions: keep(notes.decisions),
+            todos: keep(notes.todos),
+        })
+    }
+}
+
+/// Summarizer selected by `SummarizationConfig.provider`
+pub fn summarizer(config: &SummarizationConfig) -> Result<Box<dyn Summarizer>> {
+    match config.provider.as_str() {
+        "llm" => llm_summarizer(config),
+        _ => Ok(Box::new(ExtractiveSummarizer::new(config.max_items))),
+    }
+}
+
+#[cfg(feature = "ml")]
+fn llm_summarizer(config: &SummarizationConfig) -> Result<Box<dyn Summarizer>> {
+    Ok(Box::new(LlmSummarizer::new(&config.base_url, &config.model, config.max_items)?))
+}
+
+#[cfg(not(feature = "ml"))]
+fn llm_summarizer(config: &SummarizationConfig) -> Result<Box<dyn Summarizer>> {
+    warn!("LLM summaries require the `ml` feature; using the extractive summarizer");
+    Ok(Box::new(ExtractiveSummarizer::new(config.max_items)))
+}
+
+/// Write digests for finished sessions that have none or whose digest is stale
+///
+/// A session is finished once it has been idle for `idle_minutes`, or with `force`
+/// regardless. `limit` caps how many sessions are summarized in one call, so a hook
+/// run stays short. Returns how many digests were written.
+pub async fn summarize_sessions(
+    memory: &Memory,
+    config: &SummarizationConfig,
+    force: bool,
+    limit: Option<usize>,
+) -> Result<usize> {
+    if !config.enabled {
+        return Ok(0);
+    }
+
+    let summarizer = summarizer(config)?;
+    let idle_cutoff = Utc::now() - Duration::minutes(config.idle_minutes as i64);
+    let mut written = 0;
+
+    for session in memory.sessions() {
+        if limit.is_some_and(|limit| written >= limit) {
+            break;
+        }
+        if !force && session.ended_at > idle_cutoff {
+            continue;
+        }
+        if digest_session(memory, summarizer.as_ref(), config, &session.id, force).await? {
+            written += 1;
+        }
+    }
+
+    debug!("Wrote {} session digests", written);
+    Ok(written)
+}
+
+/// Write the digest of one session right away, idle or not
+///
+/// Used when Claude Code reports the session ended. Returns whether a digest was
+/// written.
+pub async fn summarize_session(memory: &Memory, config: &SummarizationConfig, session_id: &str) -> Result<bool> {
+    if !config.enabled {
+        return Ok(false);
+    }
+    let summarizer = summarizer(config)?;
+    digest_session(memory, summarizer.as_ref(), config, session_id, false).await
+}
+
+/// Digest a session unless its digest is already current (or `force`)
+async fn digest_session(
+    memory: &Memory,
+    summarizer: &dyn Summarizer,
+    config: &SummarizationConfig,
+    session_id: &str,
+    force: bool,
+) -> Result<bool> {
+    let conversations = memory.session_conversations(session_id);
+    if conversations.is_empty() {
+        return Ok(false);
+    }
+
+    let fingerprint = session_fingerprint(&conversations);
+    let existing = memory.session_digest(session_id);
+    if !force && existing.as_ref().is_some_and(|d| {
+        d.source_fingerprint == fingerprint && d.summarizer == summarizer.name()
+    }) {
+        return Ok(false);
+    }
+
+    let digest = match build_digest(summarizer, session_id, &conversations, fingerprint).await {
+        Ok(digest) => digest,
+        Err(e) if summarizer.name() != "extractive" => {
+            warn!("Summarizer {} failed for session {}, using extractive: {}", summarizer.name(), session_id, e);
+            let fallback = ExtractiveSummarizer::new(config.max_items);
+            build_digest(&fallback, session_id, &conversations, fingerprint).await?
+        }
+        Err(e) => return Err(e),
+    };
+
+    Ok(memory.store_digest(&digest).await? != StoreOutcome::Unchanged)
+}
+
+async fn build_digest(
+    summarizer: &dyn Summarizer,
+    session_id: &str,
+    conversations: &[Conversation],
+    fingerprint: u64,
+) -> Result<SessionDigest> {
+    let notes = summarizer.summarize(conversations).await?;
+
+    let mut files_touched: Vec<String> = Vec::new();
+    for conversation in conversations {
+        for file in &conversation.metadata.files_touched {
+            if files_touched.len() < MAX_FILES && !files_touched.contains(file) {
+                files_touched.push(file.clone());
+            }
+        }
+    }
+
+    Ok(SessionDigest {
+        session_id: session_id.to_string(),
+        started_at: conversations.first().map(|c| c.timestamp).unwrap_or_else(Utc::now),
+        ended_at: conversations.last().map(|c| c.timestamp).unwrap_or_else(Utc::now),
+        conversation_count: conversations.len(),
+        topics: notes.topics,
+        decisions: notes.decisions,
+        files_touched,
+        todos: notes.todos,
+        summarizer: summarizer.name(),
+        source_fingerprint: fingerprint,
+        created_at: Utc::now(),
+    })
+}
+
+/// Render a digest as an injectable note
+pub fn render_digest(digest: &SessionDigest) -> String {
+    let mut text = format!(
+        "Session {} ({} exchanges):\n",
+        digest.ended_at.format("%Y-%m-%d"),
+        digest.conversation_count
+    );
+    let sections = [
+        ("Worked on", &digest.topics),
+        ("Decided", &digest.decisions),
+        ("Files", &digest.files_touched),
+        ("Open TODOs", &digest.todos),
+    ];
+    for (label, lines) in sections {
+        if !lines.is_empty() {
+            text.push_str(&format!("- {}: {}\n", label, lines.join("; ")));
+        }
+    }
+    text
+}
+
+fn session_fingerprint(conversations: &[Conversation]) -> u64 {
+    let mut content = String::new();
+    for conversation in conversations {
+        content.push_str(&format!(
+            "{}\u{0}{}\u{0}{}\u{0}",
+            conversation.id, conversation.user_message, conversation.assistant_response
+        ));
+    }
+    fnv1a(content.as_bytes())
+}
+
+/// Sentences of `text`, skipping fenced code blocks
+fn sentences(text: &str) -> Vec<String> {
+    let mut result = Vec::new();
+    let mut in_code = false;
+
+    for line in text.lines() {
+        let line = line.trim();
+        if line.starts_with("```") {
+            in_code = !in_code;
+            continue;
+        }
+        if in_code || line.is_empty() {
+            continue;
+        }
+        let line = line.trim_start_matches(['-', '*', '•', '#', ' ']);
+
+        let mut start = 0;
+        for (i, c) in line.char_indices() {
+            let next = line[i + c.len_utf8()..].chars().next();
+            if matches!(c, '.' | '!' | '?') && next.is_none_or(char::is_whitespace) {
+                push_sentence(&mut result, &line[start..i + c.len_utf8()]);
+                start = i + c.len_utf8();
+            }
+        }
+        push_sentence(&mut result, &line[start..]);
+    }
+
+    result
+}
+
+fn push_sentence(target: &mut Vec<String>, sentence: &str) {
+    let sentence = sentence.trim();
+    if sentence.chars().count() > 3 {
+        target.push(sentence.to_string());
+    }
+}
+
+fn has_cue(sentence: &str, cues: &[&str]) -> bool {
+    let lower = sentence.to_lowercase();
+    cues.iter().any(|cue| lower.contains(cue))
+}
+
+fn first_line(text: &str) -> String {
+    text.lines().map(str::trim).find(|l| !l.is_empty()).unwrap_or("").to_string()
+}
+
+fn clip(text: &str) -> String {
+    if text.chars().count() > MAX_LINE_CHARS {
+        format!("{}...", text.chars().take(MAX_LINE_CHARS).collect::<String>())
+    } else {
+        text.to_string()
+    }
+}
+
+fn push_unique(target: &mut Vec<String>, line: String, max: usize) {
+    if target.len() < max && !line.is_empty() && !target.contains(&line) {
+        target.push(line);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn exchange(user: &str, assistant: &str) -> Conversation {
+        Conversation::sample("session-a", "2026-09-01T10:00:00Z", user, assistant)
+    }
+
+    #[tokio::test]
+    async fn extractive_notes_pick_topics_decisions_and_todos() {
+        let conversations = [
+            exchange(
+                "Why do login tokens expire early?\nThey die after a minute.",
+                "The clock skew check is too strict. We decided to allow 30 seconds of leeway.\n\
+                 ```rust\n// decided: not a sentence to pick\n```\n\
+                 The integration test is still pending.",
+            ),
+            exchange("TODO: add a metric for rejected tokens.", "Sure. I'll use a counter."),
+        ];
+
+        let notes = ExtractiveSummarizer::new(6).summarize(&conversations).await.unwrap();
+        assert_eq!(
+            notes.topics,
+            [
+                "Why do login tokens expire early? → The clock skew check is too strict.",
+                "TODO: add a metric for rejected tokens. → Sure.",
+            ]
+        );
+        assert_eq!(notes.decisions, ["We decided to allow 30 seconds of leeway.", "I'll use a counter."]);
+        assert_eq!(notes.todos, ["The integration test is still pending.", "TODO: add a metric for rejected tokens."]);
+    }
+
+    #[tokio::test]
+    async fn extractive_notes_are_capped_and_deduplicated() {
+        let conversations: Vec<Conversation> = (0..5)
+            .map(|i| exchange(&format!("Question {}", i % 2), "Answer. We decided to keep it."))
+            .collect();
+
+        let notes = ExtractiveSummarizer::new(1).summarize(&conversations).await.unwrap();
+        assert_eq!(notes.topics, ["Question 0 → Answer."]);
+        assert_eq!(notes.decisions, ["We decided to keep it."]);
+        assert!(notes.todos.is_empty());
+
+        let notes = ExtractiveSummarizer::new(6).summarize(&conversations).await.unwrap();
+        assert_eq!(notes.topics.len(), 2);
+        assert_eq!(notes.decisions.len(), 1);
+    }
+
+    #[test]
+    fn fingerprint_follows_content_and_order() {
+        let first = exchange("How are tokens checked?", "By signature.");
+        let second = exchange("And expiry?", "Against the clock.");
+        let fingerprint = session_fingerprint(&[first.clone(), second.clone()]);
+
+        assert_eq!(fingerprint, session_fingerprint(&[first.clone(), second.clone()]));
+        assert_ne!(fingerprint, session_fingerprint(&[second.clone(), first.clone()]));
+        assert_ne!(fingerprint, session_fingerprint(std::slice::from_ref(&first)));
+
+        let mut edited = second;
+        edited.assistant_response.push_str(" And leeway.");
+        assert_ne!(fingerprint, session_fingerprint(&[first, edited]));
+    }
+
+    #[test]
+    fn rendered_digest_skips_empty_sections() {
+        let digest = SessionDigest {
+            session_id: "session-a".to_string(),
+            started_at: "2026-09-01T10:00:00Z".parse().unwrap(),
+            ended_at: "2026-09-01T11:00:00Z".parse().unwrap(),
+            conversation_count: 2,
+            topics: vec!["auth".to_string(), "tokens".to_string()],
+            decisions: Vec::new(),
+            files_touched: vec!["src/auth.rs".to_string()],
+            todos: Vec::new(),
+            summarizer: "extractive".to_string(),
+            source_fingerprint: 0,
+            created_at: Utc::now(),
+        };
+
+        assert_eq!(
+            render_digest(&digest),
+            "Session 2026-09-01 (2 exchanges):\n- Worked on: auth; tokens\n- Files: src/auth.rs\n"
+        );
+    }
+}
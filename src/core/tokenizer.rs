@@ -0,0 +1,161 @@
+use std::sync::OnceLock;
+use tiktoken_rs::CoreBPE;
+use tracing::warn;
+
+/// Shared cl100k BPE encoder; building it parses the bundled vocab, so do it once
+fn encoder() -> Option<&'static CoreBPE> {
+    static ENCODER: OnceLock<Option<CoreBPE>> = OnceLock::new();
+    ENCODER
+        .get_or_init(|| match tiktoken_rs::cl100k_base() {
+            Ok(bpe) => Some(bpe),
+            Err(e) => {
+                warn!("Failed to load BPE vocabulary, estimating tokens: {}", e);
+                None
+            }
+        })
+        .as_ref()
+}
+
+/// Count BPE tokens in `text`
+pub fn count_tokens(text: &str) -> usize {
+    if text.is_empty() {
+        return 0;
+    }
+    match encoder() {
+        Some(bpe) => bpe.encode_with_special_tokens(text).len(),
+        None => estimate_tokens(text),
+    }
+}
+
+/// Rough token count, 4 bytes per token, that needs no vocabulary
+///
+/// Loading the BPE vocabulary costs more than a hook run can spare, so capture
+/// stores this and exact counts are left to context building.
+pub fn estimate_tokens(text: &str) -> usize {
+    text.len().div_ceil(4)
+}
+
+/// Cut `text` to at most `max_tokens`, preferring the last sentence boundary that fits
+///
+/// Falls back to a word boundary (with an ellipsis) when not even the first sentence
+/// fits. Returns the text unchanged if it is already within budget.
+pub fn truncate_to_tokens(text: &str, max_tokens: usize) -> String {
+    if max_tokens == 0 {
+        return String::new();
+    }
+
+    // No token is longer than a few dozen bytes, so anything past this can't fit
+    let window = max_tokens.saturating_mul(32);
+    let (text, clipped) = match text.char_indices().nth(window) {
+        Some((i, _)) => (&text[..i], true),
+        None => (text, false),
+    };
+    if !clipped && count_tokens(text) <= max_tokens {
+        return text.to_string();
+    }
+
+    // Longest prefix ending at a sentence boundary that still fits. Token counts
+    // grow with the prefix, so binary search instead of counting every prefix.
+    let ends = sentence_ends(text);
+    let fitting = ends.partition_point(|&end| count_tokens(&text[..end]) <= max_tokens);
+    if fitting > 0 {
+        return text[..ends[fitting - 1]].trim_end().to_string();
+    }
+
+    // No whole sentence fits: cut on a word boundary and mark the elision
+    let budget = max_tokens.saturating_sub(1);
+    let leading = text.len() - text.trim_start().len();
+    let breaks: Vec<usize> = text
+        .char_indices()
+        .filter(|(i, c)| *i > leading && c.is_whitespace())
+        .map(|(i, _)| i)
+        .collect();
+    let fitting = breaks.partition_point(|&i| count_tokens(&text[..i]) <= budget);
+    if fitting == 0 {
+        return String::new();
+    }
+    format!("{}…", text[..breaks[fitting - 1]].trim_end())
+}
+
+/// Byte offsets just past each sentence terminator in `text`
+fn sentence_ends(text: &str) -> Vec<usize> {
+    let mut ends = Vec::new();
+    let mut chars = text.char_indices().peekable();
+
+    while let Some((i, c)) = chars.next() {
+        let end = i + c.len_utf8();
+        let at_boundary = match c {
+            '\n' => true,
+            '.' | '!' | '?' => !matches!(chars.peek(), Some((_, next)) if !next.is_whitespace()),
+            _ => false,
+        };
+        if at_boundary {
+            ends.push(end);
+        }
+    }
+
+    ends
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn counts_bpe_tokens() {
+        assert_eq!(count_tokens(""), 0);
+        assert_eq!(count_tokens("hello world"), 2);
+        assert!(count_tokens(&"word ".repeat(100)) >= 100);
+    }
+
+    #[test]
+    fn estimate_rounds_up() {
+        assert_eq!(estimate_tokens(""), 0);
+        assert_eq!(estimate_tokens("hello"), 2);
+        assert_eq!(estimate_tokens("abcdefgh"), 2);
+    }
+
+    #[test]
+    fn text_within_budget_is_unchanged() {
+        let text = "Short enough. Really.";
+        assert_eq!(truncate_to_tokens(text, 50), text);
+        assert_eq!(truncate_to_tokens(text, 0), "");
+    }
+
+    #[test]
+    fn cuts_at_the_last_sentence_that_fits() {
+        let text = "The first sentence is here. The second one follows it! Is there a third? Yes.";
+        for max in 1..count_tokens(text) {
+            let cut = truncate_to_tokens(text, max);
+            assert!(count_tokens(&cut) <= max, "{} tokens allowed: {:?}", max, cut);
+            assert!(text.starts_with(cut.trim_end_matches('…')), "{:?}", cut);
+        }
+        assert_eq!(truncate_to_tokens(text, 12), "The first sentence is here. The second one follows it!");
+    }
+
+    #[test]
+    fn falls_back_to_a_word_boundary_with_an_ellipsis() {
+        let text = "one two three four five six seven eight nine ten eleven twelve";
+        let cut = truncate_to_tokens(text, 5);
+        assert_eq!(cut, "one two three four…");
+        assert_eq!(truncate_to_tokens("  indented words go here", 4), "  indented…");
+        assert_eq!(truncate_to_tokens("unbreakable", 1), "");
+    }
+
+    #[test]
+    fn long_text_is_cut_quickly() {
+        let text = "A sentence of several words that keeps going. ".repeat(20_000);
+        let started = std::time::Instant::now();
+        let cut = truncate_to_tokens(&text, 500);
+        assert!(count_tokens(&cut) <= 500);
+        assert!(cut.ends_with("going."));
+        assert!(started.elapsed() < std::time::Duration::from_secs(2), "{:?}", started.elapsed());
+    }
+
+    #[test]
+    fn sentence_ends_need_a_terminator_and_whitespace() {
+        let text = "Call foo.bar() now. Done!\nNext";
+        let ends: Vec<&str> = sentence_ends(text).into_iter().map(|end| &text[..end]).collect();
+        assert_eq!(ends, ["Call foo.bar() now.", "Call foo.bar() now. Done!", "Call foo.bar() now. Done!\n"]);
+    }
+}
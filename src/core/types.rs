@@ -2,8 +2,15 @@ use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
+use crate::core::passage::Passage;
+use crate::core::query::Role;
+use crate::core::ranking::Signals;
+
+/// Namespace for deterministic conversation ids (UUID v5)
+const CONVERSATION_ID_NAMESPACE: Uuid = Uuid::from_u128(0x5d3c_9a4e_7b21_4f08_9e6d_2a1c_0b8f_4e73);
+
 /// A conversation between user and assistant
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Conversation {
     pub id: Uuid,
     pub timestamp: DateTime<Utc>,
@@ -12,14 +19,105 @@ pub struct Conversation {
     pub metadata: ConversationMetadata,
 }
 
+impl Conversation {
+    /// Deterministic id for this exchange
+    ///
+    /// Derived from the session id plus the transcript uuid of the prompt when both
+    /// are known, otherwise from the session id and the exchange text, so parsing the
+    /// same transcript twice yields the same ids.
+    pub fn stable_id(&self) -> Uuid {
+        let session = self.metadata.session_id.as_deref().unwrap_or("");
+        let name = match &self.metadata.source_uuid {
+            Some(source) if !session.is_empty() => format!("message:{}:{}", session, source),
+            _ => format!(
+                "content:{}:{}\u{0}{}",
+                session, self.user_message, self.assistant_response
+            ),
+        };
+        Uuid::new_v5(&CONVERSATION_ID_NAMESPACE, name.as_bytes())
+    }
+
+    /// Exchange from `session` at `timestamp` (RFC 3339), with its stable id
+    #[cfg(test)]
+    pub fn sample(session: &str, timestamp: &str, user_message: &str, assistant_response: &str) -> Self {
+        let mut conversation = Conversation {
+            id: Uuid::nil(),
+            timestamp: timestamp.parse().expect("RFC 3339 timestamp"),
+            user_message: user_message.to_string(),
+            assistant_response: assistant_response.to_string(),
+            metadata: ConversationMetadata {
+                session_id: Some(session.to_string()),
+                ..Default::default()
+            },
+        };
+        conversation.id = conversation.stable_id();
+        conversation
+    }
+}
+
 /// Metadata associated with a conversation
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
 pub struct ConversationMetadata {
     pub session_id: Option<String>,
     pub project_path: Option<String>,
     pub tags: Vec<String>,
     pub token_count: usize,
     pub embedding_model: Option<String>,
+    /// Transcript `uuid` of the user prompt this exchange starts at
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub source_uuid: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub git_branch: Option<String>,
+    /// Tools the assistant ran during the exchange, in first-use order
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tools_used: Vec<String>,
+    /// Paths read or modified by those tools
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub files_touched: Vec<String>,
+    /// Other paths named in the messages, code or shell commands
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub files_mentioned: Vec<String>,
+    /// Languages of the fenced code blocks
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub languages: Vec<String>,
+    /// Identifiers defined, called or named in code, prose or tool edits
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub symbols: Vec<String>,
+    /// Exempt from retention pruning while `retention.keep_pinned` is set
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub pinned: bool,
+}
+
+/// A Claude Code session, aggregated from the conversations captured in it
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Session {
+    pub id: String,
+    pub started_at: DateTime<Utc>,
+    pub ended_at: DateTime<Utc>,
+    pub turn_count: usize,
+    pub token_total: usize,
+    /// Branch and working directory of the latest turn that recorded them
+    pub git_branch: Option<String>,
+    pub cwd: Option<String>,
+}
+
+/// Compact notes on a finished session, injected instead of its raw exchanges
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SessionDigest {
+    pub session_id: String,
+    pub started_at: DateTime<Utc>,
+    pub ended_at: DateTime<Utc>,
+    pub conversation_count: usize,
+    /// What was asked and the gist of the answer, one line per exchange
+    pub topics: Vec<String>,
+    pub decisions: Vec<String>,
+    pub files_touched: Vec<String>,
+    pub todos: Vec<String>,
+    /// Summarizer that wrote the notes ("extractive" or "llm:<model>")
+    pub summarizer: String,
+    /// Fingerprint of the conversations summarized, to notice when the session changes
+    pub source_fingerprint: u64,
+    pub created_at: DateTime<Utc>,
 }
 
 /// Claude Code transcript structure
@@ -45,6 +143,31 @@ pub struct SearchResult {
     pub conversation: Conversation,
     pub score: f32,
     pub snippet: String,
+    /// Where the query matched, in message order
+    pub matches: Vec<TextMatch>,
+    /// How each ranking signal contributed to `score`
+    pub signals: Signals,
+    /// The part of the exchange that matched, when it could be pinned down
+    pub passage: Option<Passage>,
+}
+
+/// Byte range of a search hit within one side of a conversation
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct TextMatch {
+    pub role: Role,
+    pub start: usize,
+    pub end: usize,
+}
+
+impl TextMatch {
+    /// The matched text
+    pub fn text<'a>(&self, conversation: &'a Conversation) -> &'a str {
+        let message = match self.role {
+            Role::User => &conversation.user_message,
+            Role::Assistant => &conversation.assistant_response,
+        };
+        &message[self.start..self.end]
+    }
 }
 
 /// Configuration for the off-context system
@@ -54,6 +177,16 @@ pub struct Config {
     pub embeddings: EmbeddingsConfig,
     pub context: ContextConfig,
     pub hooks: HooksConfig,
+    #[serde(default)]
+    pub redaction: RedactionConfig,
+    #[serde(default)]
+    pub encryption: EncryptionConfig,
+    #[serde(default)]
+    pub retention: RetentionConfig,
+    #[serde(default)]
+    pub summarization: SummarizationConfig,
+    #[serde(default)]
+    pub ranking: RankingConfig,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -67,6 +200,13 @@ pub struct EmbeddingsConfig {
     pub provider: String,
     pub model: String,
     pub dimension: usize,
+    /// Base URL of an Ollama-compatible embeddings server
+    #[serde(default = "default_embeddings_base_url")]
+    pub base_url: String,
+}
+
+pub fn default_embeddings_base_url() -> String {
+    "http://localhost:11434".to_string()
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -80,4 +220,256 @@ pub struct ContextConfig {
 pub struct HooksConfig {
     pub enabled: bool,
     pub auto_inject: bool,
-}
\ No newline at end of file
+    /// How UserPromptSubmit injections are emitted: "additional_context" (structured
+    /// hook JSON) or "legacy" (context glued onto the prompt text)
+    #[serde(default = "default_prompt_output")]
+    pub prompt_output: String,
+    /// Inject again on every Nth prompt of a session; unset means once per session
+    #[serde(default)]
+    pub reinject_after_turns: Option<u32>,
+    /// Inject again when a prompt shares almost no terms with the one that last
+    /// triggered injection
+    #[serde(default)]
+    pub reinject_on_topic_change: bool,
+    /// Days without activity after which a session's injection state is dropped
+    #[serde(default = "default_session_state_ttl_days")]
+    pub session_state_ttl_days: u32,
+}
+
+pub fn default_prompt_output() -> String {
+    "additional_context".to_string()
+}
+
+pub fn default_session_state_ttl_days() -> u32 {
+    30
+}
+
+/// Secret/PII scrubbing applied to conversations before they are stored
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RedactionConfig {
+    #[serde(default = "default_redaction_enabled")]
+    pub enabled: bool,
+    /// Also redact long random-looking tokens that match no known format
+    #[serde(default = "default_high_entropy")]
+    pub high_entropy: bool,
+    /// Built-in detectors to turn off (e.g. "email")
+    #[serde(default)]
+    pub disabled_detectors: Vec<String>,
+    /// Extra patterns; a capture group, if present, limits what gets replaced
+    #[serde(default)]
+    pub rules: Vec<RedactionRule>,
+}
+
+pub fn default_redaction_enabled() -> bool {
+    true
+}
+
+pub fn default_high_entropy() -> bool {
+    true
+}
+
+impl Default for RedactionConfig {
+    fn default() -> Self {
+        Self {
+            enabled: default_redaction_enabled(),
+            high_entropy: default_high_entropy(),
+            disabled_detectors: Vec::new(),
+            rules: Vec::new(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RedactionRule {
+    pub name: String,
+    pub pattern: String,
+}
+
+/// Encryption at rest for the memory store
+///
+/// Turning it on takes effect the next time the store is opened; turning it off
+/// again goes through `off-context encryption disable`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EncryptionConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    /// "key_file" (random key under ~/.off-context/keys) or "passphrase"
+    /// (derived from OFF_CONTEXT_PASSPHRASE)
+    #[serde(default = "default_key_source")]
+    pub key_source: String,
+}
+
+pub fn default_key_source() -> String {
+    "key_file".to_string()
+}
+
+impl Default for EncryptionConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            key_source: default_key_source(),
+        }
+    }
+}
+
+/// Limits old memory is pruned to; every limit is off unless set
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RetentionConfig {
+    /// Drop conversations older than this many days
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_age_days: Option<u32>,
+    /// Keep at most this many conversations, dropping the oldest first
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_conversations: Option<usize>,
+    /// Keep the live records under this many megabytes, dropping the oldest first
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_store_mb: Option<u64>,
+    /// Never prune pinned conversations
+    #[serde(default = "default_keep_pinned")]
+    pub keep_pinned: bool,
+}
+
+pub fn default_keep_pinned() -> bool {
+    true
+}
+
+impl Default for RetentionConfig {
+    fn default() -> Self {
+        Self {
+            max_age_days: None,
+            max_conversations: None,
+            max_store_mb: None,
+            keep_pinned: default_keep_pinned(),
+        }
+    }
+}
+
+/// Digests of finished sessions
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SummarizationConfig {
+    #[serde(default = "default_summarization_enabled")]
+    pub enabled: bool,
+    /// "extractive" (offline) or "llm" (Ollama-compatible `/api/generate`, needs the
+    /// `ml` feature)
+    #[serde(default = "default_summary_provider")]
+    pub provider: String,
+    #[serde(default = "default_embeddings_base_url")]
+    pub base_url: String,
+    #[serde(default = "default_summary_model")]
+    pub model: String,
+    /// A session counts as finished once it has been idle this long
+    #[serde(default = "default_idle_minutes")]
+    pub idle_minutes: u64,
+    /// Cap on lines per digest section
+    #[serde(default = "default_max_items")]
+    pub max_items: usize,
+}
+
+pub fn default_summarization_enabled() -> bool {
+    true
+}
+
+pub fn default_summary_provider() -> String {
+    "extractive".to_string()
+}
+
+pub fn default_summary_model() -> String {
+    "llama3.2".to_string()
+}
+
+pub fn default_idle_minutes() -> u64 {
+    30
+}
+
+pub fn default_max_items() -> usize {
+    6
+}
+
+impl Default for SummarizationConfig {
+    fn default() -> Self {
+        Self {
+            enabled: default_summarization_enabled(),
+            provider: default_summary_provider(),
+            base_url: default_embeddings_base_url(),
+            model: default_summary_model(),
+            idle_minutes: default_idle_minutes(),
+            max_items: default_max_items(),
+        }
+    }
+}
+
+/// How search signals are fused into one score (reciprocal rank fusion)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RankingConfig {
+    /// Damping constant; larger values shrink the gap between neighbouring ranks
+    #[serde(default = "default_rrf_k")]
+    pub rrf_k: f64,
+    #[serde(default = "default_signal_weight")]
+    pub keyword_weight: f64,
+    #[serde(default = "default_signal_weight")]
+    pub semantic_weight: f64,
+    /// Boost for a conversation captured just now, relative to a first place in one
+    /// signal; 0 turns recency off
+    #[serde(default = "default_recency_weight")]
+    pub recency_weight: f64,
+    /// Age at which the recency boost has halved
+    #[serde(default = "default_recency_half_life_days")]
+    pub recency_half_life_days: f64,
+}
+
+pub fn default_rrf_k() -> f64 {
+    60.0
+}
+
+pub fn default_signal_weight() -> f64 {
+    1.0
+}
+
+pub fn default_recency_weight() -> f64 {
+    0.1
+}
+
+pub fn default_recency_half_life_days() -> f64 {
+    30.0
+}
+
+impl Default for RankingConfig {
+    fn default() -> Self {
+        Self {
+            rrf_k: default_rrf_k(),
+            keyword_weight: default_signal_weight(),
+            semantic_weight: default_signal_weight(),
+            recency_weight: default_recency_weight(),
+            recency_half_life_days: default_recency_half_life_days(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn partial_summarization_section_keeps_defaults() {
+        let config: SummarizationConfig = toml::from_str("idle_minutes = 5").unwrap();
+        assert!(config.enabled);
+        assert_eq!(config.provider, "extractive");
+        assert_eq!(config.idle_minutes, 5);
+        assert_eq!(config.max_items, default_max_items());
+    }
+
+    #[test]
+    fn partial_redaction_section_keeps_defaults() {
+        let config: RedactionConfig = toml::from_str("disabled_detectors = [\"email\"]").unwrap();
+        assert!(config.enabled);
+        assert!(config.high_entropy);
+        assert_eq!(config.disabled_detectors, ["email"]);
+    }
+
+    #[test]
+    fn partial_encryption_section_keeps_defaults() {
+        let config: EncryptionConfig = toml::from_str("key_source = \"passphrase\"").unwrap();
+        assert!(!config.enabled);
+        assert_eq!(config.key_source, "passphrase");
+    }
+}
@@ -0,0 +1,76 @@
+use anyhow::{Context, Result};
+use tracing::debug;
+
+use crate::commands::{hook, inject};
+use crate::core::{
+    config::{is_in_project, load_project_config},
+    memory::Memory,
+    summarize,
+};
+use crate::hooks::payload::{PreCompact, SessionEnd, SessionStart, Stop, SubagentStop, UserPromptSubmit};
+
+/// Inject memory for a prompt
+pub async fn user_prompt_submit(
+    input: &UserPromptSubmit,
+    raw: &serde_json::Value,
+    source: inject::ContextSource,
+) -> Result<Option<String>> {
+    Ok(inject::respond_to_prompt(input, raw, source).await)
+}
+
+/// Inject the previous session before the first prompt
+pub async fn session_start(input: &SessionStart) -> Result<Option<String>> {
+    debug!("Session {} started ({})", input.common.session_id, input.source);
+    inject::respond_to_session_start(input).await
+}
+
+/// Capture the exchange that just finished
+pub async fn stop(input: &Stop) -> Result<Option<String>> {
+    capture(input.common.transcript_path.as_deref()).await?;
+    Ok(None)
+}
+
+/// Capture a subagent's work as soon as it reports back
+pub async fn subagent_stop(input: &SubagentStop) -> Result<Option<String>> {
+    debug!("Subagent {:?} stopped", input.agent_id);
+    capture(input.agent_transcript_path.as_deref()).await?;
+    capture(input.common.transcript_path.as_deref()).await?;
+    Ok(None)
+}
+
+/// Store everything said so far before compaction summarizes it away
+pub async fn pre_compact(input: &PreCompact) -> Result<Option<String>> {
+    debug!("Capturing session {} before {} compaction", input.common.session_id, input.trigger);
+    capture(input.common.transcript_path.as_deref()).await?;
+    Ok(None)
+}
+
+/// Capture the end of the session and write its digest without waiting for it to
+/// go idle
+pub async fn session_end(input: &SessionEnd) -> Result<Option<String>> {
+    debug!("Session {} ended ({})", input.common.session_id, input.reason);
+    capture(input.common.transcript_path.as_deref()).await?;
+
+    let session_id = match input.common.session_id() {
+        Some(session_id) if is_in_project() => session_id,
+        _ => return Ok(None),
+    };
+    let config = load_project_config().await.context("Failed to load configuration")?;
+    let memory = Memory::new(&config).await
+        .context("Failed to initialize memory store")?;
+    if summarize::summarize_session(&memory, &config.summarization, session_id).await? {
+        debug!("Wrote digest for session {}", session_id);
+    }
+    Ok(None)
+}
+
+async fn capture(transcript_path: Option<&str>) -> Result<()> {
+    match transcript_path {
+        Some(path) if std::path::Path::new(path).is_file() => hook::handle_hook(path).await,
+        Some(path) => {
+            debug!("Transcript {} does not exist, nothing to capture", path);
+            Ok(())
+        }
+        None => Ok(()),
+    }
+}
@@ -0,0 +1,158 @@
+mod handlers;
+pub mod payload;
+pub mod settings;
+
+use anyhow::{bail, Context, Result};
+use futures::FutureExt;
+use std::io::{self, Read};
+use std::panic::AssertUnwindSafe;
+use std::time::Instant;
+use tracing::{debug, info, warn};
+
+use crate::commands::inject::ContextSource;
+use crate::core::config::{is_in_project, load_project_config};
+
+pub use payload::{HookEvent, HookPayload};
+pub use settings::SettingsScope;
+
+// `Dispatcher::run` fails open by catching handler panics, which needs unwinding
+#[cfg(panic = "abort")]
+compile_error!("hooks must be built with panic = \"unwind\" so a panicking handler still passes the prompt through");
+
+/// Events `init` registers with Claude Code
+pub const REGISTERED_EVENTS: [HookEvent; 6] = [
+    HookEvent::UserPromptSubmit,
+    HookEvent::Stop,
+    HookEvent::SessionStart,
+    HookEvent::PreCompact,
+    HookEvent::SubagentStop,
+    HookEvent::SessionEnd,
+];
+
+/// Command Claude Code runs for `event`: this binary, reading the payload on stdin
+///
+/// Shared settings name the binary on `PATH`, since this machine's install path
+/// means nothing to the rest of the team.
+pub fn hook_command(event: HookEvent, scope: SettingsScope) -> String {
+    let binary = match std::env::current_exe() {
+        Ok(path) if !scope.is_shared() => path.to_string_lossy().into_owned(),
+        _ => "off-context".to_string(),
+    };
+    let binary = if binary.contains(char::is_whitespace) {
+        format!("\"{}\"", binary)
+    } else {
+        binary
+    };
+    format!("{} hook-event {}", binary, event.arg())
+}
+
+/// Handles one hook invocation for the event it was registered for
+///
+/// The payload is read from stdin, decoded into a typed `HookPayload` and routed
+/// to the handler for its event.
+pub struct Dispatcher {
+    expected: HookEvent,
+    prompt_source: ContextSource,
+}
+
+impl Dispatcher {
+    pub fn new(expected: HookEvent) -> Self {
+        Self {
+            expected,
+            prompt_source: ContextSource::Relevant,
+        }
+    }
+
+    /// Memory injected on UserPromptSubmit (relevant exchanges by default)
+    pub fn prompt_source(mut self, source: ContextSource) -> Self {
+        self.prompt_source = source;
+        self
+    }
+
+    /// Read the payload from stdin, handle it and print the response
+    ///
+    /// Fails open: unreadable input, a bad payload, a failing or even panicking
+    /// handler are logged and the hook still exits successfully with pass-through
+    /// output, so the user's prompt always reaches Claude Code.
+    pub async fn run(&self) {
+        let mut raw = String::new();
+        if let Err(e) = io::stdin().read_to_string(&mut raw) {
+            warn!(event = %self.expected, error = %e, "Failed to read hook input");
+            return;
+        }
+
+        let started = Instant::now();
+        let output = match AssertUnwindSafe(self.handle(&raw)).catch_unwind().await {
+            Ok(Ok(output)) => {
+                info!(
+                    event = %self.expected,
+                    elapsed_ms = started.elapsed().as_millis() as u64,
+                    responded = output.is_some(),
+                    "Hook handled"
+                );
+                output
+            }
+            Ok(Err(e)) => {
+                warn!(event = %self.expected, error = format!("{:#}", e), "Hook failed, passing through");
+                self.passthrough(&raw).await
+            }
+            Err(_) => {
+                warn!(event = %self.expected, "Hook panicked, passing through");
+                self.passthrough(&raw).await
+            }
+        };
+
+        if let Some(output) = output {
+            println!("{}", output);
+        }
+    }
+
+    /// Decode and dispatch a raw payload, returning what should be printed
+    pub async fn handle(&self, raw: &str) -> Result<Option<String>> {
+        let value: serde_json::Value = serde_json::from_str(raw).context("Hook input is not JSON")?;
+        let payload = HookPayload::parse(&value, self.expected)?;
+        if payload.event() != self.expected {
+            bail!("Expected a {} payload, got {}", self.expected, payload.event());
+        }
+        info!(
+            event = %payload.event(),
+            session_id = payload.common().session_id().unwrap_or(""),
+            "Dispatching hook"
+        );
+
+        match &payload {
+            HookPayload::UserPromptSubmit(input) => {
+                handlers::user_prompt_submit(input, &value, self.prompt_source).await
+            }
+            HookPayload::SessionStart(input) => handlers::session_start(input).await,
+            HookPayload::Stop(input) => handlers::stop(input).await,
+            HookPayload::SubagentStop(input) => handlers::subagent_stop(input).await,
+            HookPayload::PreCompact(input) => handlers::pre_compact(input).await,
+            HookPayload::SessionEnd(input) => handlers::session_end(input).await,
+            _ => {
+                debug!("No handler for {} hooks", payload.event());
+                Ok(None)
+            }
+        }
+    }
+
+    /// Output that leaves the prompt untouched
+    ///
+    /// Nothing for structured output. In legacy mode the hook's stdout replaces
+    /// the prompt, so the original is echoed back when it can be recovered.
+    async fn passthrough(&self, raw: &str) -> Option<String> {
+        if self.expected != HookEvent::UserPromptSubmit || !is_in_project() {
+            return None;
+        }
+        let legacy = load_project_config().await.is_ok_and(|c| c.hooks.prompt_output == "legacy");
+        if !legacy {
+            return None;
+        }
+
+        let value: serde_json::Value = serde_json::from_str(raw).ok()?;
+        match self.prompt_source {
+            ContextSource::Relevant => value.get("prompt")?.as_str().map(str::to_string),
+            ContextSource::PreviousSession => Some(value.to_string()),
+        }
+    }
+}
@@ -0,0 +1,319 @@
+use anyhow::{bail, Context, Result};
+use serde::Deserialize;
+use std::fmt;
+use std::str::FromStr;
+
+/// Claude Code hook events
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum HookEvent {
+    PreToolUse,
+    PostToolUse,
+    Notification,
+    UserPromptSubmit,
+    Stop,
+    SubagentStop,
+    PreCompact,
+    SessionStart,
+    SessionEnd,
+}
+
+impl HookEvent {
+    pub const ALL: [HookEvent; 9] = [
+        HookEvent::PreToolUse,
+        HookEvent::PostToolUse,
+        HookEvent::Notification,
+        HookEvent::UserPromptSubmit,
+        HookEvent::Stop,
+        HookEvent::SubagentStop,
+        HookEvent::PreCompact,
+        HookEvent::SessionStart,
+        HookEvent::SessionEnd,
+    ];
+
+    /// Name used in `hook_event_name` and in Claude Code settings
+    pub fn name(self) -> &'static str {
+        match self {
+            HookEvent::PreToolUse => "PreToolUse",
+            HookEvent::PostToolUse => "PostToolUse",
+            HookEvent::Notification => "Notification",
+            HookEvent::UserPromptSubmit => "UserPromptSubmit",
+            HookEvent::Stop => "Stop",
+            HookEvent::SubagentStop => "SubagentStop",
+            HookEvent::PreCompact => "PreCompact",
+            HookEvent::SessionStart => "SessionStart",
+            HookEvent::SessionEnd => "SessionEnd",
+        }
+    }
+
+    /// Kebab-case name taken by `off-context hook-event`, e.g. `session-start`
+    pub fn arg(self) -> String {
+        let mut arg = String::new();
+        for (i, c) in self.name().chars().enumerate() {
+            if c.is_ascii_uppercase() && i > 0 {
+                arg.push('-');
+            }
+            arg.push(c.to_ascii_lowercase());
+        }
+        arg
+    }
+}
+
+impl fmt::Display for HookEvent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl FromStr for HookEvent {
+    type Err = anyhow::Error;
+
+    /// Accepts the event name in any case, with or without `-`/`_` separators
+    fn from_str(s: &str) -> Result<Self> {
+        let wanted: String = s.chars().filter(|c| *c != '-' && *c != '_').collect();
+        match HookEvent::ALL.into_iter().find(|e| e.name().eq_ignore_ascii_case(&wanted)) {
+            Some(event) => Ok(event),
+            None => bail!("Unknown hook event: {}", s),
+        }
+    }
+}
+
+/// Fields Claude Code sends with every hook event
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct CommonFields {
+    #[serde(default)]
+    pub session_id: String,
+    #[serde(default)]
+    pub transcript_path: Option<String>,
+    #[serde(default)]
+    #[allow(dead_code)]
+    pub cwd: Option<String>,
+    #[serde(default)]
+    #[allow(dead_code)]
+    pub permission_mode: Option<String>,
+}
+
+impl CommonFields {
+    /// Session id, unless Claude Code left it empty
+    pub fn session_id(&self) -> Option<&str> {
+        Some(self.session_id.as_str()).filter(|id| !id.is_empty())
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct ToolUse {
+    #[serde(flatten)]
+    pub common: CommonFields,
+    #[serde(default)]
+    #[allow(dead_code)]
+    pub tool_name: String,
+    #[serde(default)]
+    #[allow(dead_code)]
+    pub tool_input: serde_json::Value,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct ToolResult {
+    #[serde(flatten)]
+    pub common: CommonFields,
+    #[serde(default)]
+    #[allow(dead_code)]
+    pub tool_name: String,
+    #[serde(default)]
+    #[allow(dead_code)]
+    pub tool_input: serde_json::Value,
+    #[serde(default)]
+    #[allow(dead_code)]
+    pub tool_response: serde_json::Value,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct Notification {
+    #[serde(flatten)]
+    pub common: CommonFields,
+    #[serde(default)]
+    #[allow(dead_code)]
+    pub message: String,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct UserPromptSubmit {
+    #[serde(flatten)]
+    pub common: CommonFields,
+    #[serde(default)]
+    pub prompt: String,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct Stop {
+    #[serde(flatten)]
+    pub common: CommonFields,
+    /// Set when Claude Code is already continuing because of a stop hook
+    #[serde(default)]
+    #[allow(dead_code)]
+    pub stop_hook_active: bool,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct SubagentStop {
+    #[serde(flatten)]
+    pub common: CommonFields,
+    #[serde(default)]
+    #[allow(dead_code)]
+    pub stop_hook_active: bool,
+    #[serde(default)]
+    pub agent_id: Option<String>,
+    /// The subagent's own transcript; older Claude Code versions write subagent
+    /// lines into the main transcript instead
+    #[serde(default)]
+    pub agent_transcript_path: Option<String>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct PreCompact {
+    #[serde(flatten)]
+    pub common: CommonFields,
+    /// "manual" (`/compact`) or "auto" (context window full)
+    #[serde(default)]
+    pub trigger: String,
+    /// What the user asked `/compact` to keep; empty for automatic compaction
+    #[serde(default)]
+    #[allow(dead_code)]
+    pub custom_instructions: String,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct SessionStart {
+    #[serde(flatten)]
+    pub common: CommonFields,
+    /// "startup", "resume", "clear" or "compact"
+    #[serde(default)]
+    pub source: String,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct SessionEnd {
+    #[serde(flatten)]
+    pub common: CommonFields,
+    /// "clear", "logout", "prompt_input_exit" or "other"
+    #[serde(default)]
+    pub reason: String,
+}
+
+/// A decoded hook payload
+#[derive(Debug, Clone, Deserialize)]
+#[serde(tag = "hook_event_name")]
+pub enum HookPayload {
+    PreToolUse(ToolUse),
+    PostToolUse(ToolResult),
+    Notification(Notification),
+    UserPromptSubmit(UserPromptSubmit),
+    Stop(Stop),
+    SubagentStop(SubagentStop),
+    PreCompact(PreCompact),
+    SessionStart(SessionStart),
+    SessionEnd(SessionEnd),
+}
+
+impl HookPayload {
+    /// Decode a payload, taking `default_event` when it carries no `hook_event_name`
+    /// (older Claude Code versions and hand-written tests omit it)
+    pub fn parse(value: &serde_json::Value, default_event: HookEvent) -> Result<Self> {
+        let mut value = value.clone();
+        match value.as_object_mut() {
+            Some(object) => {
+                object
+                    .entry("hook_event_name")
+                    .or_insert_with(|| default_event.name().into());
+            }
+            None => bail!("Hook payload is not a JSON object"),
+        }
+        serde_json::from_value(value).context("Invalid hook payload")
+    }
+
+    pub fn event(&self) -> HookEvent {
+        match self {
+            HookPayload::PreToolUse(_) => HookEvent::PreToolUse,
+            HookPayload::PostToolUse(_) => HookEvent::PostToolUse,
+            HookPayload::Notification(_) => HookEvent::Notification,
+            HookPayload::UserPromptSubmit(_) => HookEvent::UserPromptSubmit,
+            HookPayload::Stop(_) => HookEvent::Stop,
+            HookPayload::SubagentStop(_) => HookEvent::SubagentStop,
+            HookPayload::PreCompact(_) => HookEvent::PreCompact,
+            HookPayload::SessionStart(_) => HookEvent::SessionStart,
+            HookPayload::SessionEnd(_) => HookEvent::SessionEnd,
+        }
+    }
+
+    pub fn common(&self) -> &CommonFields {
+        match self {
+            HookPayload::PreToolUse(p) => &p.common,
+            HookPayload::PostToolUse(p) => &p.common,
+            HookPayload::Notification(p) => &p.common,
+            HookPayload::UserPromptSubmit(p) => &p.common,
+            HookPayload::Stop(p) => &p.common,
+            HookPayload::SubagentStop(p) => &p.common,
+            HookPayload::PreCompact(p) => &p.common,
+            HookPayload::SessionStart(p) => &p.common,
+            HookPayload::SessionEnd(p) => &p.common,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn fixture(name: &str) -> HookPayload {
+        let path = format!("{}/tests/fixtures/hooks/{}", env!("CARGO_MANIFEST_DIR"), name);
+        let value = serde_json::from_str(&std::fs::read_to_string(path).unwrap()).unwrap();
+        HookPayload::parse(&value, HookEvent::UserPromptSubmit).unwrap()
+    }
+
+    #[test]
+    fn tool_payloads_keep_the_tool_call() {
+        let HookPayload::PreToolUse(pre) = fixture("pre_tool_use.json") else { panic!("not PreToolUse") };
+        assert_eq!(pre.tool_name, "Read");
+        assert_eq!(pre.tool_input["file_path"], "/work/app/src/auth.rs");
+        assert_eq!(pre.common.cwd.as_deref(), Some("/work/app"));
+        assert_eq!(pre.common.permission_mode.as_deref(), Some("default"));
+
+        let HookPayload::PostToolUse(post) = fixture("post_tool_use.json") else { panic!("not PostToolUse") };
+        assert_eq!(post.tool_name, "Read");
+        assert_eq!(post.tool_input["file_path"], "/work/app/src/auth.rs");
+        assert_eq!(post.tool_response["file"]["numLines"], 42);
+        assert_eq!(post.common.permission_mode, None);
+    }
+
+    #[test]
+    fn notification_and_stop_payloads_are_typed() {
+        let HookPayload::Notification(notification) = fixture("notification.json") else { panic!("not Notification") };
+        assert_eq!(notification.message, "Claude needs your permission to use Bash");
+
+        let HookPayload::Stop(stop) = fixture("stop.json") else { panic!("not Stop") };
+        assert!(!stop.stop_hook_active);
+        assert_eq!(stop.common.session_id(), Some("session-b"));
+
+        let value = json!({ "hook_event_name": "SubagentStop", "stop_hook_active": true, "agent_id": "a-1" });
+        let HookPayload::SubagentStop(subagent) = HookPayload::parse(&value, HookEvent::Stop).unwrap() else {
+            panic!("not SubagentStop")
+        };
+        assert!(subagent.stop_hook_active);
+        assert_eq!(subagent.agent_id.as_deref(), Some("a-1"));
+    }
+
+    #[test]
+    fn pre_compact_keeps_custom_instructions() {
+        let HookPayload::PreCompact(auto) = fixture("pre_compact.json") else { panic!("not PreCompact") };
+        assert_eq!(auto.trigger, "auto");
+        assert_eq!(auto.custom_instructions, "");
+
+        let value = json!({ "trigger": "manual", "custom_instructions": "keep the migration plan" });
+        let HookPayload::PreCompact(manual) = HookPayload::parse(&value, HookEvent::PreCompact).unwrap() else {
+            panic!("not PreCompact")
+        };
+        assert_eq!(manual.trigger, "manual");
+        assert_eq!(manual.custom_instructions, "keep the migration plan");
+        assert_eq!(manual.common.session_id(), None);
+    }
+}
@@ -0,0 +1,297 @@
+use anyhow::{bail, Context, Result};
+use serde_json::{json, Map, Value};
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+use super::HookEvent;
+
+/// Claude Code settings file the hooks are installed into
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum SettingsScope {
+    /// `.claude/settings.local.json`: this checkout only, not committed
+    Local,
+    /// `.claude/settings.json`: committed and shared with the team
+    Project,
+    /// `~/.claude/settings.json`: every project of the current user
+    User,
+}
+
+impl SettingsScope {
+    pub const ALL: [SettingsScope; 3] = [SettingsScope::Local, SettingsScope::Project, SettingsScope::User];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            SettingsScope::Local => "local",
+            SettingsScope::Project => "project",
+            SettingsScope::User => "user",
+        }
+    }
+
+    /// Settings file of this scope for the project at `project_root`
+    pub fn path(self, project_root: &Path) -> Result<PathBuf> {
+        Ok(match self {
+            SettingsScope::Local => project_root.join(".claude").join("settings.local.json"),
+            SettingsScope::Project => project_root.join(".claude").join("settings.json"),
+            SettingsScope::User => dirs::home_dir()
+                .context("Could not find home directory")?
+                .join(".claude")
+                .join("settings.json"),
+        })
+    }
+
+    /// Whether the file is shared beyond this machine, so commands must not embed
+    /// local paths
+    pub fn is_shared(self) -> bool {
+        self == SettingsScope::Project
+    }
+}
+
+impl fmt::Display for SettingsScope {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// A Claude Code settings file whose off-context hooks are edited in place
+///
+/// Hooks are recognized by their command, so entries other tools or the team
+/// configured (formatters, linters, PreToolUse guards) are never touched and keys
+/// keep their order.
+pub struct SettingsFile {
+    path: PathBuf,
+    original: Option<String>,
+    value: Value,
+}
+
+impl SettingsFile {
+    /// Load a settings file, or start an empty one if it does not exist
+    pub fn load(path: impl Into<PathBuf>) -> Result<Self> {
+        let path = path.into();
+        let original = if path.exists() {
+            Some(std::fs::read_to_string(&path)
+                .with_context(|| format!("Failed to read {}", path.display()))?)
+        } else {
+            None
+        };
+
+        let value = match original.as_deref().map(str::trim) {
+            None | Some("") => json!({}),
+            Some(content) => serde_json::from_str(content)
+                .with_context(|| format!("{} is not valid JSON; fix it before off-context edits it", path.display()))?,
+        };
+        if !value.is_object() {
+            bail!("{} does not contain a JSON object", path.display());
+        }
+
+        Ok(Self { path, original, value })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Make `commands` the off-context hooks: each is added unless already present,
+    /// and any other off-context hook is removed
+    pub fn install_hooks(&mut self, commands: &[(HookEvent, String)]) -> Result<()> {
+        let root = self.value.as_object_mut().expect("settings are an object");
+        let hooks = root.entry("hooks").or_insert_with(|| json!({}));
+        let hooks = match hooks.as_object_mut() {
+            Some(hooks) => hooks,
+            None => bail!("\"hooks\" in {} is not an object", self.path.display()),
+        };
+
+        strip_hooks(hooks, |event, command| {
+            !commands.iter().any(|(e, c)| e.name() == event && c == command)
+        });
+
+        for (event, command) in commands {
+            let groups = hooks.entry(event.name()).or_insert_with(|| json!([]));
+            let groups = match groups.as_array_mut() {
+                Some(groups) => groups,
+                None => bail!("Hooks for {} in {} are not a list", event, self.path.display()),
+            };
+            let present = groups.iter().any(|group| group_commands(group).any(|c| c == command));
+            if !present {
+                groups.push(json!({ "hooks": [ { "type": "command", "command": command } ] }));
+            }
+        }
+        Ok(())
+    }
+
+    /// Remove every off-context hook, returning how many were removed
+    pub fn remove_hooks(&mut self) -> usize {
+        let root = self.value.as_object_mut().expect("settings are an object");
+        let removed = match root.get_mut("hooks").and_then(Value::as_object_mut) {
+            Some(hooks) => strip_hooks(hooks, |_, _| true),
+            None => return 0,
+        };
+        if removed > 0 && root.get("hooks").and_then(Value::as_object).is_some_and(Map::is_empty) {
+            root.shift_remove("hooks");
+        }
+        removed
+    }
+
+    /// Events with an off-context hook registered
+    pub fn installed_events(&self) -> Vec<HookEvent> {
+        let hooks = match self.value.get("hooks").and_then(Value::as_object) {
+            Some(hooks) => hooks,
+            None => return Vec::new(),
+        };
+        HookEvent::ALL
+            .into_iter()
+            .filter(|event| {
+                hooks.get(event.name()).and_then(Value::as_array).is_some_and(|groups| {
+                    groups.iter().any(|group| group_commands(group).any(is_off_context_command))
+                })
+            })
+            .collect()
+    }
+
+    /// Pretty-printed JSON as it would be written
+    pub fn render(&self) -> String {
+        let mut content = serde_json::to_string_pretty(&self.value).expect("settings serialize");
+        content.push('\n');
+        content
+    }
+
+    pub fn is_changed(&self) -> bool {
+        match &self.original {
+            Some(original) => match serde_json::from_str::<Value>(original) {
+                Ok(original) => original != self.value,
+                Err(_) => true,
+            },
+            None => true,
+        }
+    }
+
+    /// Line diff between the file on disk and what would be written
+    pub fn diff(&self) -> Vec<String> {
+        line_diff(self.original.as_deref().unwrap_or(""), &self.render())
+    }
+
+    pub fn save(&self) -> Result<()> {
+        if let Some(parent) = self.path.parent() {
+            std::fs::create_dir_all(parent)
+                .with_context(|| format!("Failed to create {}", parent.display()))?;
+        }
+        std::fs::write(&self.path, self.render())
+            .with_context(|| format!("Failed to write {}", self.path.display()))
+    }
+}
+
+/// Whether a hook command runs off-context: the binary itself, or one of the bash
+/// wrappers `setup` writes to the Claude Code hooks directory
+pub fn is_off_context_command(command: &str) -> bool {
+    let command = command.trim_start();
+    let program = match command.strip_prefix('"') {
+        Some(rest) => rest.split('"').next().unwrap_or(""),
+        None => command.split_whitespace().next().unwrap_or(""),
+    };
+    let program = Path::new(program);
+
+    match program.file_name().and_then(|n| n.to_str()) {
+        Some("off-context") => true,
+        Some(name) => {
+            let wrapper_dir = program.parent().is_some_and(|dir| dir.ends_with("claude/hooks"));
+            let event = name.strip_suffix(".sh").and_then(|stem| stem.parse::<HookEvent>().ok());
+            wrapper_dir && event.is_some()
+        }
+        None => false,
+    }
+}
+
+fn group_commands(group: &Value) -> impl Iterator<Item = &str> {
+    group
+        .get("hooks")
+        .and_then(Value::as_array)
+        .into_iter()
+        .flatten()
+        .filter_map(|hook| hook.get("command").and_then(Value::as_str))
+}
+
+/// Remove off-context hooks for which `remove(event, command)` holds, dropping the
+/// matcher groups and event lists that only held them. Returns how many went.
+fn strip_hooks(hooks: &mut Map<String, Value>, remove: impl Fn(&str, &str) -> bool) -> usize {
+    let mut removed = 0;
+    let mut emptied = Vec::new();
+
+    for (event, groups) in hooks.iter_mut() {
+        let groups = match groups.as_array_mut() {
+            Some(groups) => groups,
+            None => continue,
+        };
+        let before = groups.len();
+        groups.retain_mut(|group| {
+            let entries = match group.get_mut("hooks").and_then(Value::as_array_mut) {
+                Some(entries) => entries,
+                None => return true,
+            };
+            let count = entries.len();
+            entries.retain(|hook| {
+                let command = hook.get("command").and_then(Value::as_str).unwrap_or("");
+                !(is_off_context_command(command) && remove(event, command))
+            });
+            removed += count - entries.len();
+            count == entries.len() || !entries.is_empty()
+        });
+        if groups.is_empty() && before > 0 {
+            emptied.push(event.clone());
+        }
+    }
+
+    for event in emptied {
+        hooks.shift_remove(&event);
+    }
+    removed
+}
+
+/// Changed lines prefixed with `-`/`+`, with two lines of context around each change
+fn line_diff(before: &str, after: &str) -> Vec<String> {
+    let old: Vec<&str> = before.lines().collect();
+    let new: Vec<&str> = after.lines().collect();
+
+    // Longest common subsequence, filled from the end
+    let mut lcs = vec![vec![0usize; new.len() + 1]; old.len() + 1];
+    for i in (0..old.len()).rev() {
+        for j in (0..new.len()).rev() {
+            lcs[i][j] = if old[i] == new[j] {
+                lcs[i + 1][j + 1] + 1
+            } else {
+                lcs[i + 1][j].max(lcs[i][j + 1])
+            };
+        }
+    }
+
+    let mut lines = Vec::new();
+    let (mut i, mut j) = (0, 0);
+    while i < old.len() || j < new.len() {
+        if i < old.len() && j < new.len() && old[i] == new[j] {
+            lines.push((' ', old[i]));
+            i += 1;
+            j += 1;
+        } else if i < old.len() && (j == new.len() || lcs[i + 1][j] >= lcs[i][j + 1]) {
+            lines.push(('-', old[i]));
+            i += 1;
+        } else {
+            lines.push(('+', new[j]));
+            j += 1;
+        }
+    }
+
+    const CONTEXT: usize = 2;
+    let changed: Vec<usize> = (0..lines.len()).filter(|&k| lines[k].0 != ' ').collect();
+    let mut out = Vec::new();
+    let mut last = None;
+    for (k, (mark, line)) in lines.iter().enumerate() {
+        let near = changed.iter().any(|&c| c.abs_diff(k) <= CONTEXT);
+        if !near {
+            continue;
+        }
+        if last.is_some_and(|l: usize| k > l + 1) {
+            out.push("  ...".to_string());
+        }
+        out.push(format!("{} {}", mark, line));
+        last = Some(k);
+    }
+    out
+}
@@ -1,11 +1,10 @@
 use anyhow::Result;
 use clap::{Parser, Subcommand};
 use tracing::info;
-use std::io::{self, Read};
-use futures::executor::block_on;
 
 mod commands;
 mod core;
+mod hooks;
 
 use commands::*;
 
@@ -32,6 +31,9 @@ enum Commands {
         /// Force reconfiguration even if already set up
         #[arg(short, long)]
         force: bool,
+        /// Also write optional bash hook wrappers to ~/.config/claude/hooks
+        #[arg(long)]
+        wrappers: bool,
     },
 
     /// Show memory system status and statistics
@@ -44,6 +46,15 @@ enum Commands {
         /// Maximum number of results
         #[arg(short, long, default_value = "5")]
         limit: usize,
+        /// Treat the query as a regular expression
+        #[arg(long, conflicts_with = "fuzzy")]
+        regex: bool,
+        /// Also match words a few typos away
+        #[arg(long)]
+        fuzzy: bool,
+        /// Show how each ranking signal contributed to every result's score
+        #[arg(long)]
+        explain: bool,
     },
 
     /// Reset/clear all stored memory
@@ -70,6 +81,55 @@ enum Commands {
         output: Option<String>,
     },
 
+    /// Merge duplicate conversations and move them to stable ids
+    Dedupe {
+        /// Report what would change without modifying the store
+        #[arg(long)]
+        dry_run: bool,
+    },
+
+    /// Redact secrets and personal data from stored conversations
+    Scan {
+        /// Report findings without modifying the store
+        #[arg(long)]
+        dry_run: bool,
+    },
+
+    /// Delete conversations outside the retention rules in config.toml
+    Prune {
+        /// List what would be deleted without modifying the store
+        #[arg(long)]
+        dry_run: bool,
+    },
+
+    /// Digest finished sessions into compact notes for injection
+    Summarize {
+        /// Also summarize active sessions and rewrite up-to-date digests
+        #[arg(long)]
+        force: bool,
+    },
+
+    /// Pin a conversation so retention rules never prune it
+    Pin {
+        /// Conversation id (or a unique prefix of it)
+        id: String,
+        /// Remove the pin instead
+        #[arg(long)]
+        unpin: bool,
+    },
+
+    /// List, inspect and delete captured sessions
+    Sessions {
+        #[command(subcommand)]
+        action: sessions::SessionsAction,
+    },
+
+    /// Manage encryption at rest for the project memory store
+    Encryption {
+        #[command(subcommand)]
+        action: encryption::EncryptionAction,
+    },
+
     /// Process Claude Code transcript (internal command)
     #[command(hide = true)]
     Hook {
@@ -84,11 +144,34 @@ enum Commands {
         query: String,
     },
 
-    /// Initialize local project integration with off-context (creates/updates .claude/settings.local.json)
-    Init,
+    /// Initialize local project integration with off-context (adds hooks to .claude/settings.local.json by default)
+    Init {
+        /// Settings file to install the hooks into
+        #[arg(long, value_enum, default_value = "local")]
+        scope: hooks::SettingsScope,
+        /// Point the hooks at bash wrappers in ~/.config/claude/hooks instead of the binary
+        #[arg(long)]
+        wrappers: bool,
+        /// Show the changes to the settings file without writing anything
+        #[arg(long)]
+        dry_run: bool,
+    },
 
-    /// Remove hooks from current project (.claude/settings.local.json)
-    Clear,
+    /// Remove off-context hooks from the local settings (or another scope)
+    Clear {
+        /// Settings file to remove the hooks from
+        #[arg(long, value_enum, default_value = "local", conflicts_with = "all")]
+        scope: hooks::SettingsScope,
+        /// Remove the hooks from the local, shared project and user settings
+        #[arg(long)]
+        all: bool,
+        /// Skip the confirmation prompt of --all
+        #[arg(short, long)]
+        yes: bool,
+        /// Show the changes to the settings file without writing anything
+        #[arg(long)]
+        dry_run: bool,
+    },
 
     /// Remove global hooks and off-context global memory
     Uninstall,
@@ -99,6 +182,13 @@ enum Commands {
     /// Process UserPromptSubmit hook JSON (official format)
     InjectPrompt,
 
+    /// Handle a Claude Code hook event read from stdin (internal command)
+    #[command(hide = true)]
+    HookEvent {
+        /// Event name, e.g. session-start or SessionStart
+        event: hooks::HookEvent,
+    },
+
     /// Start web admin interface
     Admin {
         /// Port to bind the server to
@@ -112,19 +202,31 @@ async fn main() -> Result<()> {
     let cli = Cli::parse();
 
     // Initialize logging
-    init_logging(cli.verbose)?;
+    // Hooks run where nobody sees stderr, so they also log to a file
+    let hook_run = matches!(
+        cli.command,
+        Some(Commands::Hook { .. } | Commands::HookEvent { .. } | Commands::SmartInject | Commands::InjectPrompt)
+    );
+    init_logging(cli.verbose, hook_run)?;
 
     // Handle commands
     match cli.command {
-        Some(Commands::Setup { force }) => {
+        Some(Commands::Setup { force, wrappers }) => {
             info!("Starting setup process...");
-            setup::handle_setup(force).await
+            setup::handle_setup(force, wrappers).await
         }
         Some(Commands::Status) => {
             status::handle_status().await
         }
-        Some(Commands::Search { query, limit }) => {
-            search::handle_search(&query, limit).await
+        Some(Commands::Search { query, limit, regex, fuzzy, explain }) => {
+            let mode = if regex {
+                core::query::SearchMode::Regex
+            } else if fuzzy {
+                core::query::SearchMode::Fuzzy
+            } else {
+                core::query::SearchMode::Keyword
+            };
+            search::handle_search(&query, limit, mode, explain).await
         }
         Some(Commands::Reset { yes }) => {
             reset::handle_reset(yes).await
@@ -135,115 +237,57 @@ async fn main() -> Result<()> {
         Some(Commands::Export { format, output }) => {
             export::handle_export(&format, output.as_deref()).await
         }
+        Some(Commands::Dedupe { dry_run }) => {
+            dedupe::handle_dedupe(dry_run).await
+        }
+        Some(Commands::Scan { dry_run }) => {
+            scan::handle_scan(dry_run).await
+        }
+        Some(Commands::Prune { dry_run }) => {
+            prune::handle_prune(dry_run).await
+        }
+        Some(Commands::Summarize { force }) => {
+            summarize::handle_summarize(force).await
+        }
+        Some(Commands::Pin { id, unpin }) => {
+            pin::handle_pin(&id, unpin).await
+        }
+        Some(Commands::Sessions { action }) => {
+            sessions::handle_sessions(action).await
+        }
+        Some(Commands::Encryption { action }) => {
+            encryption::handle_encryption(action).await
+        }
         Some(Commands::Hook { transcript_path }) => {
             hook::handle_hook(&transcript_path).await
         }
         Some(Commands::Inject { query }) => {
             inject::handle_inject(&query).await
         }
-        Some(Commands::Init) => {
-            commands::init::handle_init().await
+        Some(Commands::Init { scope, wrappers, dry_run }) => {
+            commands::init::handle_init(scope, wrappers, dry_run).await
         }
-        Some(Commands::Clear) => {
-            commands::clear::handle_clear().await
+        Some(Commands::Clear { scope, all, yes, dry_run }) => {
+            commands::clear::handle_clear(scope, all, yes, dry_run).await
         }
         Some(Commands::Uninstall) => {
             commands::init::handle_uninstall().await
         }
         Some(Commands::SmartInject) => {
-            // Read JSON from stdin
-            let mut buffer = String::new();
-            io::stdin().read_to_string(&mut buffer).expect("Failed to read stdin");
-            let mut json: serde_json::Value = serde_json::from_str(&buffer).expect("Invalid JSON input");
-
-            // Extract session_id and check if it's a new session
-            let session_id = json.get("session_id").and_then(|v| v.as_str()).unwrap_or("");
-            let is_new_session = if !session_id.is_empty() {
-                if let Ok(config_dir) = crate::core::config::project_config_dir() {
-                    std::fs::create_dir_all(&config_dir).ok();
-                    let session_file = config_dir.join(format!("session_injected_{}", session_id));
-                    let already_injected = session_file.exists();
-                    
-                    if !already_injected {
-                        // Mark this session as having received context
-                        std::fs::write(&session_file, "").ok();
-                        true
-                    } else {
-                        false
-                    }
-                } else {
-                    true
-                }
-            } else {
-                true
-            };
-
-            // Extract prompt
-            let prompt_text = json.get("prompt").and_then(|v| v.as_str()).unwrap_or("");
-            if prompt_text.is_empty() {
-                println!("{}", buffer);
-                return Ok(());
-            }
-
-            // Inject context if new session, otherwise just pass through
-            let enhanced_prompt = if is_new_session {
-                // Use existing inject logic (now async)
-                match block_on(crate::commands::inject::inject_context_internal(prompt_text)) {
-                    Ok(ctx) => ctx,
-                    Err(_) => prompt_text.to_string(),
-                }
-            } else {
-                prompt_text.to_string()
-            };
-            json["prompt"] = serde_json::Value::String(enhanced_prompt);
-            println!("{}", serde_json::to_string(&json).unwrap());
+            // Inject the previous session on the session's first prompt, otherwise pass through
+            hooks::Dispatcher::new(hooks::HookEvent::UserPromptSubmit)
+                .prompt_source(inject::ContextSource::PreviousSession)
+                .run()
+                .await;
             Ok(())
         }
         Some(Commands::InjectPrompt) => {
-            // Process standard UserPromptSubmit hook JSON
-            let mut buffer = String::new();
-            io::stdin().read_to_string(&mut buffer).expect("Failed to read stdin");
-            let json: serde_json::Value = serde_json::from_str(&buffer).expect("Invalid JSON input");
-
-            // Extract standard hook fields
-            let session_id = json.get("session_id").and_then(|v| v.as_str()).unwrap_or("");
-            let prompt_text = json.get("prompt").and_then(|v| v.as_str()).unwrap_or("");
-            
-            if prompt_text.is_empty() {
-                println!("{}", prompt_text);
-                return Ok(());
-            }
-
-            // Check if we already injected context for this session  
-            let is_new_session = if !session_id.is_empty() {
-                if let Ok(config_dir) = crate::core::config::project_config_dir() {
-                    std::fs::create_dir_all(&config_dir).ok();
-                    let session_file = config_dir.join(format!("session_injected_{}", session_id));
-                    let already_injected = session_file.exists();
-                    
-                    if !already_injected {
-                        // Mark this session as having received context
-                        std::fs::write(&session_file, "").ok();
-                        true
-                    } else {
-                        false
-                    }
-                } else {
-                    true
-                }
-            } else {
-                true
-            };
-
-            // Inject context only if new session
-            if is_new_session {
-                match block_on(crate::commands::inject::inject_context_simple(prompt_text)) {
-                    Ok(enhanced) => println!("{}", enhanced),
-                    Err(_) => println!("{}", prompt_text),
-                }
-            } else {
-                println!("{}", prompt_text);
-            }
+            // Session state decides whether this prompt gets relevant memory
+            hooks::Dispatcher::new(hooks::HookEvent::UserPromptSubmit).run().await;
+            Ok(())
+        }
+        Some(Commands::HookEvent { event }) => {
+            hooks::Dispatcher::new(event).run().await;
             Ok(())
         }
         Some(Commands::Admin { port }) => {
@@ -267,18 +311,47 @@ async fn main() -> Result<()> {
     }
 }
 
-fn init_logging(verbose: bool) -> Result<()> {
-    use tracing_subscriber::{fmt, EnvFilter};
+fn init_logging(verbose: bool, hook_run: bool) -> Result<()> {
+    use tracing_subscriber::{fmt, prelude::*, EnvFilter};
 
     let level = if verbose { "debug" } else { "warn" };
     
     let filter = EnvFilter::try_from_default_env()
         .unwrap_or_else(|_| EnvFilter::new(level));
 
-    fmt()
-        .with_env_filter(filter)
+    let stderr = fmt::layer()
         .with_target(false)
+        .with_writer(std::io::stderr)
+        .with_filter(filter);
+
+    // One JSON object per line, rotated daily; logging must never break a hook
+    let hook_log = hook_run.then(hook_log_appender).flatten().map(|appender| {
+        let level = if verbose { "off_context=debug" } else { "off_context=info" };
+        fmt::layer()
+            .json()
+            .with_writer(appender)
+            .with_filter(EnvFilter::new(level))
+    });
+
+    tracing_subscriber::registry()
+        .with(stderr)
+        .with(hook_log)
         .init();
 
     Ok(())
-}
\ No newline at end of file
+}
+
+/// Appender for `~/.off-context/logs/hooks.<date>.log`, keeping a week of files
+fn hook_log_appender() -> Option<tracing_appender::rolling::RollingFileAppender> {
+    use tracing_appender::rolling::{RollingFileAppender, Rotation};
+
+    let dir = core::config::hook_log_dir().ok()?;
+    std::fs::create_dir_all(&dir).ok()?;
+    RollingFileAppender::builder()
+        .rotation(Rotation::DAILY)
+        .filename_prefix("hooks")
+        .filename_suffix("log")
+        .max_log_files(7)
+        .build(dir)
+        .ok()
+}
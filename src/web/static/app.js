@@ -169,6 +169,7 @@ class OffContextAdmin {
     async performSearch() {
         const query = document.getElementById('search-input').value.trim();
         const limit = document.getElementById('search-limit').value;
+        const mode = document.getElementById('search-mode').value;
         
         if (!query) {
             this.showError('Please enter a search query');
@@ -198,7 +199,12 @@ class OffContextAdmin {
         `;
 
         try {
-            const response = await fetch(`/api/search?q=${encodeURIComponent(query)}&limit=${limit}`);
+            const response = await fetch(`/api/search?q=${encodeURIComponent(query)}&limit=${limit}&mode=${mode}`);
+            if (response.status === 400) {
+                const body = await response.json().catch(() => ({}));
+                this.showError(body.error ? this.escapeHtml(body.error) : (mode === 'regex' ? 'Invalid regular expression' : 'Invalid search query'));
+                return;
+            }
             const data = await response.json();
             this.displaySearchResults(data);
         } catch (error) {
@@ -0,0 +1,241 @@
+//! Shared helpers for driving the `off-context` binary inside a throwaway project.
+
+#![allow(dead_code)]
+
+use serde_json::Value;
+use std::io::{Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, Output, Stdio};
+use std::time::{Duration, Instant};
+
+use tempfile::TempDir;
+
+/// A fake `$HOME` plus a project directory, both removed on drop
+pub struct Sandbox {
+    _root: TempDir,
+    pub home: PathBuf,
+    pub project: PathBuf,
+}
+
+impl Sandbox {
+    /// Create an empty sandbox (the project is not initialized)
+    pub fn new() -> Self {
+        let root = TempDir::new().expect("create temp dir");
+        let home = root.path().join("home");
+        let project = root.path().join("project");
+        std::fs::create_dir_all(&home).unwrap();
+        std::fs::create_dir_all(&project).unwrap();
+
+        Self { _root: root, home, project }
+    }
+
+    /// Create a sandbox and run `off-context init` in it
+    pub fn initialized() -> Self {
+        let sandbox = Self::new();
+        let output = sandbox.run(&["init"], None);
+        assert!(output.status.success(), "init failed: {}", String::from_utf8_lossy(&output.stderr));
+        sandbox
+    }
+
+    /// Initialized sandbox with the basic transcript (session-a) already captured
+    pub fn with_history() -> Self {
+        Self::with_transcripts(&["transcript_basic.jsonl"])
+    }
+
+    /// Initialized sandbox holding two sessions: session-a (basic: auth and
+    /// database, 2026-09-01) and session-c (rust: JWT, 2026-09-10)
+    pub fn with_sessions() -> Self {
+        Self::with_transcripts(&["transcript_basic.jsonl", "transcript_rust.jsonl"])
+    }
+
+    /// Initialized sandbox with each fixture transcript captured, in order
+    pub fn with_transcripts(names: &[&str]) -> Self {
+        let sandbox = Self::initialized();
+        for name in names {
+            sandbox.ingest(name);
+        }
+        sandbox
+    }
+
+    /// Capture a fixture transcript through the `hook` command
+    pub fn ingest(&self, name: &str) {
+        self.ingest_with_env(name, &[]);
+    }
+
+    /// Like `ingest`, with extra environment variables set
+    pub fn ingest_with_env(&self, name: &str, env: &[(&str, &str)]) {
+        let transcript = self.write(name, &fixture(name));
+        self.run_ok(&["hook", transcript.to_str().unwrap()], env);
+    }
+
+    /// Run the binary from the project directory, optionally feeding `stdin`
+    pub fn run(&self, args: &[&str], stdin: Option<&str>) -> Output {
+        self.run_with_env(args, stdin, &[])
+    }
+
+    /// Like `run`, with extra environment variables set
+    pub fn run_with_env(&self, args: &[&str], stdin: Option<&str>, env: &[(&str, &str)]) -> Output {
+        let mut child = self.command(args)
+            .envs(env.iter().copied())
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .expect("spawn off-context");
+
+        {
+            let mut pipe = child.stdin.take().unwrap();
+            if let Some(input) = stdin {
+                pipe.write_all(input.as_bytes()).unwrap();
+            }
+        }
+
+        child.wait_with_output().expect("wait for off-context")
+    }
+
+    /// Like `run_with_env` without stdin, asserting the command succeeded
+    pub fn run_ok(&self, args: &[&str], env: &[(&str, &str)]) -> Output {
+        let output = self.run_with_env(args, None, env);
+        assert_success(&output, &args.join(" "));
+        output
+    }
+
+    /// Start a long-running command (such as `admin`) without waiting for it
+    pub fn spawn(&self, args: &[&str]) -> Child {
+        self.command(args)
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn()
+            .expect("spawn off-context")
+    }
+
+    fn command(&self, args: &[&str]) -> Command {
+        let mut command = Command::new(env!("CARGO_BIN_EXE_off-context"));
+        command
+            .args(args)
+            .current_dir(&self.project)
+            .env("HOME", &self.home)
+            .env_remove("RUST_LOG")
+            .env_remove("OFF_CONTEXT_PASSPHRASE")
+            .env_remove("OFF_CONTEXT_NEW_PASSPHRASE");
+        command
+    }
+
+    /// Path of the project-local config file
+    pub fn config_path(&self) -> PathBuf {
+        self.project.join(".off-context").join("config.toml")
+    }
+
+    /// Apply literal replacements to the project config written by `init`
+    pub fn edit_config(&self, replacements: &[(&str, &str)]) {
+        let path = self.config_path();
+        let mut content = std::fs::read_to_string(&path).expect("read project config");
+        for (from, to) in replacements {
+            assert!(content.contains(from), "config has no `{}`:\n{}", from, content);
+            content = content.replace(from, to);
+        }
+        std::fs::write(&path, content).unwrap();
+    }
+
+    /// Directory of the project's memory store
+    pub fn store_dir(&self) -> PathBuf {
+        self.project.join(".off-context").join("qdrant")
+    }
+
+    /// Segment files of the store, in write order
+    pub fn segment_files(&self) -> Vec<PathBuf> {
+        let mut paths: Vec<PathBuf> = std::fs::read_dir(self.store_dir().join("segments"))
+            .unwrap()
+            .map(|entry| entry.unwrap().path())
+            .filter(|path| path.extension().is_some_and(|ext| ext == "jsonl"))
+            .collect();
+        paths.sort();
+        paths
+    }
+
+    /// Concatenated segment files of the store
+    pub fn segments(&self) -> String {
+        self.segment_files()
+            .iter()
+            .map(|path| std::fs::read_to_string(path).unwrap())
+            .collect()
+    }
+
+    /// Write a file relative to the sandbox project
+    pub fn write(&self, relative: impl AsRef<Path>, content: &str) -> PathBuf {
+        let path = self.project.join(relative);
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent).unwrap();
+        }
+        std::fs::write(&path, content).unwrap();
+        path
+    }
+}
+
+/// The admin server, stopped on drop
+pub struct Admin {
+    child: Child,
+    pub port: u16,
+}
+
+impl Admin {
+    pub fn start(sandbox: &Sandbox) -> Self {
+        let port = TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().port();
+        let child = sandbox.spawn(&["admin", "--port", &port.to_string()]);
+        let admin = Self { child, port };
+
+        let deadline = Instant::now() + Duration::from_secs(10);
+        while TcpStream::connect(("127.0.0.1", port)).is_err() {
+            assert!(Instant::now() < deadline, "admin server did not start");
+            std::thread::sleep(Duration::from_millis(50));
+        }
+        admin
+    }
+
+    /// Status code and JSON body (`Null` when empty)
+    pub fn request(&self, method: &str, path: &str) -> (u16, Value) {
+        self.request_from(method, path, None)
+    }
+
+    /// Like `request`, sent by a browser page from `origin`
+    pub fn request_from(&self, method: &str, path: &str, origin: Option<&str>) -> (u16, Value) {
+        let mut stream = TcpStream::connect(("127.0.0.1", self.port)).unwrap();
+        let origin = origin.map_or_else(String::new, |origin| format!("Origin: {}\r\n", origin));
+        write!(
+            stream,
+            "{} {} HTTP/1.1\r\nHost: localhost:{}\r\n{}Connection: close\r\n\r\n",
+            method, path, self.port, origin
+        ).unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+
+        let (head, body) = response.split_once("\r\n\r\n").unwrap();
+        let status = head.split_whitespace().nth(1).unwrap().parse().unwrap();
+        let body = if body.trim().is_empty() { Value::Null } else { serde_json::from_str(body).unwrap() };
+        (status, body)
+    }
+}
+
+impl Drop for Admin {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+/// Contents of a file under `tests/fixtures`
+pub fn fixture(name: &str) -> String {
+    let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests").join("fixtures").join(name);
+    std::fs::read_to_string(&path).unwrap_or_else(|e| panic!("read fixture {}: {}", path.display(), e))
+}
+
+/// Fail with the command's stderr unless it exited successfully
+pub fn assert_success(output: &Output, what: &str) {
+    assert!(output.status.success(), "{} failed: {}", what, String::from_utf8_lossy(&output.stderr));
+}
+
+pub fn stdout(output: &Output) -> String {
+    String::from_utf8_lossy(&output.stdout).into_owned()
+}
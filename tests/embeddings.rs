@@ -0,0 +1,101 @@
+//! Semantic search against a stub Ollama-compatible embeddings server.
+
+#![cfg(feature = "ml")]
+
+mod common;
+
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::TcpListener;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+use common::{stdout, Sandbox};
+
+/// Serve `/api/tags` and `/api/embeddings`, mapping prompts onto a tiny topic space
+fn spawn_stub_server(requests: Arc<AtomicUsize>) -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+
+    std::thread::spawn(move || {
+        for stream in listener.incoming() {
+            let mut stream = match stream {
+                Ok(s) => s,
+                Err(_) => continue,
+            };
+            let mut reader = BufReader::new(stream.try_clone().unwrap());
+
+            let mut request_line = String::new();
+            reader.read_line(&mut request_line).unwrap();
+            let mut content_length = 0;
+            loop {
+                let mut header = String::new();
+                reader.read_line(&mut header).unwrap();
+                if header.trim().is_empty() {
+                    break;
+                }
+                if let Some(value) = header.to_ascii_lowercase().strip_prefix("content-length:") {
+                    content_length = value.trim().parse().unwrap();
+                }
+            }
+            let mut body = vec![0; content_length];
+            reader.read_exact(&mut body).unwrap();
+
+            let response = if request_line.contains("/api/embeddings") {
+                requests.fetch_add(1, Ordering::SeqCst);
+                let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+                let prompt = body["prompt"].as_str().unwrap_or("").to_lowercase();
+                let auth = if prompt.contains("auth") || prompt.contains("credentials") { 1.0 } else { 0.0 };
+                let db = if prompt.contains("database") || prompt.contains("migration") { 1.0 } else { 0.0 };
+                serde_json::json!({ "embedding": [auth, db, 0.05] }).to_string()
+            } else {
+                serde_json::json!({ "models": [] }).to_string()
+            };
+
+            let _ = write!(
+                stream,
+                "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                response.len(),
+                response
+            );
+        }
+    });
+
+    format!("http://{}", addr)
+}
+
+#[test]
+fn ranks_by_embedding_similarity_from_remote_provider() {
+    let requests = Arc::new(AtomicUsize::new(0));
+    let base_url = spawn_stub_server(requests.clone());
+
+    let sandbox = Sandbox::initialized();
+    sandbox.edit_config(&[
+        ("provider = \"simple\"", "provider = \"ollama\""),
+        ("base_url = \"http://localhost:11434\"", &format!("base_url = \"{}\"", base_url)),
+    ]);
+
+    sandbox.ingest("transcript_basic.jsonl");
+    assert!(requests.load(Ordering::SeqCst) >= 2, "conversations were not embedded");
+
+    // No keyword overlap with the stored text, so only the embeddings can match
+    let output = sandbox.run(&["search", "credentials"], None);
+    let text = stdout(&output);
+    assert!(text.contains("Found 1 results"), "{}", text);
+    assert!(text.contains("JWT signature"), "{}", text);
+    assert!(!text.contains("drop_legacy_table"), "{}", text);
+}
+
+#[test]
+fn falls_back_to_offline_vectors_when_server_is_down() {
+    let sandbox = Sandbox::initialized();
+    sandbox.edit_config(&[
+        ("provider = \"simple\"", "provider = \"ollama\""),
+        ("base_url = \"http://localhost:11434\"", "base_url = \"http://127.0.0.1:9\""),
+        ("relevance_threshold = 0.6", "relevance_threshold = 0.1"),
+    ]);
+
+    sandbox.ingest("transcript_basic.jsonl");
+
+    let text = stdout(&sandbox.run(&["search", "pending database migrations"], None));
+    assert!(text.contains("drop_legacy_table"), "{}", text);
+}
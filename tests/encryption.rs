@@ -0,0 +1,255 @@
+//! Encryption at rest: enabling, rotating and disabling it, and what is left on disk
+//! when any of that is interrupted.
+
+mod common;
+
+use common::{assert_success, stdout, Sandbox};
+use serde_json::Value;
+use std::path::{Path, PathBuf};
+
+const AUTH_QUESTION: &str = "How does the auth module validate login tokens?";
+const MIGRATIONS_QUESTION: &str = "Which database migrations are still pending?";
+
+/// User prompts of the hits for `query`
+fn hits(sandbox: &Sandbox, query: &str) -> Vec<String> {
+    hits_with_env(sandbox, query, &[])
+}
+
+fn hits_with_env(sandbox: &Sandbox, query: &str, env: &[(&str, &str)]) -> Vec<String> {
+    stdout(&sandbox.run_ok(&["search", query, "--limit", "10"], env))
+        .lines()
+        .filter_map(|line| line.trim().strip_prefix("│ User: "))
+        .map(str::to_string)
+        .collect()
+}
+
+fn segment_lines(sandbox: &Sandbox) -> Vec<String> {
+    sandbox.segments().lines().map(str::to_string).collect()
+}
+
+/// Key ids the segment lines are encrypted with, and how many lines are plaintext
+fn line_keys(sandbox: &Sandbox) -> (Vec<String>, usize) {
+    let mut keys = Vec::new();
+    let mut plaintext = 0;
+    for line in segment_lines(sandbox) {
+        match line.strip_prefix("enc:").and_then(|rest| rest.split_once(':')) {
+            Some((id, _)) => {
+                if !keys.contains(&id.to_string()) {
+                    keys.push(id.to_string());
+                }
+            }
+            None => plaintext += 1,
+        }
+    }
+    (keys, plaintext)
+}
+
+fn header_path(sandbox: &Sandbox) -> PathBuf {
+    sandbox.store_dir().join("encryption.json")
+}
+
+fn header(sandbox: &Sandbox) -> Value {
+    serde_json::from_str(&std::fs::read_to_string(header_path(sandbox)).unwrap()).unwrap()
+}
+
+fn key_files(sandbox: &Sandbox) -> Vec<PathBuf> {
+    match std::fs::read_dir(sandbox.home.join(".off-context").join("keys")) {
+        Ok(entries) => entries.map(|entry| entry.unwrap().path()).collect(),
+        Err(_) => Vec::new(),
+    }
+}
+
+/// Every file under `dir`, recursively
+fn files_under(dir: &Path) -> Vec<PathBuf> {
+    let mut files = Vec::new();
+    for entry in std::fs::read_dir(dir).unwrap() {
+        let path = entry.unwrap().path();
+        if path.is_dir() {
+            files.extend(files_under(&path));
+        } else {
+            files.push(path);
+        }
+    }
+    files
+}
+
+/// Assert no file in the store holds `text` in the clear
+fn assert_not_on_disk(sandbox: &Sandbox, text: &str) {
+    for path in files_under(&sandbox.store_dir()) {
+        let content = String::from_utf8_lossy(&std::fs::read(&path).unwrap()).into_owned();
+        assert!(!content.contains(text), "{} holds {:?} in plaintext", path.display(), text);
+    }
+}
+
+#[test]
+fn enabled_store_is_encrypted_on_disk_and_still_searchable() {
+    let sandbox = Sandbox::with_history();
+    assert_eq!(line_keys(&sandbox).0.len(), 0);
+
+    let output = sandbox.run_ok(&["encryption", "enable"], &[]);
+    assert!(stdout(&output).contains("Encrypted 2 conversations"), "{}", stdout(&output));
+
+    let (keys, plaintext) = line_keys(&sandbox);
+    assert_eq!(keys.len(), 1);
+    assert_eq!(plaintext, 0);
+    assert_eq!(key_files(&sandbox).len(), 1);
+    assert!(header(&sandbox).get("pending_rewrite").is_none(), "{}", header(&sandbox));
+    assert_not_on_disk(&sandbox, "validate login tokens");
+
+    // New records are written encrypted too
+    sandbox.ingest("transcript_rust.jsonl");
+    assert_eq!(line_keys(&sandbox), (keys, 0));
+    assert_not_on_disk(&sandbox, "borrow checker");
+
+    assert_eq!(hits(&sandbox, "auth"), [AUTH_QUESTION]);
+    assert_eq!(hits(&sandbox, "\"borrow checker\"").len(), 1);
+    assert!(stdout(&sandbox.run_ok(&["status"], &[])).contains("Encryption at rest: ✅ Enabled"));
+}
+
+#[test]
+fn passphrase_store_needs_the_right_passphrase() {
+    let sandbox = Sandbox::with_history();
+    let passphrase = [("OFF_CONTEXT_PASSPHRASE", "correct horse battery staple")];
+    sandbox.run_ok(&["encryption", "enable", "--key-source", "passphrase"], &passphrase);
+
+    assert!(key_files(&sandbox).is_empty());
+    assert_eq!(hits_with_env(&sandbox, "auth", &passphrase), [AUTH_QUESTION]);
+
+    let wrong = sandbox.run_with_env(&["search", "auth"], None, &[("OFF_CONTEXT_PASSPHRASE", "tr0ub4dor")]);
+    assert!(!wrong.status.success());
+    assert!(String::from_utf8_lossy(&wrong.stderr).contains("Wrong passphrase"), "{}", String::from_utf8_lossy(&wrong.stderr));
+
+    let missing = sandbox.run(&["search", "auth"], None);
+    assert!(!missing.status.success());
+    assert!(String::from_utf8_lossy(&missing.stderr).contains("OFF_CONTEXT_PASSPHRASE"), "{}", String::from_utf8_lossy(&missing.stderr));
+}
+
+#[test]
+fn tampered_record_fails_authentication_and_is_skipped() {
+    let sandbox = Sandbox::with_history();
+    sandbox.run_ok(&["encryption", "enable"], &[]);
+
+    // Flip one character inside the ciphertext of the first record
+    let segment = sandbox.segment_files().pop().unwrap();
+    let content = std::fs::read_to_string(&segment).unwrap();
+    let (first, rest) = content.split_once('\n').unwrap();
+    let mut tampered: Vec<char> = first.chars().collect();
+    let at = tampered.len() - 10;
+    tampered[at] = if tampered[at] == 'A' { 'B' } else { 'A' };
+    let tampered: String = tampered.into_iter().collect();
+    std::fs::write(&segment, format!("{}\n{}", tampered, rest)).unwrap();
+
+    let output = sandbox.run_with_env(&["search", "auth OR migrations", "--limit", "10"], None, &[("RUST_LOG", "warn")]);
+    assert_success(&output, "search");
+    assert!(String::from_utf8_lossy(&output.stderr).contains("failed authentication"), "{}", String::from_utf8_lossy(&output.stderr));
+    let found: Vec<String> = stdout(&output)
+        .lines()
+        .filter_map(|line| line.trim().strip_prefix("│ User: "))
+        .map(str::to_string)
+        .collect();
+    assert_eq!(found.len(), 1, "{:?}", found);
+}
+
+#[test]
+fn interrupted_initial_rewrite_is_finished_on_next_open() {
+    let sandbox = Sandbox::with_history();
+    let plaintext = segment_lines(&sandbox);
+    sandbox.run_ok(&["encryption", "enable"], &[]);
+
+    // As if the process died after writing the header but before rewriting the log
+    let mut pending = header(&sandbox);
+    pending["pending_rewrite"] = Value::Bool(true);
+    std::fs::write(header_path(&sandbox), pending.to_string()).unwrap();
+    for path in sandbox.segment_files() {
+        std::fs::remove_file(path).unwrap();
+    }
+    std::fs::write(sandbox.store_dir().join("segments").join("00000001.jsonl"), plaintext.join("\n") + "\n").unwrap();
+
+    assert_eq!(hits(&sandbox, "migrations"), [MIGRATIONS_QUESTION]);
+    assert_eq!(line_keys(&sandbox).1, 0);
+    assert_not_on_disk(&sandbox, "validate login tokens");
+    assert!(header(&sandbox).get("pending_rewrite").is_none(), "{}", header(&sandbox));
+}
+
+#[test]
+fn enabling_removes_the_plaintext_migration_backup() {
+    let sandbox = Sandbox::with_history();
+    // Turn the log back into a pre-segment `conversations.json`
+    let conversations: Vec<Value> = segment_lines(&sandbox)
+        .iter()
+        .map(|line| serde_json::from_str::<Value>(line).unwrap())
+        .filter(|record| record["op"] == "put")
+        .map(|record| record["conversation"].clone())
+        .collect();
+    std::fs::remove_dir_all(sandbox.store_dir()).unwrap();
+    std::fs::create_dir_all(sandbox.store_dir()).unwrap();
+    std::fs::write(sandbox.store_dir().join("conversations.json"), Value::from(conversations).to_string()).unwrap();
+
+    assert_eq!(hits(&sandbox, "auth"), [AUTH_QUESTION]);
+    let backup = sandbox.store_dir().join("conversations.json.migrated");
+    assert!(backup.exists());
+
+    let output = sandbox.run_ok(&["encryption", "enable"], &[]);
+    assert!(stdout(&output).contains("Removed plaintext migration backup"), "{}", stdout(&output));
+    assert!(!backup.exists());
+    assert_not_on_disk(&sandbox, "validate login tokens");
+}
+
+#[test]
+fn interrupted_rotation_keeps_old_records_readable_until_rerun() {
+    let sandbox = Sandbox::with_history();
+    sandbox.run_ok(&["encryption", "enable"], &[]);
+    let old_header = header(&sandbox);
+    let old_segments: Vec<(PathBuf, Vec<u8>)> =
+        sandbox.segment_files().into_iter().map(|path| (path.clone(), std::fs::read(&path).unwrap())).collect();
+    let old_keys: Vec<(PathBuf, Vec<u8>)> =
+        key_files(&sandbox).into_iter().map(|path| (path.clone(), std::fs::read(&path).unwrap())).collect();
+
+    sandbox.run_ok(&["encryption", "rotate"], &[]);
+    let new_key = header(&sandbox)["active"].as_str().unwrap().to_string();
+    assert_ne!(old_header["active"], header(&sandbox)["active"]);
+    assert_eq!(key_files(&sandbox).len(), 1);
+
+    // Halfway: the header lists both keys and the old segment sits next to the
+    // rewritten one
+    let mut mid_rotation = header(&sandbox);
+    let mut keys = old_header["keys"].as_array().unwrap().clone();
+    keys.extend(mid_rotation["keys"].as_array().unwrap().iter().cloned());
+    mid_rotation["keys"] = Value::from(keys);
+    std::fs::write(header_path(&sandbox), mid_rotation.to_string()).unwrap();
+    for (path, content) in old_segments.iter().chain(&old_keys) {
+        std::fs::write(path, content).unwrap();
+    }
+    assert_eq!(line_keys(&sandbox).0.len(), 2);
+
+    let status = stdout(&sandbox.run_ok(&["status"], &[]));
+    assert!(status.contains("Interrupted key rotation (1 old keys)"), "{}", status);
+    assert_eq!(hits(&sandbox, "auth"), [AUTH_QUESTION]);
+
+    // Running the rotation again finishes it
+    sandbox.run_ok(&["encryption", "rotate"], &[]);
+    let (keys, plaintext) = line_keys(&sandbox);
+    assert_eq!(plaintext, 0);
+    assert_eq!(keys.len(), 1);
+    assert_ne!(keys[0], new_key);
+    assert_eq!(header(&sandbox)["keys"].as_array().unwrap().len(), 1);
+    assert_eq!(key_files(&sandbox).len(), 1);
+    assert!(!stdout(&sandbox.run_ok(&["status"], &[])).contains("Interrupted key rotation"));
+    assert_eq!(hits(&sandbox, "auth"), [AUTH_QUESTION]);
+}
+
+#[test]
+fn disable_decrypts_and_forgets_the_keys() {
+    let sandbox = Sandbox::with_history();
+    sandbox.run_ok(&["encryption", "enable"], &[]);
+    sandbox.run_ok(&["encryption", "disable"], &[]);
+
+    let (keys, plaintext) = line_keys(&sandbox);
+    assert!(keys.is_empty(), "{:?}", keys);
+    assert!(plaintext > 0);
+    assert!(!header_path(&sandbox).exists());
+    assert!(key_files(&sandbox).is_empty());
+    assert!(std::fs::read_to_string(sandbox.config_path()).unwrap().contains("[encryption]\nenabled = false"));
+    assert_eq!(hits(&sandbox, "auth"), [AUTH_QUESTION]);
+    assert!(stdout(&sandbox.run_ok(&["status"], &[])).contains("Encryption at rest: ❌ Disabled"));
+}
@@ -0,0 +1,308 @@
+//! Hook runtime: recorded Claude Code payloads for every event, plus broken input
+//! that must never block the user's prompt.
+
+mod common;
+
+use common::{fixture, stdout, Sandbox};
+use serde_json::{json, Value};
+use std::process::Output;
+
+const PROMPT_HOOKS: [&str; 2] = ["inject-prompt", "smart-inject"];
+
+const RECORDED_PAYLOADS: [&str; 13] = [
+    "hooks/user_prompt_submit.json",
+    "hooks/user_prompt_submit_empty.json",
+    "hooks/user_prompt_submit_minimal.json",
+    "hooks/user_prompt_submit_extra_fields.json",
+    "hooks/user_prompt_submit_bad_types.json",
+    "hooks/session_start.json",
+    "hooks/session_end.json",
+    "hooks/pre_compact.json",
+    "hooks/stop.json",
+    "hooks/subagent_stop.json",
+    "hooks/pre_tool_use.json",
+    "hooks/post_tool_use.json",
+    "hooks/notification.json",
+];
+
+/// The hook exited cleanly, without a panic
+fn assert_passed(output: &Output, input: &str) {
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(output.status.success(), "hook failed for {:?}: {}", input, stderr);
+    assert!(!stderr.contains("panicked"), "hook panicked for {:?}: {}", input, stderr);
+}
+
+fn additional_context(output: &Output) -> String {
+    let text = stdout(output);
+    let json: Value = serde_json::from_str(text.trim()).unwrap_or_else(|e| panic!("not JSON ({}): {:?}", e, text));
+    json["hookSpecificOutput"]["additionalContext"].as_str().unwrap().to_string()
+}
+
+#[test]
+fn recorded_payloads_never_fail_prompt_hooks() {
+    let sandbox = Sandbox::with_history();
+
+    for name in RECORDED_PAYLOADS {
+        for hook in PROMPT_HOOKS {
+            let output = sandbox.run(&[hook], Some(&fixture(name)));
+            assert_passed(&output, name);
+        }
+    }
+}
+
+#[test]
+fn malformed_input_passes_through() {
+    let sandbox = Sandbox::with_history();
+
+    for input in ["", "   ", "not json", "{\"prompt\": ", "[1, 2, 3]", "null", "\"prompt\""] {
+        for hook in PROMPT_HOOKS {
+            let output = sandbox.run(&[hook], Some(input));
+            assert_passed(&output, input);
+            assert_eq!(stdout(&output).trim(), "", "{} printed output for {:?}", hook, input);
+        }
+    }
+}
+
+#[test]
+fn wrongly_typed_fields_pass_through() {
+    let sandbox = Sandbox::with_history();
+
+    let output = sandbox.run(&["inject-prompt"], Some(&fixture("hooks/user_prompt_submit_bad_types.json")));
+    assert_passed(&output, "bad types");
+    assert_eq!(stdout(&output).trim(), "");
+}
+
+#[test]
+fn payload_without_event_name_is_treated_as_a_prompt() {
+    let sandbox = Sandbox::with_history();
+
+    let output = sandbox.run(&["inject-prompt"], Some(&fixture("hooks/user_prompt_submit_minimal.json")));
+    assert_passed(&output, "minimal");
+    assert!(additional_context(&output).contains("JWT signature"));
+}
+
+#[test]
+fn unknown_fields_are_ignored() {
+    let sandbox = Sandbox::with_history();
+
+    let output = sandbox.run(&["inject-prompt"], Some(&fixture("hooks/user_prompt_submit_extra_fields.json")));
+    assert_passed(&output, "extra fields");
+    assert!(additional_context(&output).starts_with("[PREV: "));
+}
+
+#[test]
+fn other_events_are_not_treated_as_prompts() {
+    let sandbox = Sandbox::with_history();
+
+    for name in ["hooks/stop.json", "hooks/session_start.json", "hooks/notification.json"] {
+        let output = sandbox.run(&["inject-prompt"], Some(&fixture(name)));
+        assert_passed(&output, name);
+        assert_eq!(stdout(&output).trim(), "", "{}", name);
+    }
+
+    // The session was not marked as injected along the way
+    let output = sandbox.run(&["inject-prompt"], Some(&fixture("hooks/user_prompt_submit.json")));
+    assert!(additional_context(&output).contains("JWT signature"));
+}
+
+#[test]
+fn broken_config_fails_open() {
+    let sandbox = Sandbox::with_history();
+    std::fs::write(sandbox.config_path(), "[hooks\nauto_inject = ").unwrap();
+
+    for hook in PROMPT_HOOKS {
+        let output = sandbox.run(&[hook], Some(&fixture("hooks/user_prompt_submit.json")));
+        assert_passed(&output, hook);
+        assert_eq!(stdout(&output).trim(), "");
+    }
+}
+
+#[test]
+fn legacy_mode_echoes_the_prompt_when_the_payload_is_invalid() {
+    let sandbox = Sandbox::with_history();
+    sandbox.edit_config(&[(
+        "prompt_output = \"additional_context\"",
+        "prompt_output = \"legacy\"",
+    )]);
+    let payload = r#"{"session_id": 7, "prompt": "Why are login tokens being rejected after deploy?"}"#;
+
+    let output = sandbox.run(&["inject-prompt"], Some(payload));
+    assert_passed(&output, payload);
+    assert_eq!(stdout(&output).trim(), "Why are login tokens being rejected after deploy?");
+}
+
+/// Payload for `hook-event`, pointing at a transcript inside the sandbox
+fn event_payload(event: &str, session_id: &str, transcript: &std::path::Path, extra: Value) -> String {
+    let mut payload = json!({
+        "session_id": session_id,
+        "transcript_path": transcript,
+        "cwd": "/work/app",
+        "hook_event_name": event,
+    });
+    payload.as_object_mut().unwrap().extend(extra.as_object().unwrap().clone());
+    payload.to_string()
+}
+
+#[test]
+fn session_start_recalls_the_previous_session_once() {
+    let sandbox = Sandbox::with_history();
+    let transcript = sandbox.project.join("session-b.jsonl");
+    let start = event_payload("SessionStart", "session-b", &transcript, json!({ "source": "startup" }));
+
+    let output = sandbox.run(&["hook-event", "session-start"], Some(&start));
+    assert_passed(&output, "session start");
+    let json: Value = serde_json::from_str(stdout(&output).trim()).unwrap();
+    assert_eq!(json["hookSpecificOutput"]["hookEventName"], "SessionStart");
+    assert!(additional_context(&output).contains("JWT signature"));
+
+    // A resumed session already has it
+    let resume = event_payload("SessionStart", "session-b", &transcript, json!({ "source": "resume" }));
+    let output = sandbox.run(&["hook-event", "SessionStart"], Some(&resume));
+    assert_passed(&output, "resume");
+    assert_eq!(stdout(&output).trim(), "");
+}
+
+#[test]
+fn first_prompt_after_session_start_still_gets_relevant_context() {
+    let sandbox = Sandbox::with_history();
+    sandbox.ingest("transcript_rust.jsonl");
+
+    // session-c is the latest, so it is what SessionStart recalls
+    let transcript = sandbox.project.join("session-b.jsonl");
+    let start = event_payload("SessionStart", "session-b", &transcript, json!({ "source": "startup" }));
+    let output = sandbox.run(&["hook-event", "session-start"], Some(&start));
+    assert_passed(&output, "session start");
+    let recalled = additional_context(&output);
+    assert!(recalled.contains("token_expiry_is_enforced"), "{}", recalled);
+
+    // The prompt is about login tokens: session-a answers it, minus what was recalled
+    let output = sandbox.run(&["inject-prompt"], Some(&fixture("hooks/user_prompt_submit.json")));
+    assert_passed(&output, "first prompt");
+    let context = additional_context(&output);
+    assert!(context.contains("JWT signature"), "{}", context);
+    assert!(!context.contains("token_expiry_is_enforced"), "{}", context);
+}
+
+#[test]
+fn session_start_with_nothing_to_recall_claims_nothing() {
+    let sandbox = Sandbox::initialized();
+    let transcript = sandbox.project.join("session-b.jsonl");
+    let start = event_payload("SessionStart", "session-b", &transcript, json!({ "source": "startup" }));
+    let output = sandbox.run(&["hook-event", "session-start"], Some(&start));
+    assert_passed(&output, "empty session start");
+    assert_eq!(stdout(&output).trim(), "");
+
+    // Memory captured since then is recalled on the next start of the same session
+    sandbox.ingest("transcript_basic.jsonl");
+    let resume = event_payload("SessionStart", "session-b", &transcript, json!({ "source": "resume" }));
+    let output = sandbox.run(&["hook-event", "session-start"], Some(&resume));
+    assert_passed(&output, "resume");
+    assert!(additional_context(&output).contains("JWT signature"));
+}
+
+#[test]
+fn pre_compact_captures_the_transcript() {
+    let sandbox = Sandbox::initialized();
+    let transcript = sandbox.write("session-a.jsonl", &fixture("transcript_basic.jsonl"));
+    let payload = event_payload("PreCompact", "session-a", &transcript, json!({ "trigger": "auto" }));
+
+    let output = sandbox.run(&["hook-event", "pre-compact"], Some(&payload));
+    assert_passed(&output, "pre compact");
+
+    let output = sandbox.run(&["sessions", "show", "session-a"], None);
+    assert!(stdout(&output).contains("JWT signature"), "{}", stdout(&output));
+}
+
+#[test]
+fn subagent_stop_captures_the_subagent_thread() {
+    let sandbox = Sandbox::with_history();
+    let transcript = sandbox.project.join("transcript_basic.jsonl");
+    let agent_transcript = sandbox.write("agent-1.jsonl", &fixture("transcript_subagent.jsonl"));
+    let payload = event_payload("SubagentStop", "session-a", &transcript, json!({
+        "stop_hook_active": false,
+        "agent_id": "agent-1",
+        "agent_transcript_path": agent_transcript,
+    }));
+
+    let output = sandbox.run(&["hook-event", "subagent_stop"], Some(&payload));
+    assert_passed(&output, "subagent stop");
+
+    let output = sandbox.run(&["search", "refresh tokens rotated"], None);
+    let text = stdout(&output);
+    assert!(text.contains("SessionStore::rotate"), "{}", text);
+    assert!(text.contains("subagent"), "{}", text);
+}
+
+#[test]
+fn session_end_writes_the_digest() {
+    let sandbox = Sandbox::initialized();
+    let transcript = sandbox.write("session-a.jsonl", &fixture("transcript_basic.jsonl"));
+    let payload = event_payload("SessionEnd", "session-a", &transcript, json!({ "reason": "prompt_input_exit" }));
+
+    let output = sandbox.run(&["hook-event", "session-end"], Some(&payload));
+    assert_passed(&output, "session end");
+
+    let output = sandbox.run(&["sessions", "show", "session-a"], None);
+    assert!(stdout(&output).contains("📝 Digest"), "{}", stdout(&output));
+}
+
+#[test]
+fn lifecycle_events_fail_open() {
+    let sandbox = Sandbox::with_history();
+
+    for event in ["session-start", "pre-compact", "subagent-stop", "session-end"] {
+        for input in ["", "not json", "{\"session_id\": 7}", &fixture("hooks/user_prompt_submit.json")] {
+            let output = sandbox.run(&["hook-event", event], Some(input));
+            assert_passed(&output, input);
+            assert_eq!(stdout(&output).trim(), "", "{} printed output for {:?}", event, input);
+        }
+    }
+}
+
+#[test]
+fn init_registers_the_binary_for_every_event() {
+    let sandbox = Sandbox::initialized();
+    let settings: Value = serde_json::from_str(
+        &std::fs::read_to_string(sandbox.project.join(".claude/settings.local.json")).unwrap(),
+    )
+    .unwrap();
+
+    for (event, arg) in [
+        ("UserPromptSubmit", "user-prompt-submit"),
+        ("Stop", "stop"),
+        ("SessionStart", "session-start"),
+        ("PreCompact", "pre-compact"),
+        ("SubagentStop", "subagent-stop"),
+        ("SessionEnd", "session-end"),
+    ] {
+        let command = settings["hooks"][event][0]["hooks"][0]["command"].as_str().unwrap();
+        assert!(command.ends_with(&format!("off-context hook-event {}", arg)), "{}: {}", event, command);
+    }
+    assert!(!sandbox.home.join(".config/claude/hooks").exists());
+}
+
+#[test]
+fn stop_is_captured_natively_and_logged() {
+    let sandbox = Sandbox::initialized();
+    let transcript = sandbox.write("session-a.jsonl", &fixture("transcript_basic.jsonl"));
+    let payload = event_payload("Stop", "session-a", &transcript, json!({ "stop_hook_active": false }));
+
+    let output = sandbox.run(&["hook-event", "stop"], Some(&payload));
+    assert_passed(&output, "stop");
+    let output = sandbox.run(&["hook-event", "stop"], Some("not json"));
+    assert_passed(&output, "not json");
+
+    let output = sandbox.run(&["sessions", "show", "session-a"], None);
+    assert!(stdout(&output).contains("JWT signature"), "{}", stdout(&output));
+
+    let logs: String = std::fs::read_dir(sandbox.home.join(".off-context/logs")).unwrap()
+        .flatten()
+        .map(|entry| std::fs::read_to_string(entry.path()).unwrap())
+        .collect();
+    let lines: Vec<Value> = logs
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap_or_else(|e| panic!("log line is not JSON ({}): {}", e, line)))
+        .collect();
+    assert!(lines.iter().any(|l| l["fields"]["message"] == "Hook handled" && l["fields"]["event"] == "Stop"));
+    assert!(lines.iter().any(|l| l["level"] == "WARN" && l["fields"]["message"] == "Hook failed, passing through"));
+}
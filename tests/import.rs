@@ -0,0 +1,32 @@
+//! `import` picks up Claude Code's JSONL transcripts and, being checkpointed, adds
+//! nothing when run again over the same files.
+
+mod common;
+
+use common::{fixture, stdout, Sandbox};
+
+#[test]
+fn reimporting_the_same_transcripts_adds_nothing() {
+    let sandbox = Sandbox::initialized();
+    // Named after their session, as under ~/.claude/projects
+    for (session, name) in [
+        ("4b1f7c2e-session-a", "transcript_basic.jsonl"),
+        ("9d3e0a51-session-c", "transcript_rust.jsonl"),
+    ] {
+        sandbox.write(format!("claude/projects/-work-app/{}.jsonl", session), &fixture(name));
+    }
+    let import = || {
+        let output = sandbox.run(&["import", "--path", "claude"], None);
+        assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+        stdout(&output)
+    };
+
+    let first = import();
+    assert!(first.contains("Found 2 potential transcript files"), "{}", first);
+    assert!(!first.contains("Total conversations imported: 0"), "{}", first);
+    let total = first.lines().find(|line| line.contains("Total conversations in database")).unwrap().to_string();
+
+    let second = import();
+    assert!(second.contains("Total conversations imported: 0"), "{}", second);
+    assert!(second.contains(&total), "{}", second);
+}
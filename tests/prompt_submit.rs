@@ -0,0 +1,165 @@
+//! UserPromptSubmit hook output, driven by recorded hook payloads.
+
+mod common;
+
+use common::{fixture, stdout, Sandbox};
+use serde_json::Value;
+
+fn parse(output: &str) -> Value {
+    serde_json::from_str(output.trim()).unwrap_or_else(|e| panic!("not JSON ({}): {:?}", e, output))
+}
+
+#[test]
+fn inject_prompt_emits_additional_context() {
+    let sandbox = Sandbox::with_history();
+    let payload = fixture("hooks/user_prompt_submit.json");
+
+    let output = sandbox.run(&["inject-prompt"], Some(&payload));
+    assert!(output.status.success());
+
+    let json = parse(&stdout(&output));
+    assert_eq!(json["continue"], true);
+    assert_eq!(json["suppressOutput"], true);
+    assert_eq!(json["hookSpecificOutput"]["hookEventName"], "UserPromptSubmit");
+
+    let context = json["hookSpecificOutput"]["additionalContext"].as_str().unwrap();
+    assert!(context.starts_with("[PREV: "), "{}", context);
+    assert!(context.contains("JWT signature"), "{}", context);
+    // The prompt itself is left to Claude Code, not echoed back
+    assert!(!context.contains("rejected after deploy"), "{}", context);
+}
+
+#[test]
+fn inject_prompt_injects_once_per_session() {
+    let sandbox = Sandbox::with_history();
+    let payload = fixture("hooks/user_prompt_submit.json");
+
+    assert!(!stdout(&sandbox.run(&["inject-prompt"], Some(&payload))).trim().is_empty());
+
+    let output = sandbox.run(&["inject-prompt"], Some(&payload));
+    assert!(output.status.success());
+    assert_eq!(stdout(&output).trim(), "");
+}
+
+#[test]
+fn reinjection_skips_conversations_already_injected() {
+    let sandbox = Sandbox::with_history();
+    sandbox.edit_config(&[("session_state_ttl_days = 30", "session_state_ttl_days = 30\nreinject_after_turns = 1")]);
+    let payload = fixture("hooks/user_prompt_submit.json");
+    let session_state = || -> Value {
+        let path = sandbox.project.join(".off-context").join("session_state.json");
+        parse(&std::fs::read_to_string(path).unwrap())["session-b"].clone()
+    };
+
+    let first = parse(&stdout(&sandbox.run(&["inject-prompt"], Some(&payload))));
+    let context = first["hookSpecificOutput"]["additionalContext"].as_str().unwrap();
+    assert!(context.contains("JWT signature"), "{}", context);
+    let injected = session_state()["injected_conversations"].clone();
+    assert!(!injected.as_array().unwrap().is_empty());
+
+    // Due again, but everything relevant is already in the session
+    let output = sandbox.run(&["inject-prompt"], Some(&payload));
+    assert!(output.status.success());
+    assert_eq!(stdout(&output).trim(), "");
+    assert_eq!(session_state()["injected_conversations"], injected);
+    assert_eq!(session_state()["injection_count"], 1);
+}
+
+#[test]
+fn inject_prompt_is_silent_without_memory() {
+    let payload = fixture("hooks/user_prompt_submit.json");
+
+    // Not an off-context project
+    let output = Sandbox::new().run(&["inject-prompt"], Some(&payload));
+    assert!(output.status.success());
+    assert_eq!(stdout(&output).trim(), "");
+
+    // Initialized, but nothing captured yet
+    let output = Sandbox::initialized().run(&["inject-prompt"], Some(&payload));
+    assert!(output.status.success());
+    assert_eq!(stdout(&output).trim(), "");
+}
+
+#[test]
+fn inject_prompt_ignores_empty_prompts() {
+    let sandbox = Sandbox::with_history();
+    let output = sandbox.run(&["inject-prompt"], Some(&fixture("hooks/user_prompt_submit_empty.json")));
+    assert!(output.status.success());
+    assert_eq!(stdout(&output).trim(), "");
+}
+
+#[test]
+fn inject_prompt_respects_auto_inject() {
+    let sandbox = Sandbox::with_history();
+    sandbox.edit_config(&[("auto_inject = true", "auto_inject = false")]);
+
+    let output = sandbox.run(&["inject-prompt"], Some(&fixture("hooks/user_prompt_submit.json")));
+    assert!(output.status.success());
+    assert_eq!(stdout(&output).trim(), "");
+}
+
+#[test]
+fn smart_inject_emits_previous_session_as_additional_context() {
+    let sandbox = Sandbox::with_history();
+
+    let output = sandbox.run(&["smart-inject"], Some(&fixture("hooks/user_prompt_submit.json")));
+    assert!(output.status.success());
+
+    let json = parse(&stdout(&output));
+    let context = json["hookSpecificOutput"]["additionalContext"].as_str().unwrap();
+    assert!(context.starts_with("[INSTRUCTION]"), "{}", context);
+    assert!(context.contains("pending"), "{}", context);
+    assert!(json.get("prompt").is_none());
+}
+
+#[test]
+fn legacy_output_rewrites_the_prompt() {
+    let sandbox = Sandbox::with_history();
+    sandbox.edit_config(&[(
+        "prompt_output = \"additional_context\"",
+        "prompt_output = \"legacy\"",
+    )]);
+    let payload = fixture("hooks/user_prompt_submit.json");
+
+    let text = stdout(&sandbox.run(&["inject-prompt"], Some(&payload)));
+    assert!(text.starts_with("[PREV: "), "{}", text);
+    assert!(text.trim_end().ends_with("\n\nWhy are login tokens being rejected after deploy?"), "{}", text);
+
+    // Later prompts in the same session pass through unchanged
+    let text = stdout(&sandbox.run(&["inject-prompt"], Some(&payload)));
+    assert_eq!(text.trim(), "Why are login tokens being rejected after deploy?");
+}
+
+#[test]
+fn legacy_smart_inject_rewrites_the_prompt_field() {
+    let sandbox = Sandbox::with_history();
+    sandbox.edit_config(&[(
+        "prompt_output = \"additional_context\"",
+        "prompt_output = \"legacy\"",
+    )]);
+
+    let json = parse(&stdout(&sandbox.run(&["smart-inject"], Some(&fixture("hooks/user_prompt_submit.json")))));
+    let prompt = json["prompt"].as_str().unwrap();
+    assert!(prompt.starts_with("[INSTRUCTION]\n"), "{}", prompt);
+    assert!(prompt.ends_with("[/INSTRUCTION]\n\nWhy are login tokens being rejected after deploy?"), "{}", prompt);
+    assert_eq!(json["session_id"], "session-b");
+}
+
+#[test]
+fn digests_stand_in_for_their_exchanges() {
+    let sandbox = Sandbox::with_history();
+    let payload = fixture("hooks/user_prompt_submit.json");
+
+    let json = parse(&stdout(&sandbox.run(&["inject-prompt"], Some(&payload))));
+    let context = json["hookSpecificOutput"]["additionalContext"].as_str().unwrap();
+    assert!(context.starts_with("[PREV: Session 2026-09-01 (2 exchanges)"), "{}", context);
+    assert!(!context.contains("U:\""), "{}", context);
+
+    // Without digests the same exchanges go in raw
+    let sandbox = Sandbox::with_history();
+    sandbox.edit_config(&[("[summarization]\nenabled = true", "[summarization]\nenabled = false")]);
+    let json = parse(&stdout(&sandbox.run(&["inject-prompt"], Some(&payload))));
+    let context = json["hookSpecificOutput"]["additionalContext"].as_str().unwrap();
+    assert!(context.contains("U:\"How does the auth module validate login tokens?\""), "{}", context);
+    assert!(!context.contains("Session 2026-09-01"), "{}", context);
+}
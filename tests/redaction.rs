@@ -0,0 +1,56 @@
+//! Secrets are scrubbed before anything is stored, and `scan` cleans up history
+//! captured before that.
+
+mod common;
+
+use common::{stdout, Sandbox};
+
+const SECRETS: [&str; 3] = ["d3pl0yT0k3nVa1ue", "ops@example.com", "r3g1stryS3ss10nK3yXyZ"];
+
+#[test]
+fn secrets_are_redacted_at_capture_and_code_is_not() {
+    let sandbox = Sandbox::initialized();
+    sandbox.ingest("transcript_secrets.jsonl");
+
+    let stored = sandbox.segments();
+    for secret in SECRETS {
+        assert!(!stored.contains(secret), "{} was stored", secret);
+    }
+    assert!(stored.contains("DEPLOY_TOKEN=[REDACTED:secret-assignment]"), "{}", stored);
+    assert!(stored.contains("let token = parse_token(raw)?;"), "{}", stored);
+    assert!(stored.contains("password: &str"), "{}", stored);
+
+    let output = sandbox.run_ok(&["scan", "--dry-run"], &[]);
+    assert!(stdout(&output).contains("No secrets found"), "{}", stdout(&output));
+}
+
+#[test]
+fn scan_reports_then_redacts_stored_history() {
+    let sandbox = Sandbox::initialized();
+    sandbox.edit_config(&[("[redaction]\nenabled = true", "[redaction]\nenabled = false")]);
+    sandbox.ingest("transcript_secrets.jsonl");
+    sandbox.edit_config(&[("[redaction]\nenabled = false", "[redaction]\nenabled = true")]);
+
+    let output = sandbox.run_ok(&["scan", "--dry-run"], &[]);
+    let text = stdout(&output);
+    assert!(text.contains("Scanned 2 conversations"), "{}", text);
+    assert!(text.contains("Conversations with findings: 1"), "{}", text);
+    // The session digest quotes the answer too
+    assert!(text.contains("Session digests with findings: 1"), "{}", text);
+    for line in ["• bearer-token: 2", "• email: 1", "• secret-assignment: 1"] {
+        assert!(text.contains(line), "{}", text);
+    }
+    assert!(text.contains("Dry run"), "{}", text);
+    assert!(sandbox.segments().contains(SECRETS[0]));
+
+    let output = sandbox.run_ok(&["scan"], &[]);
+    assert!(stdout(&output).contains("Redacted 4 findings in 1 conversations and 1 digests"), "{}", stdout(&output));
+    let stored = sandbox.segments();
+    for secret in SECRETS {
+        assert!(!stored.contains(secret), "{} survived the scan", secret);
+    }
+
+    let output = sandbox.run_ok(&["search", "deploy", "--limit", "10"], &[]);
+    assert!(stdout(&output).contains("[REDACTED:secret-assignment]"), "{}", stdout(&output));
+    assert!(stdout(&sandbox.run_ok(&["scan"], &[])).contains("No secrets found"));
+}
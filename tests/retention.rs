@@ -0,0 +1,20 @@
+//! Automatic pruning after a hook run, and what it leaves on disk.
+
+mod common;
+
+use common::{stdout, Sandbox};
+
+#[test]
+fn hook_prunes_over_the_limit_and_compacts() {
+    let sandbox = Sandbox::initialized();
+    sandbox.edit_config(&[("keep_pinned = true", "keep_pinned = true\nmax_conversations = 2")]);
+
+    sandbox.ingest("transcript_basic.jsonl");
+    sandbox.ingest("transcript_rust.jsonl");
+
+    let list = stdout(&sandbox.run(&["sessions", "list"], None));
+    assert!(list.contains("📊 1 session\n"), "{}", list);
+    // The older session is gone from the segment files, not just tombstoned
+    assert!(!sandbox.segments().contains("JWT signature"));
+    assert!(sandbox.segments().contains("borrow checker"));
+}
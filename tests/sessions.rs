@@ -0,0 +1,93 @@
+//! Sessions from the CLI and the admin API: listing, prefix lookup and deletion,
+//! and what deletion leaves on disk.
+
+mod common;
+
+use common::{stdout, Admin, Sandbox};
+
+#[test]
+fn cli_lists_shows_and_deletes_by_prefix() {
+    let sandbox = Sandbox::with_sessions();
+
+    let list = stdout(&sandbox.run(&["sessions", "list"], None));
+    assert!(list.contains("📊 2 sessions"), "{}", list);
+    // Most recently active first
+    assert!(list.find("session-c").unwrap() < list.find("session-a").unwrap(), "{}", list);
+
+    let show = sandbox.run(&["sessions", "show", "session-a"], None);
+    assert!(stdout(&show).contains("validate login tokens"), "{}", stdout(&show));
+
+    let ambiguous = sandbox.run(&["sessions", "show", "session-"], None);
+    assert!(!ambiguous.status.success());
+    assert!(String::from_utf8_lossy(&ambiguous.stderr).contains("matches 2 sessions"));
+
+    let delete = sandbox.run(&["sessions", "delete", "session-a", "--yes"], None);
+    assert!(stdout(&delete).contains("Deleted session session-a (2 conversations)"), "{}", stdout(&delete));
+    assert!(!sandbox.segments().contains("JWT signature"));
+    assert!(sandbox.segments().contains("borrow checker"));
+
+    // Only one session left, so the shared prefix is unique now
+    let show = stdout(&sandbox.run(&["sessions", "show", "session-"], None));
+    assert!(show.contains("🆔 session-c"), "{}", show);
+}
+
+#[test]
+fn cli_delete_asks_first() {
+    let sandbox = Sandbox::with_sessions();
+    let output = sandbox.run(&["sessions", "delete", "session-a"], Some("n\n"));
+    assert!(stdout(&output).contains("Delete cancelled"), "{}", stdout(&output));
+    assert!(sandbox.segments().contains("JWT signature"));
+}
+
+#[test]
+fn api_lists_shows_and_deletes_sessions() {
+    let sandbox = Sandbox::with_sessions();
+    let admin = Admin::start(&sandbox);
+
+    let (status, sessions) = admin.request("GET", "/api/sessions");
+    assert_eq!(status, 200);
+    let ids: Vec<&str> = sessions.as_array().unwrap().iter().map(|s| s["id"].as_str().unwrap()).collect();
+    assert_eq!(ids, ["session-c", "session-a"]);
+    assert_eq!(sessions[1]["turn_count"], 2);
+    // The fixtures are long idle, so capturing them wrote their digests
+    assert_eq!(sessions[1]["has_digest"], true);
+
+    let (status, detail) = admin.request("GET", "/api/sessions/session-a");
+    assert_eq!(status, 200);
+    assert_eq!(detail["id"], "session-a");
+    assert_eq!(detail["conversations"].as_array().unwrap().len(), 2);
+    assert_eq!(detail["digest"]["session_id"], "session-a");
+
+    assert_eq!(admin.request("GET", "/api/sessions/nope").0, 404);
+    assert_eq!(admin.request("DELETE", "/api/sessions/nope").0, 404);
+    // Unique prefixes resolve like on the CLI
+    assert_eq!(admin.request("GET", "/api/sessions/session-c").1["id"], "session-c");
+    assert_eq!(admin.request("GET", "/api/sessions/session-").0, 409);
+    assert_eq!(admin.request("DELETE", "/api/sessions/session-").0, 409);
+
+    let (status, deleted) = admin.request("DELETE", "/api/sessions/session-a");
+    assert_eq!(status, 200);
+    assert_eq!(deleted["deleted_conversations"], 2);
+    assert_eq!(admin.request("GET", "/api/sessions/session-a").0, 404);
+    assert_eq!(admin.request("GET", "/api/sessions").1.as_array().unwrap().len(), 1);
+
+    // Compacted right away, so the text is gone from disk too, digest included
+    assert!(!sandbox.segments().contains("JWT signature"));
+    assert!(!sandbox.segments().contains("validate login tokens"));
+}
+
+#[test]
+fn api_refuses_changes_from_other_origins() {
+    let sandbox = Sandbox::with_sessions();
+    let admin = Admin::start(&sandbox);
+
+    assert_eq!(admin.request_from("DELETE", "/api/sessions/session-a", Some("https://evil.example")).0, 403);
+    assert_eq!(admin.request_from("POST", "/api/reset", Some("https://evil.example")).0, 403);
+    // Reading is harmless: without CORS headers the page cannot see the answer
+    assert_eq!(admin.request_from("GET", "/api/sessions", Some("https://evil.example")).0, 200);
+    assert_eq!(admin.request("GET", "/api/sessions").1.as_array().unwrap().len(), 2);
+
+    // The admin page itself is served from the same origin
+    let origin = format!("http://localhost:{}", admin.port);
+    assert_eq!(admin.request_from("DELETE", "/api/sessions/session-c", Some(&origin)).0, 200);
+}
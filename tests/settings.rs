@@ -0,0 +1,188 @@
+//! `init` and `clear` edit `.claude/settings.local.json` without disturbing hooks
+//! and settings that are not off-context's.
+
+mod common;
+
+use common::{fixture, stdout, Sandbox};
+use serde_json::Value;
+
+const SETTINGS: &str = ".claude/settings.local.json";
+
+fn settings(sandbox: &Sandbox) -> Value {
+    serde_json::from_str(&std::fs::read_to_string(sandbox.project.join(SETTINGS)).unwrap()).unwrap()
+}
+
+fn commands(settings: &Value, event: &str) -> Vec<String> {
+    settings["hooks"][event]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .flat_map(|group| group["hooks"].as_array().unwrap().clone())
+        .map(|hook| hook["command"].as_str().unwrap().to_string())
+        .collect()
+}
+
+/// Project with the team's settings in place, not yet initialized
+fn sandbox_with_team_settings() -> Sandbox {
+    let sandbox = Sandbox::new();
+    sandbox.write(SETTINGS, &fixture("settings/team_settings.json"));
+    sandbox
+}
+
+#[test]
+fn init_keeps_other_hooks_and_settings() {
+    let sandbox = sandbox_with_team_settings();
+    let output = sandbox.run(&["init"], None);
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+
+    let settings = settings(&sandbox);
+    let keys: Vec<&String> = settings.as_object().unwrap().keys().collect();
+    assert_eq!(keys, ["permissions", "hooks", "model"]);
+    assert_eq!(commands(&settings, "PreToolUse"), ["./scripts/guard.sh"]);
+
+    // The old wrapper is replaced, the formatter stays first
+    let stop = commands(&settings, "Stop");
+    assert_eq!(stop.len(), 2, "{:?}", stop);
+    assert_eq!(stop[0], "cargo fmt");
+    assert!(stop[1].ends_with("off-context hook-event stop"), "{:?}", stop);
+    let prompt = commands(&settings, "UserPromptSubmit");
+    assert_eq!(prompt.len(), 1, "{:?}", prompt);
+    assert!(prompt[0].ends_with("off-context hook-event user-prompt-submit"));
+}
+
+#[test]
+fn init_twice_does_not_duplicate_hooks() {
+    let sandbox = sandbox_with_team_settings();
+    sandbox.run(&["init"], None);
+    let first = std::fs::read_to_string(sandbox.project.join(SETTINGS)).unwrap();
+
+    let output = sandbox.run(&["init"], None);
+    assert!(stdout(&output).contains("hooks already configured"), "{}", stdout(&output));
+    assert_eq!(std::fs::read_to_string(sandbox.project.join(SETTINGS)).unwrap(), first);
+}
+
+#[test]
+fn init_dry_run_shows_the_diff_without_writing() {
+    let sandbox = sandbox_with_team_settings();
+    let output = sandbox.run(&["init", "--dry-run"], None);
+    assert!(output.status.success());
+
+    let text = stdout(&output);
+    assert!(text.contains("would update"), "{}", text);
+    assert!(text.lines().any(|l| l.starts_with('+') && l.contains("hook-event session-start")), "{}", text);
+    assert!(text.lines().any(|l| l.starts_with('-') && l.contains("hooks/Stop.sh")), "{}", text);
+    assert_eq!(
+        std::fs::read_to_string(sandbox.project.join(SETTINGS)).unwrap(),
+        fixture("settings/team_settings.json")
+    );
+    assert!(!sandbox.project.join(".off-context").exists());
+}
+
+#[test]
+fn clear_removes_only_off_context_hooks() {
+    let sandbox = sandbox_with_team_settings();
+    sandbox.run(&["init"], None);
+
+    let output = sandbox.run(&["clear", "--dry-run"], None);
+    assert!(stdout(&output).contains("would update"), "{}", stdout(&output));
+    assert_eq!(commands(&settings(&sandbox), "SessionStart").len(), 1);
+
+    let output = sandbox.run(&["clear"], None);
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+
+    let settings = settings(&sandbox);
+    assert_eq!(commands(&settings, "PreToolUse"), ["./scripts/guard.sh"]);
+    assert_eq!(commands(&settings, "Stop"), ["cargo fmt"]);
+    for event in ["UserPromptSubmit", "SessionStart", "PreCompact", "SubagentStop", "SessionEnd"] {
+        assert!(settings["hooks"].get(event).is_none(), "{} left behind", event);
+    }
+    assert_eq!(settings["model"], "opus");
+}
+
+#[test]
+fn invalid_settings_are_left_alone() {
+    let sandbox = Sandbox::new();
+    sandbox.write(SETTINGS, "{ \"hooks\": ");
+
+    let output = sandbox.run(&["init"], None);
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("not valid JSON"));
+    assert_eq!(std::fs::read_to_string(sandbox.project.join(SETTINGS)).unwrap(), "{ \"hooks\": ");
+}
+
+#[test]
+fn init_scopes_target_their_settings_files() {
+    let sandbox = Sandbox::new();
+
+    let output = sandbox.run(&["init", "--scope", "project"], None);
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    let shared: Value = serde_json::from_str(
+        &std::fs::read_to_string(sandbox.project.join(".claude/settings.json")).unwrap(),
+    )
+    .unwrap();
+    // Committed settings must not carry this machine's install path
+    assert_eq!(commands(&shared, "Stop"), ["off-context hook-event stop"]);
+    assert!(!sandbox.project.join(SETTINGS).exists());
+
+    let output = sandbox.run(&["init", "--scope", "user"], None);
+    assert!(stdout(&output).contains("also in"), "{}", stdout(&output));
+    let user: Value = serde_json::from_str(
+        &std::fs::read_to_string(sandbox.home.join(".claude/settings.json")).unwrap(),
+    )
+    .unwrap();
+    assert!(commands(&user, "Stop")[0].ends_with("off-context hook-event stop"));
+
+    let output = sandbox.run(&["status"], None);
+    let text = stdout(&output);
+    assert!(text.contains("Active (project, user)"), "{}", text);
+}
+
+#[test]
+fn clear_defaults_to_the_local_scope() {
+    let sandbox = sandbox_with_team_settings();
+    for scope in ["local", "project", "user"] {
+        let output = sandbox.run(&["init", "--scope", scope], None);
+        assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    }
+
+    let output = sandbox.run(&["clear"], None);
+    assert!(output.status.success());
+    let text = stdout(&sandbox.run(&["status"], None));
+    assert!(text.contains("Active (project, user)"), "{}", text);
+
+    let output = sandbox.run(&["clear", "--scope", "user"], None);
+    assert!(output.status.success());
+    let text = stdout(&sandbox.run(&["status"], None));
+    assert!(text.contains("Active (project)"), "{}", text);
+    assert_eq!(commands(&settings(&sandbox), "Stop"), ["cargo fmt"]);
+}
+
+#[test]
+fn clear_all_asks_before_touching_every_scope() {
+    let sandbox = sandbox_with_team_settings();
+    for scope in ["local", "project", "user"] {
+        sandbox.run(&["init", "--scope", scope], None);
+    }
+
+    let output = sandbox.run(&["clear", "--all"], Some("n\n"));
+    assert!(stdout(&output).contains("Clear cancelled"), "{}", stdout(&output));
+    let text = stdout(&sandbox.run(&["status"], None));
+    assert!(text.contains("Active (local, project, user)"), "{}", text);
+
+    let output = sandbox.run(&["clear", "--all", "--scope", "user"], None);
+    assert!(!output.status.success());
+
+    let output = sandbox.run(&["clear", "--all"], Some("y\n"));
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    let text = stdout(&sandbox.run(&["status"], None));
+    assert!(text.contains("Not configured"), "{}", text);
+    assert_eq!(commands(&settings(&sandbox), "Stop"), ["cargo fmt"]);
+}
+
+#[test]
+fn wrappers_are_not_written_into_shared_settings() {
+    let sandbox = Sandbox::new();
+    let output = sandbox.run(&["init", "--scope", "project", "--wrappers"], None);
+    assert!(!output.status.success());
+    assert!(!sandbox.project.join(".claude/settings.json").exists());
+}